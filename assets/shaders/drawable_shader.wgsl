@@ -1,11 +1,99 @@
 #import bevy_pbr::forward_io::VertexOutput
 
+struct PageTemplate {
+    colour: vec4<f32>,
+    kind: u32,
+    // lengths are in page widths
+    spacing: f32,
+    line_width: f32,
+    // height of the page over its width
+    aspect: f32,
+}
+
 @group(#{MATERIAL_BIND_GROUP}) @binding(0) var material_colour_texture: texture_2d<f32>;
 @group(#{MATERIAL_BIND_GROUP}) @binding(1) var material_colour_sampler: sampler;
+@group(#{MATERIAL_BIND_GROUP}) @binding(2) var<uniform> page_template: PageTemplate;
+
+// keep in sync with TemplateKind::shader_index and PageTemplate::marks
+const LINED: u32 = 1u;
+const GRID: u32 = 2u;
+const DOT_GRID: u32 = 3u;
+const ISOMETRIC: u32 = 4u;
+const MUSIC_STAFF: u32 = 5u;
+const STAFF_PERIOD: f32 = 9.0;
+const STAFF_MARGIN: f32 = 2.0;
+
+// antialiased coverage of a line `width` wide at `distance` from its centre
+fn line_coverage(distance: f32, width: f32, pixel: f32) -> f32 {
+    return clamp((width * 0.5 + pixel * 0.5 - abs(distance)) / pixel, 0.0, 1.0);
+}
+
+// distance to the closest multiple of `spacing`
+fn repeat_distance(x: f32, spacing: f32) -> f32 {
+    return x - spacing * round(x / spacing);
+}
+
+fn template_coverage(position: vec2<f32>, pixel: f32) -> f32 {
+    let spacing = page_template.spacing;
+    let width = page_template.line_width;
+    if spacing <= 0.0 {
+        return 0.0;
+    }
+
+    switch page_template.kind {
+        case LINED: {
+            return line_coverage(repeat_distance(position.y, spacing), width, pixel);
+        }
+        case GRID: {
+            return max(
+                line_coverage(repeat_distance(position.x, spacing), width, pixel),
+                line_coverage(repeat_distance(position.y, spacing), width, pixel),
+            );
+        }
+        case DOT_GRID: {
+            let offset = vec2(repeat_distance(position.x, spacing), repeat_distance(position.y, spacing));
+            return line_coverage(length(offset), width * 3.0, pixel);
+        }
+        case ISOMETRIC: {
+            let slope = sqrt(3.0) / 2.0;
+            let a = line_coverage(repeat_distance(position.x, spacing), width, pixel);
+            let b = line_coverage(repeat_distance(dot(position, vec2(0.5, slope)), spacing), width, pixel);
+            let c = line_coverage(repeat_distance(dot(position, vec2(-0.5, slope)), spacing), width, pixel);
+            return max(a, max(b, c));
+        }
+        case MUSIC_STAFF: {
+            let period = STAFF_PERIOD * spacing;
+            let y = position.y - STAFF_MARGIN * spacing;
+            let staff_top = period * floor(y / period);
+            // staffs that don't fit on the page are left out
+            if staff_top + STAFF_MARGIN * spacing + 4.0 * spacing > page_template.aspect {
+                return 0.0;
+            }
+            let local = y - staff_top;
+            let closest = clamp(round(local / spacing), 0.0, 4.0) * spacing;
+            return line_coverage(local - closest, width, pixel);
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
 
 @fragment
 fn fragment(
     mesh: VertexOutput,
 ) -> @location(0) vec4<f32> {
-    return textureSample(material_colour_texture, material_colour_sampler, mesh.uv);
+    let ink = textureSample(material_colour_texture, material_colour_sampler, mesh.uv);
+
+    let position = mesh.uv * vec2(1.0, page_template.aspect);
+    let pixel = max(max(fwidth(position.x), fwidth(position.y)), 1e-6);
+    let background = page_template.colour.a * template_coverage(position, pixel);
+
+    // ink over the template
+    let alpha = ink.a + background * (1.0 - ink.a);
+    if alpha <= 0.0 {
+        return vec4(0.0);
+    }
+    let colour = (ink.rgb * ink.a + page_template.colour.rgb * background * (1.0 - ink.a)) / alpha;
+    return vec4(colour, alpha);
 }
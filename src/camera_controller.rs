@@ -1 +1,87 @@
 // test camera controller so i can move around
+//
+// dragging with the middle mouse button pans and scrolling zooms, both scaled
+// by the camera sensitivity setting. selecting a notebook on the desk moves
+// the camera above it
+
+use bevy::{
+    input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit},
+    prelude::*,
+    ui::RelativeCursorPosition,
+};
+
+use crate::{config::Settings, notebook::desk::NotebookSelected};
+
+/// World units the camera pans per pixel the mouse moves
+const PAN_SPEED: f32 = 0.05;
+/// World units the camera moves forward per line scrolled
+const ZOOM_SPEED: f32 = 2.0;
+/// Closest the camera gets to the desk
+const MIN_HEIGHT: f32 = 5.0;
+const MAX_HEIGHT: f32 = 100.0;
+/// How quickly the camera moves above the selected notebook
+const FOCUS_SPEED: f32 = 6.0;
+
+/// Where on the desk the camera is moving above
+#[derive(Resource, Debug, Default)]
+pub struct CameraFocus(Option<Vec3>);
+
+pub fn camera_controller_system(
+    buttons: Res<ButtonInput<MouseButton>>,
+    motion: Res<AccumulatedMouseMotion>,
+    scroll: Res<AccumulatedMouseScroll>,
+    settings: Res<Settings>,
+    mut camera: Single<&mut Transform, With<Camera3d>>,
+    // scrolling over the GUI scrolls it instead
+    ui_query: Query<&RelativeCursorPosition>,
+    mut focus: ResMut<CameraFocus>,
+) {
+    let sensitivity = settings.camera_sensitivity;
+    if buttons.pressed(MouseButton::Middle) && motion.delta != Vec2::ZERO {
+        focus.0 = None;
+        // move the desk with the mouse, the camera looks down at it
+        let right = camera.right().with_y(0.0).normalize_or_zero();
+        let up = camera.up().with_y(0.0).normalize_or_zero();
+        let delta = motion.delta * PAN_SPEED * sensitivity;
+        camera.translation += -right * delta.x + up * delta.y;
+    }
+
+    let lines = match scroll.unit {
+        MouseScrollUnit::Line => scroll.delta.y,
+        MouseScrollUnit::Pixel => scroll.delta.y / 20.0,
+    };
+    if lines != 0.0 && !ui_query.iter().any(|cursor| cursor.cursor_over) {
+        let forward = camera.forward();
+        let target = camera.translation + forward * lines * ZOOM_SPEED * sensitivity;
+        if (MIN_HEIGHT..=MAX_HEIGHT).contains(&target.y) {
+            camera.translation = target;
+        }
+    }
+}
+
+/// moves the camera above the notebook that was selected, keeping its height
+pub fn camera_focus_system(
+    mut selected_reader: MessageReader<NotebookSelected>,
+    notebook_query: Query<&GlobalTransform>,
+    mut focus: ResMut<CameraFocus>,
+    mut camera: Single<&mut Transform, With<Camera3d>>,
+    time: Res<Time>,
+) {
+    if let Some(selected) = selected_reader.read().last() {
+        if let Ok(notebook) = notebook_query.get(selected.0) {
+            focus.0 = Some(notebook.translation());
+        }
+    }
+    let Some(target) = focus.0 else {
+        return;
+    };
+    // the camera looks straight down at the desk
+    let target = target.with_y(camera.translation.y);
+    camera
+        .translation
+        .smooth_nudge(&target, FOCUS_SPEED, time.delta_secs());
+    if camera.translation.distance_squared(target) < 1e-4 {
+        camera.translation = target;
+        focus.0 = None;
+    }
+}
@@ -0,0 +1,129 @@
+//! Command line arguments
+
+use std::path::PathBuf;
+
+use bevy::{app::AppExit, math::UVec2};
+use clap::{builder::RangedU64ValueParser, Parser, Subcommand};
+
+use crate::drawable::{document::render_document, MAX_RESOLUTION};
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "A notebook to draw in",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// notebook document to open once the notebook has loaded
+    pub notebook: Option<PathBuf>,
+    /// start drawing straight away instead of showing the main menu
+    #[arg(long)]
+    pub skip_menu: bool,
+    /// size of the window in logical pixels, like 1280x720
+    #[arg(long, value_parser = parse_window_size)]
+    pub window_size: Option<UVec2>,
+    /// width and height in pixels of pages that don't set their own
+    /// resolution, instead of the one in the settings
+    #[arg(long, value_parser = resolution_parser())]
+    pub resolution: Option<usize>,
+    /// don't start the remote debugging server
+    #[arg(long)]
+    pub no_remote: bool,
+    /// host a drawing session other apps can join, at an address like 0.0.0.0:7878
+    #[arg(long, conflicts_with = "join")]
+    pub host: Option<String>,
+    /// join the drawing session hosted at an address like 192.168.1.2:7878
+    #[arg(long)]
+    pub join: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// renders every page of a notebook document to PNGs, without a window or GPU
+    Render {
+        /// the notebook document to render
+        notebook: PathBuf,
+        /// folder to write `notebook_page_N.png` files to
+        #[arg(long, short, default_value = ".")]
+        out: PathBuf,
+        /// width and height of the rendered pages in pixels
+        #[arg(long, default_value_t = 1024, value_parser = resolution_parser())]
+        resolution: usize,
+        /// folder with the brush tip PNGs
+        #[arg(long, default_value = "assets/brushes")]
+        brushes: PathBuf,
+    },
+}
+
+/// accepts resolutions from 1 to [`MAX_RESOLUTION`]
+fn resolution_parser() -> RangedU64ValueParser<usize> {
+    RangedU64ValueParser::new().range(1..=MAX_RESOLUTION as u64)
+}
+
+/// parses `WIDTHxHEIGHT`
+fn parse_window_size(size: &str) -> Result<UVec2, String> {
+    let error = || format!("`{size}` isn't a size like 1280x720");
+    let (width, height) = size.split_once(['x', 'X']).ok_or_else(error)?;
+    let width = width.trim().parse().map_err(|_| error())?;
+    let height = height.trim().parse().map_err(|_| error())?;
+    if width == 0 || height == 0 {
+        return Err(error());
+    }
+    Ok(UVec2::new(width, height))
+}
+
+/// runs the `render` command
+pub fn render(notebook: PathBuf, out: PathBuf, resolution: usize, brushes: PathBuf) -> AppExit {
+    match render_document(&notebook, &out, resolution, &brushes) {
+        Ok(paths) => {
+            for path in paths {
+                println!("{}", path.display());
+            }
+            AppExit::Success
+        }
+        Err(error) => {
+            eprintln!("Failed to render {}: {error}", notebook.display());
+            AppExit::error()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::UVec2;
+    use clap::Parser;
+
+    use super::{parse_window_size, Cli, Command};
+
+    #[test]
+    fn window_sizes() {
+        assert_eq!(parse_window_size("1280x720"), Ok(UVec2::new(1280, 720)));
+        assert!(parse_window_size("1280").is_err());
+        assert!(parse_window_size("0x720").is_err());
+    }
+
+    #[test]
+    fn notebooks_and_subcommands() {
+        let cli =
+            Cli::try_parse_from(["elements", "book.ron", "--skip-menu", "--no-remote"]).unwrap();
+        assert_eq!(cli.notebook, Some("book.ron".into()));
+        assert!(cli.skip_menu && cli.no_remote);
+        assert!(cli.command.is_none());
+
+        let cli = Cli::try_parse_from(["elements", "render", "book.ron", "-o", "out"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Render { .. })));
+        assert!(cli.notebook.is_none());
+    }
+
+    #[test]
+    fn resolutions() {
+        let cli = Cli::try_parse_from(["elements", "--resolution", "2048"]).unwrap();
+        assert_eq!(cli.resolution, Some(2048));
+        assert!(Cli::try_parse_from(["elements", "--resolution", "0"]).is_err());
+        assert!(
+            Cli::try_parse_from(["elements", "render", "book.ron", "--resolution", "0"]).is_err()
+        );
+    }
+}
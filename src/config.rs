@@ -0,0 +1,462 @@
+//! Files kept in the user's config directory, the [`Settings`] and the recent
+//! notebooks
+
+use std::{
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    drawable::{
+        autosave::AUTOSAVE_INTERVAL,
+        document::{NotebookOpened, NotebookSaved},
+        DefaultResolution, Drawable, PaintSettings, MAX_RESOLUTION,
+    },
+    notebook::desk::ShelvedNotebookSaved,
+};
+
+/// Number of notebooks the main menu lists as recent
+const MAX_RECENT: usize = 8;
+
+/// Brush sizes the settings allow
+pub const BRUSH_SIZES: RangeInclusive<f32> = 0.005..=0.3;
+
+/// Seconds between autosaves the settings allow
+pub const AUTOSAVE_INTERVALS: RangeInclusive<f64> = 5.0..=300.0;
+
+/// Camera sensitivities the settings allow
+pub const CAMERA_SENSITIVITIES: RangeInclusive<f32> = 0.1..=3.0;
+
+/// `elements` in the user's config directory, or the working directory if
+/// there isn't one
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join("elements"))
+        .unwrap_or_default()
+}
+
+/// reads a RON config file, the default if it doesn't exist or is broken
+pub fn load_config<T: for<'a> Deserialize<'a> + Default>(path: &Path) -> T {
+    match fs::read_to_string(path) {
+        Ok(text) => ron::from_str(&text).unwrap_or_else(|error| {
+            warn!("Ignoring {}: {error}", path.display());
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+pub fn save_config<T: Serialize>(path: &Path, config: &T) {
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .map_err(|error| error.to_string())
+        .and_then(|()| {
+            ron::ser::to_string_pretty(config, ron::ser::PrettyConfig::default())
+                .map_err(|error| error.to_string())
+        })
+        .and_then(|text| fs::write(path, text).map_err(|error| error.to_string()));
+    if let Err(error) = result {
+        error!("Failed to write {}: {error}", path.display());
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Dark, Theme::Light];
+
+    pub fn background(&self) -> Color {
+        match self {
+            Theme::Dark => Color::srgb(0.17, 0.17, 0.19),
+            Theme::Light => Color::srgb(0.85, 0.85, 0.82),
+        }
+    }
+
+    /// the colours of buttons when they're idle, hovered and pressed
+    pub fn button_colours(&self) -> [Color; 3] {
+        match self {
+            Theme::Dark => [
+                Color::srgb(0.15, 0.15, 0.15),
+                Color::srgb(0.25, 0.25, 0.25),
+                Color::srgb(0.35, 0.25, 0.3),
+            ],
+            Theme::Light => [
+                Color::srgb(0.45, 0.45, 0.5),
+                Color::srgb(0.55, 0.55, 0.6),
+                Color::srgb(0.6, 0.45, 0.55),
+            ],
+        }
+    }
+}
+
+/// Things the keyboard does that can be bound to other keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    TurnPage,
+    SelectTool,
+    TextTool,
+    BrushEngine,
+    PageTemplate,
+    Ruler,
+    RotateRulerLeft,
+    RotateRulerRight,
+    Snapping,
+    Recolour,
+    Delete,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 11] = [
+        KeyAction::TurnPage,
+        KeyAction::SelectTool,
+        KeyAction::TextTool,
+        KeyAction::BrushEngine,
+        KeyAction::PageTemplate,
+        KeyAction::Ruler,
+        KeyAction::RotateRulerLeft,
+        KeyAction::RotateRulerRight,
+        KeyAction::Snapping,
+        KeyAction::Recolour,
+        KeyAction::Delete,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyAction::TurnPage => "Turn page",
+            KeyAction::SelectTool => "Select tool",
+            KeyAction::TextTool => "Text tool",
+            KeyAction::BrushEngine => "Brush engine",
+            KeyAction::PageTemplate => "Page template",
+            KeyAction::Ruler => "Ruler",
+            KeyAction::RotateRulerLeft => "Rotate ruler left",
+            KeyAction::RotateRulerRight => "Rotate ruler right",
+            KeyAction::Snapping => "Snapping",
+            KeyAction::Recolour => "Recolour",
+            KeyAction::Delete => "Delete",
+        }
+    }
+}
+
+/// The keys for each [`KeyAction`], the number keys, arrows and shortcuts with
+/// Ctrl are fixed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub turn_page: KeyCode,
+    pub select_tool: KeyCode,
+    pub text_tool: KeyCode,
+    pub brush_engine: KeyCode,
+    pub page_template: KeyCode,
+    pub ruler: KeyCode,
+    pub rotate_ruler_left: KeyCode,
+    pub rotate_ruler_right: KeyCode,
+    pub snapping: KeyCode,
+    pub recolour: KeyCode,
+    pub delete: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            turn_page: KeyCode::Space,
+            select_tool: KeyCode::KeyS,
+            text_tool: KeyCode::KeyT,
+            brush_engine: KeyCode::KeyB,
+            page_template: KeyCode::KeyG,
+            ruler: KeyCode::KeyR,
+            rotate_ruler_left: KeyCode::KeyQ,
+            rotate_ruler_right: KeyCode::KeyE,
+            snapping: KeyCode::KeyN,
+            recolour: KeyCode::KeyC,
+            delete: KeyCode::Delete,
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn key(&self, action: KeyAction) -> KeyCode {
+        match action {
+            KeyAction::TurnPage => self.turn_page,
+            KeyAction::SelectTool => self.select_tool,
+            KeyAction::TextTool => self.text_tool,
+            KeyAction::BrushEngine => self.brush_engine,
+            KeyAction::PageTemplate => self.page_template,
+            KeyAction::Ruler => self.ruler,
+            KeyAction::RotateRulerLeft => self.rotate_ruler_left,
+            KeyAction::RotateRulerRight => self.rotate_ruler_right,
+            KeyAction::Snapping => self.snapping,
+            KeyAction::Recolour => self.recolour,
+            KeyAction::Delete => self.delete,
+        }
+    }
+
+    pub fn key_mut(&mut self, action: KeyAction) -> &mut KeyCode {
+        match action {
+            KeyAction::TurnPage => &mut self.turn_page,
+            KeyAction::SelectTool => &mut self.select_tool,
+            KeyAction::TextTool => &mut self.text_tool,
+            KeyAction::BrushEngine => &mut self.brush_engine,
+            KeyAction::PageTemplate => &mut self.page_template,
+            KeyAction::Ruler => &mut self.ruler,
+            KeyAction::RotateRulerLeft => &mut self.rotate_ruler_left,
+            KeyAction::RotateRulerRight => &mut self.rotate_ruler_right,
+            KeyAction::Snapping => &mut self.snapping,
+            KeyAction::Recolour => &mut self.recolour,
+            KeyAction::Delete => &mut self.delete,
+        }
+    }
+
+    /// binds `action` to `key`, an action that was bound to `key` gets the
+    /// old key of `action` so no key does two things
+    pub fn rebind(&mut self, action: KeyAction, key: KeyCode) {
+        let old = self.key(action);
+        if let Some(other) = KeyAction::ALL
+            .into_iter()
+            .find(|other| *other != action && self.key(*other) == key)
+        {
+            *self.key_mut(other) = old;
+        }
+        *self.key_mut(action) = key;
+    }
+}
+
+/// User preferences, saved to `settings.ron` in the [config directory](config_dir)
+/// whenever they change
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// the brush when the app starts
+    pub default_brush: PaintSettings,
+    /// width and height in pixels of pages that don't set their own resolution
+    pub resolution: usize,
+    /// seconds between autosaves
+    pub autosave_interval: f64,
+    pub theme: Theme,
+    pub key_bindings: KeyBindings,
+    /// how fast the camera pans and zooms
+    pub camera_sensitivity: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_brush: PaintSettings::default(),
+            resolution: 1024,
+            autosave_interval: AUTOSAVE_INTERVAL,
+            theme: Theme::default(),
+            key_bindings: KeyBindings::default(),
+            camera_sensitivity: 1.0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        config_dir().join("settings.ron")
+    }
+
+    /// reads the settings at `path`, see [`Settings::bounded`]
+    pub fn load(path: &Path) -> Self {
+        load_config::<Self>(path).bounded()
+    }
+
+    /// clamps values a hand edited file could set out of range, ones that
+    /// can't be clamped fall back to their defaults
+    pub fn bounded(mut self) -> Self {
+        let default = Self::default();
+        if !(1..=MAX_RESOLUTION).contains(&self.resolution) {
+            warn!("Ignoring the page resolution {}", self.resolution);
+            self.resolution = default.resolution;
+        }
+        let radius = &mut self.default_brush.radius;
+        *radius = bounded(*radius, BRUSH_SIZES, default.default_brush.radius);
+        self.autosave_interval = bounded(
+            self.autosave_interval,
+            AUTOSAVE_INTERVALS,
+            default.autosave_interval,
+        );
+        self.camera_sensitivity = bounded(
+            self.camera_sensitivity,
+            CAMERA_SENSITIVITIES,
+            default.camera_sensitivity,
+        );
+        self
+    }
+}
+
+/// `value` clamped to `range`, or `default` if it isn't a number
+fn bounded<T: PartialOrd + Copy>(value: T, range: RangeInclusive<T>, default: T) -> T {
+    if value < *range.start() {
+        *range.start()
+    } else if value > *range.end() {
+        *range.end()
+    } else if range.contains(&value) {
+        value
+    } else {
+        default
+    }
+}
+
+/// applies the settings that changed, the resolution is applied at startup by
+/// `main` as it can be overridden from the command line
+fn apply_settings(
+    settings: Res<Settings>,
+    mut previous: Local<Option<Settings>>,
+    mut paint_settings: ResMut<PaintSettings>,
+    mut clear_colour: ResMut<ClearColor>,
+    mut default_resolution: ResMut<DefaultResolution>,
+    mut drawable_query: Query<&mut Drawable>,
+) {
+    let previous = previous.replace(settings.clone());
+    clear_colour.0 = settings.theme.background();
+    if previous
+        .as_ref()
+        .is_none_or(|previous| previous.default_brush != settings.default_brush)
+    {
+        *paint_settings = settings.default_brush.clone();
+    }
+    if previous
+        .as_ref()
+        .is_some_and(|previous| previous.resolution != settings.resolution)
+    {
+        default_resolution.0 = settings.resolution;
+        for mut drawable in &mut drawable_query {
+            *drawable = Drawable::new(settings.resolution);
+        }
+    }
+    if previous.is_some() {
+        save_config(&Settings::path(), &*settings);
+    }
+}
+
+/// The notebooks opened or saved most recently, newest first
+#[derive(Resource, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecentNotebooks {
+    pub paths: Vec<PathBuf>,
+}
+
+impl RecentNotebooks {
+    pub fn path() -> PathBuf {
+        config_dir().join("recent.ron")
+    }
+
+    /// moves `path` to the front of the list
+    pub fn push(&mut self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.paths.retain(|recent| *recent != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT);
+    }
+}
+
+fn update_recent_notebooks(
+    mut recent: ResMut<RecentNotebooks>,
+    mut opened_reader: MessageReader<NotebookOpened>,
+    mut saved_reader: MessageReader<NotebookSaved>,
+    mut shelved_saved_reader: MessageReader<ShelvedNotebookSaved>,
+) {
+    let paths: Vec<_> = opened_reader
+        .read()
+        .map(|opened| &opened.path)
+        .chain(saved_reader.read().map(|saved| &saved.path))
+        .chain(shelved_saved_reader.read().map(|saved| &saved.path))
+        .collect();
+    if paths.is_empty() {
+        return;
+    }
+    for path in paths {
+        recent.push(path);
+    }
+    save_config(&RecentNotebooks::path(), &*recent);
+}
+
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load(&Settings::path()));
+        app.insert_resource(load_config::<RecentNotebooks>(&RecentNotebooks::path()));
+        app.add_systems(
+            Update,
+            (
+                apply_settings.run_if(resource_changed::<Settings>),
+                update_recent_notebooks,
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use bevy::input::keyboard::KeyCode;
+
+    use super::{
+        load_config, save_config, KeyAction, KeyBindings, RecentNotebooks, Settings, MAX_RECENT,
+    };
+
+    #[test]
+    fn rebinding_swaps_keys() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(KeyAction::TurnPage, KeyCode::KeyR);
+        assert_eq!(bindings.turn_page, KeyCode::KeyR);
+        assert_eq!(bindings.ruler, KeyCode::Space);
+    }
+
+    #[test]
+    fn recent_notebooks_are_newest_first() {
+        let mut recent = RecentNotebooks::default();
+        for number in 0..MAX_RECENT + 2 {
+            recent.push(&PathBuf::from(format!("missing_{number}.ron")));
+        }
+        recent.push(&PathBuf::from("missing_5.ron"));
+        assert_eq!(recent.paths.len(), MAX_RECENT);
+        assert_eq!(recent.paths[0], PathBuf::from("missing_5.ron"));
+        assert_eq!(
+            recent.paths[1],
+            PathBuf::from(format!("missing_{}.ron", MAX_RECENT + 1))
+        );
+
+        let name = format!("elements_recent_{}.ron", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        save_config(&path, &recent);
+        assert_eq!(load_config::<RecentNotebooks>(&path), recent);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn out_of_range_settings_are_bounded() {
+        let name = format!("elements_settings_{}.ron", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let text = "(resolution: 0, autosave_interval: -1.0, camera_sensitivity: NaN)";
+        std::fs::write(&path, text).unwrap();
+        let settings = Settings::load(&path);
+        let default = Settings::default();
+        assert_eq!(settings.resolution, default.resolution);
+        assert_eq!(settings.autosave_interval, 5.0);
+        assert_eq!(settings.camera_sensitivity, default.camera_sensitivity);
+
+        let mut huge = Settings {
+            resolution: 100_000,
+            ..default
+        };
+        huge.default_brush.radius = 1e6;
+        save_config(&path, &huge);
+        let settings = Settings::load(&path);
+        let default = Settings::default();
+        assert_eq!(settings.resolution, default.resolution);
+        assert_eq!(settings.default_brush.radius, 0.3);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,485 @@
+//! Autosave and crash recovery
+//!
+//! While a notebook on the desk has unsaved changes it's written to a session
+//! file of its own in [`RECOVERY_FOLDER`], at most every
+//! [`Settings::autosave_interval`] seconds and on another thread so drawing
+//! doesn't stutter. Saving the notebook removes its file. If the app closes
+//! with unsaved changes the files are left behind, and the main menu offers to
+//! restore the notebooks of the newest session one by one on the next start.
+//! A restored session file is removed once its pages are autosaved or saved
+//! again.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+
+use super::{
+    document::{
+        notebook_contents, pages_changed, DocumentError, NotebookDocument, NotebookOpened,
+        NotebookPages, NotebookSaved,
+    },
+    pages::PageControl,
+    stroke::DrawableContent,
+    Cover, DrawableObject, SelectedDrawableFilter,
+};
+use crate::{
+    config::Settings,
+    notebook::desk::{SelectedNotebook, ShelvedNotebook, ShelvedNotebookSaved},
+};
+
+pub const RECOVERY_FOLDER: &str = "./temp/recovery";
+/// Default seconds between autosaves
+pub const AUTOSAVE_INTERVAL: f64 = 30.0;
+const SESSION_PREFIX: &str = "session_";
+
+#[derive(Resource, Debug)]
+pub struct Autosave {
+    folder: PathBuf,
+    /// the start of the session in milliseconds, its files are named after it
+    started: u128,
+    /// the notebooks with changes that aren't autosaved yet
+    dirty: HashSet<Entity>,
+    last_save: f64,
+    /// writes the session files, returns the notebooks it wrote
+    saving: Option<JoinHandle<Result<Vec<Entity>, DocumentError>>>,
+    /// the files of earlier sessions opened into notebooks, removed once the
+    /// notebook is written to this session's files or saved
+    restored: HashMap<Entity, PathBuf>,
+}
+
+impl Autosave {
+    /// autosaves to new session files in `folder`
+    pub fn new(folder: &Path) -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        Self {
+            folder: folder.to_path_buf(),
+            started,
+            dirty: HashSet::new(),
+            last_save: 0.0,
+            saving: None,
+            restored: HashMap::new(),
+        }
+    }
+
+    /// where `notebook` is autosaved in this session
+    pub fn path(&self, notebook: Entity) -> PathBuf {
+        self.folder.join(format!(
+            "{SESSION_PREFIX}{}_{}.ron",
+            self.started,
+            notebook.index()
+        ))
+    }
+
+    /// whether `path` is one of this session's files
+    fn is_own(&self, path: &Path) -> bool {
+        let own_prefix = format!("{SESSION_PREFIX}{}_", self.started);
+        path.parent() == Some(&self.folder)
+            && path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&own_prefix))
+    }
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self::new(Path::new(RECOVERY_FOLDER))
+    }
+}
+
+/// The notebooks of an earlier session that closed with unsaved changes, the
+/// ones that weren't restored yet
+#[derive(Resource, Debug)]
+pub struct RecoveredSession(pub Vec<PathBuf>);
+
+impl RecoveredSession {
+    /// the files of the newest session in `folder` that isn't the `current`
+    /// one
+    pub fn find(folder: &Path, current: &Autosave) -> Option<Self> {
+        let files: Vec<_> = session_files(folder)
+            .filter(|path| !current.is_own(path))
+            .filter_map(|path| Some((session_started(&path)?, path)))
+            .collect();
+        let newest = files.iter().map(|(started, _)| *started).max()?;
+        let mut paths: Vec<_> = files
+            .into_iter()
+            .filter(|(started, _)| *started == newest)
+            .map(|(_, path)| path)
+            .collect();
+        paths.sort();
+        Some(Self(paths))
+    }
+
+    /// deletes the session files
+    pub fn discard(&self) {
+        for path in &self.0 {
+            if let Err(error) = fs::remove_file(path) {
+                warn!("Failed to remove {}: {error}", path.display());
+            }
+        }
+    }
+}
+
+fn session_files(folder: &Path) -> impl Iterator<Item = PathBuf> {
+    fs::read_dir(folder)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_session_file(path))
+}
+
+fn is_session_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    name.is_some_and(|name| name.starts_with(SESSION_PREFIX) && name.ends_with(".ron"))
+}
+
+/// the start of the session a session file is from, in milliseconds
+fn session_started(path: &Path) -> Option<u128> {
+    let name = path.file_stem()?.to_str()?.strip_prefix(SESSION_PREFIX)?;
+    name.split('_').next()?.parse().ok()
+}
+
+/// removes the session file at `path` if it exists
+fn remove_session(path: &Path) {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => {
+            warn!("Failed to remove {}: {error}", path.display());
+        }
+        _ => {}
+    }
+}
+
+/// writes the pages to the session file at `path`
+fn write_session(
+    contents: &[DrawableContent],
+    cover: Option<&DrawableContent>,
+    path: &Path,
+) -> Result<(), DocumentError> {
+    let document = NotebookDocument::from_contents(contents)?.with_cover(cover)?;
+    // a crash while writing shouldn't leave a half written file behind
+    let partial = path.with_extension("ron.partial");
+    document.save(&partial)?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn autosave_system(
+    mut autosave: ResMut<Autosave>,
+    content_query: Query<Ref<DrawableContent>, SelectedDrawableFilter>,
+    selected_query: Query<Entity, With<SelectedNotebook>>,
+    shelved_query: Query<&ShelvedNotebook>,
+    children_query: Query<&Children>,
+    drawable_query: Query<(Entity, &DrawableContent, Has<Cover>), With<DrawableObject>>,
+    mut notebook_pages: ResMut<NotebookPages>,
+    settings: Res<Settings>,
+    mut opened_reader: MessageReader<NotebookOpened>,
+    mut saved_reader: MessageReader<NotebookSaved>,
+    mut shelved_saved_reader: MessageReader<ShelvedNotebookSaved>,
+    mut page_reader: MessageReader<PageControl>,
+    time: Res<Time>,
+) {
+    if let Some(saving) = autosave.saving.take_if(|saving| saving.is_finished()) {
+        match saving.join() {
+            Ok(Ok(written)) => {
+                debug!("Autosaved to {}", autosave.folder.display());
+                for notebook in written {
+                    if let Some(restored) = autosave.restored.remove(&notebook) {
+                        remove_session(&restored);
+                    }
+                }
+            }
+            Ok(Err(error)) => error!("Failed to autosave: {error}"),
+            Err(_) => error!("Autosave thread panicked"),
+        }
+    }
+
+    let changed = pages_changed(content_query, page_reader.read());
+    let saved = saved_reader.read().last();
+    let opened = opened_reader.read().last();
+    // only the selected notebook changes, the others keep their state
+    if let Some(selected) = selected_query.iter().next() {
+        if changed {
+            autosave.dirty.insert(selected);
+        }
+        // saved and opened pages are in a document, so these come after
+        if let Some(saved) = saved {
+            debug!(
+                "Saved to {}, the autosave isn't needed",
+                saved.path.display()
+            );
+            autosave.dirty.remove(&selected);
+            remove_session(&autosave.path(selected));
+            if let Some(restored) = autosave.restored.remove(&selected) {
+                remove_session(&restored);
+            }
+        }
+        if let Some(opened) = opened {
+            let is_session = opened.path.parent() == Some(&autosave.folder)
+                && !autosave.is_own(&opened.path)
+                && is_session_file(&opened.path);
+            // the pages of an opened document are saved, unless it's a
+            // restored session, they're only in that session's file until
+            // they're autosaved
+            if is_session {
+                autosave.dirty.insert(selected);
+                // saving shouldn't write over the old session's file
+                autosave.restored.insert(selected, opened.path.clone());
+                notebook_pages.path = None;
+            } else {
+                autosave.dirty.remove(&selected);
+            }
+        }
+    }
+
+    for saved in shelved_saved_reader.read() {
+        autosave.dirty.remove(&saved.notebook);
+        remove_session(&autosave.path(saved.notebook));
+        if let Some(restored) = autosave.restored.remove(&saved.notebook) {
+            remove_session(&restored);
+        }
+    }
+
+    let now = time.elapsed_secs_f64();
+    if autosave.dirty.is_empty()
+        || autosave.saving.is_some()
+        || now - autosave.last_save < settings.autosave_interval
+    {
+        return;
+    }
+    autosave.last_save = now;
+
+    let mut sessions = Vec::new();
+    for notebook in std::mem::take(&mut autosave.dirty) {
+        // the selected notebook's pages are in the resource, see `desk`
+        let pages = if selected_query.contains(notebook) {
+            &*notebook_pages
+        } else if let Ok(shelved) = shelved_query.get(notebook) {
+            shelved.pages()
+        } else {
+            continue;
+        };
+        let (contents, cover) =
+            notebook_contents(notebook, pages, &children_query, &drawable_query);
+        let blank = contents
+            .iter()
+            .chain(&cover)
+            .all(|content| content.strokes.is_empty() && content.raster.is_none());
+        let path = autosave.path(notebook);
+        if !blank || path.exists() {
+            sessions.push((notebook, contents, cover, path));
+        } else if let Some(restored) = autosave.restored.remove(&notebook) {
+            // a blank restored session has nothing worth keeping either
+            remove_session(&restored);
+        }
+    }
+    if sessions.is_empty() {
+        return;
+    }
+    autosave.saving = Some(thread::spawn(move || {
+        let mut written = Vec::new();
+        for (notebook, contents, cover, path) in sessions {
+            write_session(&contents, cover.as_ref(), &path)?;
+            written.push(notebook);
+        }
+        Ok(written)
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::Path, thread, time::Duration};
+
+    use bevy::prelude::*;
+
+    use super::{autosave_system, write_session, Autosave, RecoveredSession};
+    use crate::{
+        config::Settings,
+        drawable::{
+            document::{NotebookDocument, NotebookOpened, NotebookPages, NotebookSaved},
+            pages::PageControl,
+            stroke::{DrawableContent, Stroke, StrokeId},
+            DrawableObject, InSelectedNotebook, PaintSettings,
+        },
+        notebook::desk::{SelectedNotebook, ShelvedNotebook, ShelvedNotebookSaved},
+    };
+
+    fn drawn_page() -> DrawableContent {
+        let mut page = DrawableContent::new(Vec2::ONE);
+        page.push_painted(Stroke {
+            id: StrokeId(1),
+            points: Vec::new(),
+            paint_settings: PaintSettings::default(),
+            started_at: 0.0,
+        });
+        page
+    }
+
+    /// an app autosaving to `folder` with a selected notebook, and the notebook
+    fn autosave_app(folder: &Path, autosave_interval: f64) -> (App, Entity) {
+        let mut app = App::new();
+        app.insert_resource(Settings {
+            autosave_interval,
+            ..default()
+        })
+        .insert_resource(Autosave::new(folder))
+        .init_resource::<NotebookPages>()
+        .init_resource::<Time>()
+        .add_message::<NotebookOpened>()
+        .add_message::<NotebookSaved>()
+        .add_message::<ShelvedNotebookSaved>()
+        .add_message::<PageControl>()
+        .add_systems(Update, autosave_system);
+        let notebook = app.world_mut().spawn(SelectedNotebook).id();
+        app.world_mut().spawn((
+            DrawableObject,
+            InSelectedNotebook,
+            DrawableContent::new(Vec2::ONE),
+            ChildOf(notebook),
+        ));
+        (app, notebook)
+    }
+
+    /// updates `app` until `done`, for autosaves on another thread
+    fn update_until(app: &mut App, mut done: impl FnMut(&App) -> bool) {
+        for _ in 0..500 {
+            app.update();
+            if done(app) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("gave up waiting for the autosave");
+    }
+
+    #[test]
+    fn sessions_are_recovered_and_kept() {
+        let folder = std::env::temp_dir().join(format!("elements_recovery_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        let previous = Autosave::new(&folder);
+        let previous_path = previous.path(Entity::PLACEHOLDER);
+        write_session(&[DrawableContent::new(Vec2::ONE)], None, &previous_path).unwrap();
+        // session file names only differ by milliseconds
+        thread::sleep(Duration::from_millis(5));
+        let current = Autosave::new(&folder);
+
+        let recovered = RecoveredSession::find(&folder, &current).unwrap();
+        assert_eq!(recovered.0, vec![previous_path.clone()]);
+        let document = NotebookDocument::load(&recovered.0[0]).unwrap();
+        assert_eq!(document.pages.len(), 1);
+
+        // autosaving this session leaves the old one alone
+        write_session(&[], None, &current.path(Entity::PLACEHOLDER)).unwrap();
+        assert!(previous_path.exists());
+        let recovered = RecoveredSession::find(&folder, &current).unwrap();
+        assert_eq!(recovered.0, vec![previous_path.clone()]);
+
+        recovered.discard();
+        assert!(!previous_path.exists());
+        assert!(RecoveredSession::find(&folder, &current).is_none());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn every_notebook_of_the_newest_session_is_recovered() {
+        let folder =
+            std::env::temp_dir().join(format!("elements_notebooks_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        let oldest = Autosave::new(&folder).path(Entity::PLACEHOLDER);
+        write_session(&[], None, &oldest).unwrap();
+        thread::sleep(Duration::from_millis(5));
+        // a session with two notebooks on the desk
+        let newest = Autosave::new(&folder);
+        let mut world = World::new();
+        let notebooks = [world.spawn_empty().id(), world.spawn_empty().id()];
+        for notebook in notebooks {
+            write_session(&[], None, &newest.path(notebook)).unwrap();
+        }
+        thread::sleep(Duration::from_millis(5));
+        let current = Autosave::new(&folder);
+
+        let recovered = RecoveredSession::find(&folder, &current).unwrap();
+        assert_eq!(recovered.0.len(), 2);
+        for notebook in notebooks {
+            assert!(recovered.0.contains(&newest.path(notebook)));
+        }
+        recovered.discard();
+        assert!(notebooks
+            .iter()
+            .all(|notebook| !newest.path(*notebook).exists()));
+        // the older session is offered once the newer one is gone
+        let recovered = RecoveredSession::find(&folder, &current).unwrap();
+        assert_eq!(recovered.0, [oldest]);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn restored_sessions_are_removed_once_autosaved() {
+        let folder = std::env::temp_dir().join(format!("elements_restore_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        let previous = Autosave::new(&folder).path(Entity::PLACEHOLDER);
+        write_session(&[drawn_page()], None, &previous).unwrap();
+        thread::sleep(Duration::from_millis(5));
+
+        let (mut app, notebook) = autosave_app(&folder, 0.0);
+        app.world_mut().write_message(NotebookOpened {
+            path: previous.clone(),
+        });
+        // the page as loaded from the session
+        let mut pages = app.world_mut().query::<&mut DrawableContent>();
+        *pages.single_mut(app.world_mut()).unwrap().into_inner() = drawn_page();
+        update_until(&mut app, |_| !previous.exists());
+        let current = app.world().resource::<Autosave>().path(notebook);
+        assert_eq!(NotebookDocument::load(&current).unwrap().pages.len(), 1);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn shelved_notebooks_are_autosaved() {
+        let folder = std::env::temp_dir().join(format!("elements_shelved_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        let (mut app, first) = autosave_app(&folder, 30.0);
+        app.update();
+
+        // draw on the first notebook, then select another before it's autosaved
+        let mut pages = app
+            .world_mut()
+            .query_filtered::<&mut DrawableContent, With<InSelectedNotebook>>();
+        let page = pages.single_mut(app.world_mut()).unwrap();
+        *page.into_inner() = drawn_page();
+        app.update();
+        let second = app.world_mut().spawn(SelectedNotebook).id();
+        let mut first_pages = app.world_mut().query::<(Entity, &ChildOf)>();
+        let first_page = first_pages
+            .iter(app.world())
+            .find(|(_, parent)| parent.parent() == first)
+            .map(|(page, _)| page)
+            .unwrap();
+        app.world_mut()
+            .entity_mut(first)
+            .remove::<SelectedNotebook>()
+            .insert(ShelvedNotebook::default());
+        app.world_mut()
+            .entity_mut(first_page)
+            .remove::<InSelectedNotebook>();
+        app.world_mut().resource_mut::<Settings>().autosave_interval = 0.0;
+
+        let path = app.world().resource::<Autosave>().path(first);
+        update_until(&mut app, |_| path.exists());
+        let document = NotebookDocument::load(&path).unwrap();
+        assert_eq!(document.pages[0].strokes.len(), 1);
+        let second_path = app.world().resource::<Autosave>().path(second);
+        assert!(!second_path.exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
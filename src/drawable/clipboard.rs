@@ -0,0 +1,315 @@
+//! Copy, cut and paste of selections
+//!
+//! With a floating selection, Ctrl+C copies it and Ctrl+X cuts it. Ctrl+V
+//! pastes it as a new floating selection on the page under the cursor, which
+//! is also how content gets duplicated onto other pages. With the
+//! `system-clipboard` feature copied selections also go on the system
+//! clipboard as images, and images copied in other apps can be pasted.
+
+use bevy::prelude::*;
+
+use super::{
+    drawable::DrawableCursor,
+    paint::stamp::BrushTips,
+    selection::{FloatingSelection, Selection, SelectionShape},
+    stroke::DrawableContent,
+    tool::Tool,
+    DefaultResolution, PageFilter,
+};
+
+/// The internal clipboard
+#[derive(Resource, Debug, Default)]
+pub struct Clipboard {
+    /// the copied selection, its drawable is the one it was copied from
+    selection: Option<FloatingSelection>,
+    #[cfg(feature = "system-clipboard")]
+    system: system::SystemClipboard,
+}
+
+impl Clipboard {
+    /// copies `selection`, `_resolution` is the one of the system clipboard image
+    pub fn copy(
+        &mut self,
+        selection: &FloatingSelection,
+        _brush_tips: &BrushTips,
+        _resolution: usize,
+    ) {
+        self.selection = Some(selection.clone());
+        #[cfg(feature = "system-clipboard")]
+        if let Some(image) = system::selection_image(selection, _brush_tips, _resolution) {
+            self.system.write(image);
+        }
+    }
+
+    /// a copy of the clipboard floating on `drawable`, images from the system
+    /// clipboard are scaled as if they were drawn at `_resolution`
+    pub fn paste(
+        &mut self,
+        drawable: Entity,
+        _plane_scale: Vec2,
+        _resolution: usize,
+    ) -> Option<FloatingSelection> {
+        #[cfg(feature = "system-clipboard")]
+        if let Some(image) = self.system.read_new() {
+            let source_size = UVec2::splat(_resolution as u32);
+            return Some(FloatingSelection::from_image(
+                drawable,
+                image,
+                source_size,
+                _plane_scale,
+            ));
+        }
+
+        let mut pasted = self.selection.clone()?;
+        pasted.drawable = drawable;
+        Some(pasted)
+    }
+}
+
+/// whether Ctrl, or Cmd on macOS, is held
+pub(super) fn ctrl_pressed(keyboard_input: &ButtonInput<KeyCode>) -> bool {
+    keyboard_input.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ])
+}
+
+/// Copies, cuts and pastes with Ctrl+C, Ctrl+X and Ctrl+V, see the
+/// [module docs](self)
+#[allow(clippy::too_many_arguments)]
+pub(super) fn clipboard_system(
+    mut cursor: DrawableCursor,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut clipboard: ResMut<Clipboard>,
+    mut selection: ResMut<Selection>,
+    mut tool: ResMut<Tool>,
+    mut content_query: Query<&mut DrawableContent>,
+    drawable_query: Query<Entity, PageFilter>,
+    brush_tips: Res<BrushTips>,
+    default_resolution: Res<DefaultResolution>,
+) {
+    if !ctrl_pressed(&keyboard_input) {
+        return;
+    }
+
+    let copy = keyboard_input.just_pressed(KeyCode::KeyC);
+    let cut = keyboard_input.just_pressed(KeyCode::KeyX);
+    if copy || cut {
+        if let Some(floating) = &selection.floating {
+            clipboard.copy(floating, &brush_tips, default_resolution.0);
+        }
+        if cut {
+            if let Some(floating) = selection.discard() {
+                if let Ok(mut content) = content_query.get_mut(floating.drawable) {
+                    content.request_render();
+                }
+            }
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        // the page under the cursor, else the page with the selection, else the first page
+        let target = cursor
+            .hit()
+            .map(|hit| hit.drawable_object)
+            .or(selection
+                .floating
+                .as_ref()
+                .map(|floating| floating.drawable))
+            .or(drawable_query.iter().min());
+        let Some(target) = target else {
+            return;
+        };
+        let Ok(plane_scale) = content_query.get(target).map(|content| content.plane_scale) else {
+            return;
+        };
+        let Some(pasted) = clipboard.paste(target, plane_scale, default_resolution.0) else {
+            return;
+        };
+
+        selection.replace(pasted, &mut content_query);
+        if let Ok(mut content) = content_query.get_mut(target) {
+            content.request_render();
+        }
+        // the pasted selection can only be moved with the selection tool
+        if *tool == Tool::Pen {
+            *tool = Tool::Select(SelectionShape::default());
+        }
+    }
+}
+
+#[cfg(feature = "system-clipboard")]
+mod system {
+    use std::borrow::Cow;
+
+    use arboard::ImageData;
+    use bevy::prelude::*;
+    use image::RgbaImage;
+
+    use crate::drawable::{
+        create_drawable_image, paint::stamp::BrushTips, selection::FloatingSelection,
+    };
+
+    /// The system clipboard, opened when it's first used
+    #[derive(Default)]
+    pub(super) struct SystemClipboard {
+        clipboard: Option<arboard::Clipboard>,
+        /// the image last put on the clipboard, so pasting it uses the strokes instead
+        written: Option<RgbaImage>,
+    }
+
+    impl std::fmt::Debug for SystemClipboard {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("SystemClipboard").finish_non_exhaustive()
+        }
+    }
+
+    impl SystemClipboard {
+        fn clipboard(&mut self) -> Option<&mut arboard::Clipboard> {
+            if self.clipboard.is_none() {
+                match arboard::Clipboard::new() {
+                    Ok(clipboard) => self.clipboard = Some(clipboard),
+                    Err(error) => warn!("Couldn't open the system clipboard: {error}"),
+                }
+            }
+            self.clipboard.as_mut()
+        }
+
+        pub(super) fn write(&mut self, image: RgbaImage) {
+            let Some(clipboard) = self.clipboard() else {
+                return;
+            };
+            let data = ImageData {
+                width: image.width() as usize,
+                height: image.height() as usize,
+                bytes: Cow::Borrowed(image.as_raw()),
+            };
+            if let Err(error) = clipboard.set_image(data) {
+                warn!("Couldn't copy to the system clipboard: {error}");
+            }
+            self.written = Some(image);
+        }
+
+        /// the image on the system clipboard if it was copied somewhere else
+        pub(super) fn read_new(&mut self) -> Option<RgbaImage> {
+            let data = self.clipboard()?.get_image().ok()?;
+            let image = RgbaImage::from_raw(
+                data.width as u32,
+                data.height as u32,
+                data.bytes.into_owned(),
+            )?;
+            (self.written.as_ref() != Some(&image)).then_some(image)
+        }
+    }
+
+    /// the transformed selection cropped to its bounds
+    pub(super) fn selection_image(
+        selection: &FloatingSelection,
+        brush_tips: &BrushTips,
+        resolution: usize,
+    ) -> Option<RgbaImage> {
+        let mut image = create_drawable_image(resolution);
+        selection.render(&mut image, brush_tips);
+        let pixels = image.try_into_dynamic().ok()?.to_rgba8();
+
+        let size = Vec2::splat(resolution as f32);
+        let corners = selection.corners().map(|corner| corner * size);
+        let low = corners.iter().fold(Vec2::INFINITY, |a, b| a.min(*b));
+        let high = corners.iter().fold(Vec2::NEG_INFINITY, |a, b| a.max(*b));
+        let low = low.floor().max(Vec2::ZERO).as_uvec2();
+        let high = high.ceil().min(size).as_uvec2();
+        if low.x >= high.x || low.y >= high.y {
+            return None;
+        }
+        let size = high - low;
+        Some(image::imageops::crop_imm(&pixels, low.x, low.y, size.x, size.y).to_image())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use super::Clipboard;
+    use crate::drawable::{
+        paint::{stamp::BrushTips, PaintSettings},
+        selection::FloatingSelection,
+        stroke::{DrawableContent, Stroke, StrokePoint},
+    };
+
+    fn page_with_stroke() -> DrawableContent {
+        let mut content = DrawableContent::new(Vec2::ONE);
+        let stroke = Stroke {
+            id: content.next_stroke_id(),
+            points: [(0.2, 0.2), (0.3, 0.3)]
+                .into_iter()
+                .map(|(x, y)| StrokePoint {
+                    position: Vec2::new(x, y),
+                    pressure: 1.0,
+                    time: 0.0,
+                })
+                .collect(),
+            paint_settings: PaintSettings {
+                radius: 0.01,
+                ..default()
+            },
+            started_at: 0.0,
+        };
+        content.push_painted(stroke);
+        content
+    }
+
+    fn lift_all(content: &mut DrawableContent) -> FloatingSelection {
+        let page = [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y];
+        FloatingSelection::lift(content, Entity::PLACEHOLDER, &page).expect("the stroke is inside")
+    }
+
+    #[test]
+    fn nothing_is_pasted_before_copying() {
+        let mut clipboard = Clipboard::default();
+        assert!(clipboard
+            .paste(Entity::PLACEHOLDER, Vec2::ONE, 1024)
+            .is_none());
+    }
+
+    #[test]
+    fn pasting_onto_the_source_page_gives_new_ids() {
+        let mut content = page_with_stroke();
+        let mut clipboard = Clipboard::default();
+        let floating = lift_all(&mut content);
+        clipboard.copy(&floating, &BrushTips::default(), 1024);
+        // pasting puts the copied selection back first
+        floating.commit(&mut content);
+        for _ in 0..2 {
+            let pasted = clipboard
+                .paste(Entity::PLACEHOLDER, Vec2::ONE, 1024)
+                .expect("a selection was copied");
+            pasted.commit(&mut content);
+        }
+
+        let mut ids: Vec<_> = content.strokes.iter().map(|stroke| stroke.id).collect();
+        assert_eq!(ids.len(), 3);
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3, "stroke ids aren't unique");
+    }
+
+    #[test]
+    fn cut_selections_can_be_pasted_again() {
+        let mut content = page_with_stroke();
+        let mut clipboard = Clipboard::default();
+        let floating = lift_all(&mut content);
+        clipboard.copy(&floating, &BrushTips::default(), 1024);
+        // cutting drops the floating selection instead of committing it
+        drop(floating);
+        assert!(content.strokes.is_empty());
+
+        let pasted = clipboard
+            .paste(Entity::PLACEHOLDER, Vec2::ONE, 1024)
+            .unwrap();
+        pasted.commit(&mut content);
+        assert_eq!(content.strokes.len(), 1);
+    }
+}
@@ -0,0 +1,697 @@
+//! Notebook documents
+//!
+//! A notebook is saved as RON with the plane scale, template, strokes and
+//! raster layer of every page, the raster layer as a base64 PNG. Pages are in
+//! the order their drawables were spawned, the drawing on the cover is saved
+//! next to them. Documents can be opened in the app
+//! or rendered to PNGs without it with [`render_document`].
+
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bevy::prelude::*;
+use image::{ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    drawable_material::create_drawable_image,
+    metadata::PageMetadata,
+    pages::PageControl,
+    paint::{stamp::BrushTips, PaintImage, PaintSettings},
+    stroke::{DrawableContent, Stroke},
+    template::{PageTemplate, SetPageTemplate, TemplateKind, TemplateMark},
+    Cover, CoverFilter, DefaultResolution, Drawable, DrawableObject, PageFilter,
+    SelectedDrawableFilter,
+};
+
+/// Where the debug menu saves and opens the notebook
+pub const NOTEBOOK_DOCUMENT_PATH: &str = "./temp/notebook.ron";
+/// Where new notebooks are created, the main menu lists the notebooks in it
+pub const NOTEBOOKS_FOLDER: &str = "./notebooks";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotebookDocument {
+    pub pages: Vec<PageDocument>,
+    /// the drawing on the notebook's cover
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover: Option<PageDocument>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageDocument {
+    pub plane_scale: Vec2,
+    #[serde(default)]
+    pub template: PageTemplate,
+    #[serde(default)]
+    pub strokes: Vec<Stroke>,
+    /// the raster layer as a base64 PNG
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raster: Option<String>,
+    #[serde(default)]
+    pub metadata: PageMetadata,
+}
+
+#[derive(Debug, Error)]
+pub enum DocumentError {
+    #[error("could not access document: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write document: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("could not read document: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not encode or decode image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("raster layer is not valid base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("could not convert rendered page: {0}")]
+    Render(#[from] bevy::image::IntoDynamicImageError),
+}
+
+impl PageDocument {
+    pub fn from_content(content: &DrawableContent) -> Result<Self, DocumentError> {
+        let raster = match &content.raster {
+            Some(raster) => {
+                let mut png = Vec::new();
+                raster.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+                Some(STANDARD.encode(png))
+            }
+            None => None,
+        };
+        Ok(Self {
+            plane_scale: content.plane_scale,
+            template: content.template,
+            strokes: content.strokes.clone(),
+            raster,
+            metadata: content.metadata.clone(),
+        })
+    }
+
+    pub fn to_content(&self) -> Result<DrawableContent, DocumentError> {
+        let mut content = DrawableContent::new(self.plane_scale);
+        content.template = self.template;
+        content.metadata = self.metadata.clone();
+        for stroke in &self.strokes {
+            content.push_painted(stroke.clone());
+        }
+        if let Some(raster) = &self.raster {
+            let png = STANDARD.decode(raster)?;
+            content.raster = Some(image::load_from_memory(&png)?.to_rgba8());
+        }
+        content.request_render();
+        Ok(content)
+    }
+}
+
+impl NotebookDocument {
+    pub fn from_contents<'a>(
+        contents: impl IntoIterator<Item = &'a DrawableContent>,
+    ) -> Result<Self, DocumentError> {
+        let pages = contents
+            .into_iter()
+            .map(PageDocument::from_content)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { pages, cover: None })
+    }
+
+    /// adds the drawing on the cover
+    pub fn with_cover(mut self, cover: Option<&DrawableContent>) -> Result<Self, DocumentError> {
+        self.cover = cover.map(PageDocument::from_content).transpose()?;
+        Ok(self)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), DocumentError> {
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, DocumentError> {
+        Ok(ron::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// The pages of the open notebook
+///
+/// A notebook can have more pages than the model has drawables, the ones that
+/// aren't shown are kept here. The drawables hold the shown pages, from
+/// `first_shown` on, so the copies of those here are out of date.
+#[derive(Resource, Debug, Default)]
+pub struct NotebookPages {
+    pages: Vec<DrawableContent>,
+    pub first_shown: usize,
+    /// the document the notebook was opened from or last saved to
+    pub path: Option<PathBuf>,
+}
+
+impl NotebookPages {
+    /// every page, with the shown ones taken from the drawables in order
+    pub fn with_shown<'a>(
+        &self,
+        shown: impl IntoIterator<Item = &'a DrawableContent>,
+    ) -> Vec<DrawableContent> {
+        let mut pages = self.pages.clone();
+        for (index, content) in (self.first_shown..).zip(shown) {
+            match pages.get_mut(index) {
+                Some(page) => *page = content.clone(),
+                None => pages.push(content.clone()),
+            }
+        }
+        pages
+    }
+
+    /// number of pages when `shown` drawables show pages from `first_shown` on
+    pub fn count(&self, shown: usize) -> usize {
+        self.pages.len().max(self.first_shown + shown)
+    }
+
+    /// page `index`, out of date if it's shown
+    pub fn get(&self, index: usize) -> Option<&DrawableContent> {
+        self.pages.get(index)
+    }
+
+    pub(super) fn set_pages(&mut self, pages: Vec<DrawableContent>, first_shown: usize) {
+        self.pages = pages;
+        self.first_shown = first_shown;
+    }
+}
+
+/// Whether the pages changed since the notebook was opened or saved
+#[derive(Resource, Debug, Default, PartialEq)]
+pub struct UnsavedChanges(pub bool);
+
+/// A path in [`NOTEBOOKS_FOLDER`] that isn't taken, for a new notebook
+pub fn new_notebook_path() -> PathBuf {
+    let folder = Path::new(NOTEBOOKS_FOLDER);
+    (1..)
+        .map(|number| folder.join(format!("notebook_{number}.ron")))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// The notebook documents in [`NOTEBOOKS_FOLDER`], by name
+pub fn notebook_files() -> Vec<PathBuf> {
+    let mut files: Vec<_> = fs::read_dir(NOTEBOOKS_FOLDER)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Renders the template, raster layer and strokes of a page with the CPU paint
+/// code, the same way the app shows them
+pub fn render_page(
+    page: &PageDocument,
+    resolution: usize,
+    brush_tips: &BrushTips,
+) -> Result<RgbaImage, DocumentError> {
+    render_content(&page.to_content()?, resolution, brush_tips)
+}
+
+/// [`render_page`] for the content of a drawable
+pub fn render_content(
+    content: &DrawableContent,
+    resolution: usize,
+    brush_tips: &BrushTips,
+) -> Result<RgbaImage, DocumentError> {
+    let ink = content.render_to_image(resolution, brush_tips);
+    let ink = ink.try_into_dynamic()?.to_rgba8();
+
+    let mut image = template_image(content, resolution)?;
+    image::imageops::overlay(&mut image, &ink, 0, 0);
+    Ok(image)
+}
+
+/// the page template, which the app draws in the shader
+fn template_image(
+    content: &DrawableContent,
+    resolution: usize,
+) -> Result<RgbaImage, DocumentError> {
+    let template = &content.template;
+    let aspect = content.plane_scale.y / content.plane_scale.x;
+    let mut image = create_drawable_image(resolution);
+    if aspect.is_finite() {
+        // the image is square, so page widths become page units by dividing y
+        let size = resolution as f32;
+        let to_pixel = |point: Vec2| {
+            let pixel = Vec2::new(point.x, point.y / aspect) * size;
+            (pixel.x.max(0.0) as usize, pixel.y.max(0.0) as usize)
+        };
+        let line = PaintSettings {
+            radius: template.line_width / 2.0,
+            colour: template.colour.with_alpha(1.0),
+            ..default()
+        };
+        let dot = PaintSettings {
+            radius: template.line_width * 1.5,
+            ..line.clone()
+        };
+        for mark in template.marks(aspect) {
+            match mark {
+                TemplateMark::Line(start, end) => {
+                    let ((x1, y1), (x2, y2)) = (to_pixel(start), to_pixel(end));
+                    image.draw_thick_line_antialias(x1, y1, x2, y2, &line, Vec2::ONE);
+                }
+                TemplateMark::Dot(centre) => {
+                    let (x, y) = to_pixel(centre);
+                    image.draw_spot(x, y, &dot, Vec2::ONE);
+                }
+            }
+        }
+    }
+
+    let mut image = image.try_into_dynamic()?.to_rgba8();
+    let opacity = template.colour.alpha();
+    for pixel in image.pixels_mut() {
+        pixel.0[3] = (pixel.0[3] as f32 * opacity).round() as u8;
+    }
+    Ok(image)
+}
+
+/// Renders every page of the document at `document_path` to
+/// `notebook_page_N.png` files in `out`, with the brush tips in
+/// `brush_tips_folder`. Returns the paths written.
+pub fn render_document(
+    document_path: &Path,
+    out: &Path,
+    resolution: usize,
+    brush_tips_folder: &Path,
+) -> Result<Vec<PathBuf>, DocumentError> {
+    let document = NotebookDocument::load(document_path)?;
+    let brush_tips = BrushTips::from_folder(brush_tips_folder);
+    fs::create_dir_all(out)?;
+    let mut paths = Vec::new();
+    for (index, page) in document.pages.iter().enumerate() {
+        let path = out.join(format!("notebook_page_{}.png", index + 1));
+        render_page(page, resolution, &brush_tips)?.save(&path)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Message for saving the notebook to a document
+#[derive(Debug, Message)]
+pub struct SaveNotebook {
+    pub path: PathBuf,
+}
+
+/// Sent when the notebook was saved to a document
+#[derive(Debug, Message)]
+pub struct NotebookSaved {
+    pub path: PathBuf,
+}
+
+/// Message for replacing the pages with the ones in a document
+#[derive(Debug, Message)]
+pub struct OpenNotebook {
+    pub path: PathBuf,
+}
+
+/// Sent when a document was opened
+#[derive(Debug, Message)]
+pub struct NotebookOpened {
+    pub path: PathBuf,
+}
+
+/// Message for creating a notebook of blank pages, which is saved to a new
+/// file in [`NOTEBOOKS_FOLDER`] and opened
+#[derive(Debug, Message)]
+pub struct NewNotebook {
+    pub pages: usize,
+    pub template: TemplateKind,
+    /// width and height of the page images in pixels
+    pub resolution: usize,
+}
+
+/// A document to open as soon as the notebook's pages are loaded
+#[derive(Resource, Debug)]
+pub struct StartupNotebook(pub PathBuf);
+
+pub(super) fn open_startup_notebook(
+    mut commands: Commands,
+    startup_notebook: Res<StartupNotebook>,
+    drawable_query: Query<(), PageFilter>,
+    mut open_writer: MessageWriter<OpenNotebook>,
+) {
+    if drawable_query.is_empty() {
+        return;
+    }
+    open_writer.write(OpenNotebook {
+        path: startup_notebook.0.clone(),
+    });
+    commands.remove_resource::<StartupNotebook>();
+}
+
+pub(super) fn save_notebook(
+    mut reader: MessageReader<SaveNotebook>,
+    drawable_query: Query<(Entity, &DrawableContent), PageFilter>,
+    cover_query: Query<&DrawableContent, CoverFilter>,
+    mut notebook_pages: ResMut<NotebookPages>,
+    mut saved_writer: MessageWriter<NotebookSaved>,
+) {
+    for message in reader.read() {
+        let mut drawables: Vec<_> = drawable_query.iter().collect();
+        drawables.sort_by_key(|(entity, _)| *entity);
+        let pages = notebook_pages.with_shown(drawables.into_iter().map(|(_, c)| c));
+        let result = NotebookDocument::from_contents(&pages)
+            .and_then(|document| document.with_cover(cover_query.iter().next()))
+            .and_then(|document| document.save(&message.path));
+        match result {
+            Ok(()) => {
+                info!("Saved notebook to {}", message.path.display());
+                notebook_pages.path = Some(message.path.clone());
+                saved_writer.write(NotebookSaved {
+                    path: message.path.clone(),
+                });
+            }
+            Err(error) => error!("Failed to save notebook: {error}"),
+        }
+    }
+}
+
+/// whether the pages changed, pages loading or going to another page isn't a
+/// change
+pub(super) fn pages_changed<'a, 'b>(
+    contents: impl IntoIterator<Item = Ref<'a, DrawableContent>>,
+    page_controls: impl IntoIterator<Item = &'b PageControl>,
+) -> bool {
+    let mut navigated = false;
+    let mut edited = false;
+    for control in page_controls {
+        navigated = true;
+        edited |= control.edits();
+    }
+    edited
+        || (!navigated
+            && contents
+                .into_iter()
+                .any(|content| content.is_changed() && !content.is_added()))
+}
+
+pub(super) fn track_unsaved_changes(
+    mut unsaved: ResMut<UnsavedChanges>,
+    content_query: Query<Ref<DrawableContent>, SelectedDrawableFilter>,
+    mut opened_reader: MessageReader<NotebookOpened>,
+    mut saved_reader: MessageReader<NotebookSaved>,
+    mut page_reader: MessageReader<PageControl>,
+) {
+    if pages_changed(content_query, page_reader.read()) {
+        unsaved.set_if_neq(UnsavedChanges(true));
+    }
+    // opening changes the pages too, so this comes after
+    if opened_reader.read().count() + saved_reader.read().count() > 0 {
+        unsaved.set_if_neq(UnsavedChanges(false));
+    }
+}
+
+pub(super) fn new_notebook(
+    mut commands: Commands,
+    mut reader: MessageReader<NewNotebook>,
+    mut default_resolution: ResMut<DefaultResolution>,
+    mut drawable_query: Query<&mut Drawable>,
+) {
+    for message in reader.read() {
+        let template = PageTemplate {
+            kind: message.template,
+            ..default()
+        };
+        let page = PageDocument {
+            // the size of the pages comes from the model when they're opened
+            plane_scale: Vec2::ONE,
+            template,
+            strokes: Vec::new(),
+            raster: None,
+            metadata: PageMetadata::new(),
+        };
+        let document = NotebookDocument {
+            pages: vec![page; message.pages.max(1)],
+            cover: None,
+        };
+        let path = new_notebook_path();
+        if let Err(error) = document.save(&path) {
+            error!("Failed to create notebook: {error}");
+            continue;
+        }
+        // pages that haven't loaded yet get the default resolution
+        default_resolution.0 = message.resolution;
+        for mut drawable in &mut drawable_query {
+            *drawable = Drawable::new(message.resolution);
+        }
+        info!("Created notebook {}", path.display());
+        commands.insert_resource(StartupNotebook(path));
+    }
+}
+
+pub(super) fn open_notebook(
+    mut reader: MessageReader<OpenNotebook>,
+    mut drawable_query: Query<(Entity, &mut DrawableContent), PageFilter>,
+    mut cover_query: Query<(Entity, &mut DrawableContent), CoverFilter>,
+    mut notebook_pages: ResMut<NotebookPages>,
+    mut template_writer: MessageWriter<SetPageTemplate>,
+    mut opened_writer: MessageWriter<NotebookOpened>,
+) {
+    for message in reader.read() {
+        let document = match NotebookDocument::load(&message.path) {
+            Ok(document) => document,
+            Err(error) => {
+                error!("Failed to open notebook: {error}");
+                continue;
+            }
+        };
+        let mut pages: Vec<_> = document
+            .pages
+            .iter()
+            .map(|page| {
+                page.to_content().unwrap_or_else(|error| {
+                    error!("Failed to open page: {error}");
+                    DrawableContent::new(page.plane_scale)
+                })
+            })
+            .collect();
+
+        let mut drawables: Vec<_> = drawable_query.iter_mut().collect();
+        drawables.sort_by_key(|(entity, _)| *entity);
+        show_pages(&mut drawables, &mut pages, 0, &mut template_writer);
+        for (entity, mut cover) in &mut cover_query {
+            let opened = document.cover.as_ref().and_then(|page| {
+                page.to_content()
+                    .inspect_err(|error| error!("Failed to open cover: {error}"))
+                    .ok()
+            });
+            let mut shown = opened.unwrap_or_else(|| DrawableContent::new(cover.plane_scale));
+            shown.plane_scale = cover.plane_scale;
+            shown.request_render();
+            *cover = shown;
+            template_writer.write(SetPageTemplate {
+                drawable: entity,
+                template: cover.template,
+            });
+        }
+        info!(
+            "Opened {} with {} pages",
+            message.path.display(),
+            pages.len()
+        );
+        *notebook_pages = NotebookPages {
+            pages,
+            first_shown: 0,
+            path: Some(message.path.clone()),
+        };
+        opened_writer.write(NotebookOpened {
+            path: message.path.clone(),
+        });
+    }
+}
+
+/// the pages and cover of `notebook` as they'd be saved, `pages` are the ones
+/// it keeps that aren't shown
+pub fn notebook_contents(
+    notebook: Entity,
+    pages: &NotebookPages,
+    children_query: &Query<&Children>,
+    drawable_query: &Query<(Entity, &DrawableContent, Has<Cover>), With<DrawableObject>>,
+) -> (Vec<DrawableContent>, Option<DrawableContent>) {
+    let (covers, mut shown): (Vec<_>, Vec<_>) = children_query
+        .iter_descendants(notebook)
+        .filter_map(|entity| drawable_query.get(entity).ok())
+        .partition(|(_, _, cover)| *cover);
+    shown.sort_by_key(|(entity, _, _)| *entity);
+    let contents = pages.with_shown(shown.into_iter().map(|(_, content, _)| content));
+    let cover = covers
+        .into_iter()
+        .next()
+        .map(|(_, content, _)| content.clone());
+    (contents, cover)
+}
+
+/// puts `pages` from `first_shown` on into the drawables, which are sorted,
+/// adding blank pages if there aren't enough
+pub(super) fn show_pages(
+    drawables: &mut [(Entity, Mut<DrawableContent>)],
+    pages: &mut Vec<DrawableContent>,
+    first_shown: usize,
+    template_writer: &mut MessageWriter<SetPageTemplate>,
+) {
+    for (index, (entity, content)) in (first_shown..).zip(drawables.iter_mut()) {
+        while pages.len() <= index {
+            pages.push(DrawableContent::new(content.plane_scale));
+        }
+        let mut shown = pages[index].clone();
+        // the size of the page comes from the model
+        shown.plane_scale = content.plane_scale;
+        shown.request_render();
+        **content = shown;
+        template_writer.write(SetPageTemplate {
+            drawable: *entity,
+            template: content.template,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+    use image::RgbaImage;
+
+    use super::{
+        render_page, track_unsaved_changes, NotebookDocument, NotebookPages, NotebookSaved,
+        UnsavedChanges,
+    };
+    use crate::drawable::{
+        pages::PageControl,
+        paint::{stamp::BrushTips, PaintSettings},
+        stroke::{DrawableContent, Stroke, StrokePoint},
+        template::{PageTemplate, TemplateKind},
+        DrawableObject, InSelectedNotebook,
+    };
+
+    fn page() -> DrawableContent {
+        let mut content = DrawableContent::new(Vec2::ONE);
+        let stroke = Stroke {
+            id: content.next_stroke_id(),
+            points: [0.25, 0.75]
+                .into_iter()
+                .map(|x| StrokePoint {
+                    position: Vec2::new(x, 0.5),
+                    pressure: 1.0,
+                    time: 0.0,
+                })
+                .collect(),
+            paint_settings: PaintSettings {
+                radius: 0.05,
+                ..default()
+            },
+            started_at: 0.0,
+        };
+        content.push_painted(stroke);
+        content.raster = Some(RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255])));
+        content.template = PageTemplate {
+            kind: TemplateKind::Grid,
+            spacing: 0.25,
+            ..default()
+        };
+        content
+    }
+
+    #[test]
+    fn documents_round_trip_through_ron() {
+        let content = page();
+        let document = NotebookDocument::from_contents([&content]).unwrap();
+        let text = ron::to_string(&document).unwrap();
+        let opened = ron::from_str::<NotebookDocument>(&text).unwrap().pages[0]
+            .to_content()
+            .unwrap();
+
+        assert_eq!(opened.strokes.len(), 1);
+        assert_eq!(opened.strokes[0].points, content.strokes[0].points);
+        assert_eq!(opened.raster, content.raster);
+        assert_eq!(opened.template, content.template);
+    }
+
+    #[test]
+    fn pages_that_arent_shown_are_kept() {
+        let mut notebook_pages = NotebookPages {
+            pages: vec![DrawableContent::new(Vec2::ONE); 3],
+            first_shown: 1,
+            path: None,
+        };
+        let shown = page();
+        let pages = notebook_pages.with_shown([&shown]);
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0].strokes.is_empty() && pages[2].strokes.is_empty());
+        assert_eq!(pages[1].strokes.len(), 1);
+
+        // more drawables than pages adds pages
+        notebook_pages.first_shown = 2;
+        assert_eq!(notebook_pages.with_shown([&shown, &shown]).len(), 4);
+    }
+
+    #[test]
+    fn saving_clears_unsaved_changes() {
+        let mut app = App::new();
+        app.add_message::<super::NotebookOpened>()
+            .add_message::<NotebookSaved>()
+            .add_message::<PageControl>()
+            .init_resource::<UnsavedChanges>()
+            .add_systems(Update, track_unsaved_changes);
+        let drawable = app
+            .world_mut()
+            .spawn((
+                DrawableObject,
+                InSelectedNotebook,
+                DrawableContent::new(Vec2::ONE),
+            ))
+            .id();
+        app.update();
+        assert_eq!(
+            *app.world().resource::<UnsavedChanges>(),
+            UnsavedChanges(false)
+        );
+
+        let mut content = app
+            .world_mut()
+            .get_mut::<DrawableContent>(drawable)
+            .unwrap();
+        content.push_painted(page().strokes[0].clone());
+        app.update();
+        assert_eq!(
+            *app.world().resource::<UnsavedChanges>(),
+            UnsavedChanges(true)
+        );
+
+        app.world_mut().write_message(NotebookSaved {
+            path: "saved.ron".into(),
+        });
+        app.update();
+        assert_eq!(
+            *app.world().resource::<UnsavedChanges>(),
+            UnsavedChanges(false)
+        );
+    }
+
+    #[test]
+    fn pages_render_without_the_app() {
+        let mut content = page();
+        content.raster = None;
+        let document = NotebookDocument::from_contents([&content]).unwrap();
+        let image = render_page(&document.pages[0], 64, &BrushTips::default()).unwrap();
+
+        // the stroke across the middle, a grid line and the blank page
+        assert_eq!(image.get_pixel(32, 32).0, [0, 0, 0, 255]);
+        assert!(image.get_pixel(16, 10).0[3] > 0);
+        assert_eq!(image.get_pixel(10, 10).0[3], 0);
+    }
+}
@@ -1,8 +1,16 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
 
-use crate::drawable::drawable_material::DrawableMaterial;
+use crate::{drawable::drawable_material::DrawableMaterial, notebook::animation::NotebookState};
 
-use super::paint::{paint_input::PaintInput, PaintImage, PaintSettings};
+use super::{
+    guide::{Ruler, Snapping, StrokeGuide},
+    paint::{
+        paint_input::{ActiveStroke, PaintInput},
+        stamp::BrushTips,
+        PaintSettings, StrokeRaster,
+    },
+    stroke::{DrawableContent, Stroke, StrokePoint},
+};
 
 /// Component for the object
 #[derive(Component, Reflect, Debug)]
@@ -12,6 +20,10 @@ pub struct Drawable {
 }
 
 impl Drawable {
+    pub fn new(resolution: usize) -> Self {
+        Self { resolution }
+    }
+
     pub fn resolution(&self) -> usize {
         self.resolution
     }
@@ -19,7 +31,21 @@ impl Drawable {
 
 impl Default for Drawable {
     fn default() -> Self {
-        Self { resolution: 1024 }
+        Self::new(DefaultResolution::default().0)
+    }
+}
+
+/// The largest resolution drawables and rendered pages can have
+pub const MAX_RESOLUTION: usize = 8192;
+
+/// Resolution of drawables that don't set one, from the settings or the
+/// command line
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DefaultResolution(pub usize);
+
+impl Default for DefaultResolution {
+    fn default() -> Self {
+        Self(1024)
     }
 }
 
@@ -27,89 +53,211 @@ impl Default for Drawable {
 #[derive(Component)]
 pub struct DrawableObject;
 
-/// The main drawing system that handles mouse input for drawing on drawable objects
+/// Marks the notebook's cover, on the [`Drawable`] mesh and its
+/// [`DrawableObject`]. The cover can be drawn on but isn't one of the pages.
+#[derive(Component, Debug, Default)]
+pub struct Cover;
+
+/// Marks the [`DrawableObject`]s of the selected notebook on the desk, the
+/// others can't be drawn on
+#[derive(Component, Debug, Default)]
+pub struct InSelectedNotebook;
+
+/// Query filter for the drawable objects of the selected notebook, the pages
+/// and the cover
+pub type SelectedDrawableFilter = (With<DrawableObject>, With<InSelectedNotebook>);
+
+/// Query filter for the drawable objects that show pages of the selected
+/// notebook
+pub type PageFilter = (
+    With<DrawableObject>,
+    With<InSelectedNotebook>,
+    Without<Cover>,
+);
+
+/// Query filter for the drawable object of the selected notebook's cover
+pub type CoverFilter = (With<DrawableObject>, With<InSelectedNotebook>, With<Cover>);
+
+/// What's under the cursor on a drawable object
+#[derive(Debug, Clone, Copy)]
+pub struct DrawableHit {
+    /// the [`DrawableObject`] that was hit
+    pub drawable_object: Entity,
+    /// position on the page, between 0 and 1 on both axes
+    pub position: Vec2,
+    pub plane_scale: Vec2,
+}
+
+/// Finds where the cursor is on drawable objects
+#[derive(SystemParam)]
+pub struct DrawableCursor<'w, 's> {
+    drawable_query: Query<'w, 's, &'static Children, With<Drawable>>,
+    drawable_child_query: Query<'w, 's, &'static GlobalTransform, SelectedDrawableFilter>,
+    camera: Single<'w, 's, (&'static Camera, &'static GlobalTransform), With<Camera3d>>,
+    window: Single<'w, 's, &'static Window, With<PrimaryWindow>>,
+    ray_cast: MeshRayCast<'w, 's>,
+}
+
+impl DrawableCursor<'_, '_> {
+    pub fn hit(&mut self) -> Option<DrawableHit> {
+        let ray = cursor_ray(&self.window, *self.camera)?;
+
+        let drawable_query = &self.drawable_query;
+        let drawable_entity_filter = |entity| drawable_query.contains(entity);
+
+        let ray_settings = MeshRayCastSettings::default()
+            .always_early_exit()
+            .with_filter(&drawable_entity_filter)
+            .with_visibility(RayCastVisibility::Visible);
+
+        let hits = self.ray_cast.cast_ray(ray, &ray_settings);
+        for (entity, hit_info) in hits {
+            let children = drawable_query.get(*entity).expect("huh?");
+
+            for child in children.iter() {
+                if let Ok(transform) = self.drawable_child_query.get(child) {
+                    let (u, v) = get_uv_from_position(hit_info.point, hit_info.normal, transform);
+                    let plane_scale = transform.scale();
+
+                    return Some(DrawableHit {
+                        drawable_object: child,
+                        position: get_page_position_from_uv(-u, v),
+                        plane_scale: Vec2::new(plane_scale.x, plane_scale.z),
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The pen touching a page or being lifted, for one frame of input
+#[derive(Message, Debug, Clone, Copy)]
+pub struct PenSample {
+    /// where the pen is, `None` when it's lifted or off the pages
+    pub hit: Option<DrawableHit>,
+    /// a stroke started with this sample is a straight line
+    pub straight: bool,
+    /// seconds since the app started, or since the start of a replayed recording
+    pub time: f64,
+}
+
+/// Systems reading mouse input for drawing, so it can be replaced by a replay
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PenInputSet;
+
+/// sends the mouse as a [`PenSample`] every frame
+pub fn pen_input_system(
+    mut cursor: DrawableCursor,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut writer: MessageWriter<PenSample>,
+    ui_query: Query<&Interaction>,
+    notebook_state: Res<State<NotebookState>>,
+) {
+    // clicking or dragging on the GUI doesn't draw
+    let on_gui = ui_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed);
+    // and neither does the notebook while it's opening or closing
+    let moving = matches!(
+        notebook_state.get(),
+        NotebookState::Opening | NotebookState::Closing
+    );
+    let hit = (buttons.pressed(MouseButton::Left) && !on_gui && !moving)
+        .then(|| cursor.hit())
+        .flatten();
+    writer.write(PenSample {
+        hit,
+        straight: keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+        time: time.elapsed_secs_f64(),
+    });
+}
+
+/// The main drawing system that paints [`PenSample`]s onto drawable objects
+#[allow(clippy::too_many_arguments)]
 pub fn drawing_system(
-    drawable_query: Query<(&Children, &Drawable)>,
+    mut reader: MessageReader<PenSample>,
     mut drawable_child_query: Query<
-        (&GlobalTransform, &mut MeshMaterial3d<DrawableMaterial>),
+        (&MeshMaterial3d<DrawableMaterial>, &mut DrawableContent),
         With<DrawableObject>,
     >,
-    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
-    buttons: Res<ButtonInput<MouseButton>>,
-    window: Single<&Window, With<PrimaryWindow>>,
-    mut ray_cast: MeshRayCast,
     // this is only mutable for change detection to work
     // https://github.com/bevyengine/bevy/issues/15595
     mut drawable_mat_assets: ResMut<Assets<DrawableMaterial>>,
     mut images: ResMut<Assets<Image>>,
     mut paint_input: ResMut<PaintInput>,
+    paint_settings: Res<PaintSettings>,
+    brush_tips: Res<BrushTips>,
+    ruler: Res<Ruler>,
+    snapping: Res<Snapping>,
 ) {
-    if buttons.pressed(MouseButton::Left) {
-        if let Some(mouse_position) = window.cursor_position() {
-            //ray starts at camera and screen pos
-            let ray_info = ray_from_screen(window.size(), mouse_position, *camera);
-            let dir = Dir3::new(ray_info.1).unwrap();
-            let ray = Ray3d::new(ray_info.0, dir);
-
-            let drawable_entity_filter = |entity| drawable_query.contains(entity);
-
-            let ray_settings = MeshRayCastSettings::default()
-                .always_early_exit()
-                .with_filter(&drawable_entity_filter)
-                .with_visibility(RayCastVisibility::Visible);
-
-            let hits = ray_cast.cast_ray(ray, &ray_settings);
-            for (entity, hit_info) in hits {
-                let (hit_entity, drawable) = drawable_query.get(*entity).expect("huh?");
-
-                for child in hit_entity.iter() {
-                    if let Ok((transform, mesh_material)) = drawable_child_query.get_mut(child) {
-                        let material_option = drawable_mat_assets.get_mut(&mesh_material.0);
-
-                        if let Some(material) = material_option {
-                            let image = images.get_mut(&material.draw_texture);
-
-                            if let Some(image) = image {
-                                let (u, v) = get_uv_from_position(
-                                    hit_info.point,
-                                    hit_info.normal,
-                                    transform,
-                                );
-
-                                // let mut coord = get_coord_from_uv(-u, v, drawable.resolution());
-
-                                let (x, y) = get_coords_from_uv(-u, v, drawable.resolution());
-
-                                // println!("x: {x}, y: {y}, u: {u}, v: {v}");
-
-                                //change to resource
-                                let paint_settings = PaintSettings::default();
-                                let plane_scale = transform.scale();
-                                let scale = Vec2::new(plane_scale.x, plane_scale.z);
-
-                                if !paint_input.mouse_down {
-                                    image.draw_spot(x, y, &paint_settings, scale);
-                                } else if let Some(last_pos) = paint_input.last_input_location {
-                                    image.draw_thick_line_antialias(
-                                        last_pos.0,
-                                        last_pos.1,
-                                        x,
-                                        y,
-                                        &paint_settings,
-                                        scale,
-                                    );
-                                }
-
-                                paint_input.last_input_location = Some((x, y));
-                                paint_input.mouse_down = true;
-                            }
-                        }
-                    }
+    for sample in reader.read() {
+        // the stroke ends when the pen is lifted or it moves onto another drawable
+        let hit_entity = sample.hit.map(|hit| hit.drawable_object);
+        if paint_input.stroke.as_ref().map(|stroke| stroke.drawable) != hit_entity {
+            if let Some(active) = paint_input.stroke.take() {
+                if let Ok((_, mut content)) = drawable_child_query.get_mut(active.drawable) {
+                    content.push_painted(active.stroke);
                 }
             }
         }
-    } else {
-        paint_input.mouse_down = false;
+
+        let Some(hit) = sample.hit else {
+            continue;
+        };
+        let Ok((mesh_material, mut content)) = drawable_child_query.get_mut(hit.drawable_object)
+        else {
+            continue;
+        };
+        let Some(material) = drawable_mat_assets.get_mut(&mesh_material.0) else {
+            continue;
+        };
+        let Some(image) = images.get_mut(&material.draw_texture) else {
+            continue;
+        };
+
+        content.plane_scale = hit.plane_scale;
+        let active = paint_input.stroke.get_or_insert_with(|| ActiveStroke {
+            drawable: hit.drawable_object,
+            guide: StrokeGuide::new(
+                hit.drawable_object,
+                hit.position,
+                sample.straight,
+                &ruler,
+                &snapping,
+                &content,
+            ),
+            raster: StrokeRaster::new(image, &paint_settings, &brush_tips),
+            stroke: Stroke {
+                id: content.next_stroke_id(),
+                points: Vec::new(),
+                paint_settings: paint_settings.clone(),
+                started_at: sample.time,
+            },
+        });
+
+        let point = StrokePoint {
+            position: active.guide.apply(hit.position),
+            pressure: 1.0,
+            time: (sample.time - active.stroke.started_at) as f32,
+        };
+        if active.guide.straight {
+            // a line from the first point to the cursor, drawn with the page
+            active.stroke.points.truncate(1);
+            active.stroke.points.push(point);
+            content.request_render();
+            continue;
+        }
+        let size = Vec2::new(image.width() as f32, image.height() as f32);
+        active.raster.add_point(
+            image,
+            point.position * size,
+            &active.stroke.paint_settings,
+            hit.plane_scale,
+        );
+        active.stroke.points.push(point);
     }
 }
 
@@ -142,14 +290,21 @@ fn get_uv_from_position(
     // println!("plane scale: {:?}", plane_transform.scale());
 }
 
-fn get_coords_from_uv(u: f32, v: f32, resolution: usize) -> (usize, usize) {
+fn get_page_position_from_uv(u: f32, v: f32) -> Vec2 {
     let x = (u + 1.0) / 2.0;
     let y = (v + 1.0) / 2.0;
 
-    let x_index = x * resolution as f32;
-    let y_index = y * resolution as f32;
+    Vec2::new(x, y)
+}
+
+/// The ray from the camera through the cursor
+pub fn cursor_ray(window: &Window, camera: (&Camera, &GlobalTransform)) -> Option<Ray3d> {
+    let mouse_position = window.cursor_position()?;
 
-    (x_index as usize, y_index as usize)
+    //ray starts at camera and screen pos
+    let ray_info = ray_from_screen(window.size(), mouse_position, camera);
+    let dir = Dir3::new(ray_info.1).ok()?;
+    Some(Ray3d::new(ray_info.0, dir))
 }
 
 fn ray_from_screen(
@@ -169,3 +324,108 @@ fn ray_from_screen(
 
     (cursor_pos_world, ray_direction)
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use super::{drawing_system, DrawableHit, DrawableObject, PenSample};
+    use crate::drawable::{
+        drawable_material::{create_drawable_image, DrawableMaterial},
+        guide::{Ruler, Snapping},
+        paint::{paint_input::PaintInput, stamp::BrushTips, PaintSettings},
+        stroke::DrawableContent,
+        template::{PageTemplate, TemplateKind},
+    };
+
+    /// an app with just the drawing system and a page to draw on
+    fn drawing_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_message::<PenSample>()
+            .init_resource::<Assets<Image>>()
+            .init_resource::<Assets<DrawableMaterial>>()
+            .init_resource::<PaintInput>()
+            .init_resource::<PaintSettings>()
+            .init_resource::<BrushTips>()
+            .init_resource::<Ruler>()
+            .init_resource::<Snapping>()
+            .add_systems(Update, drawing_system);
+
+        let world = app.world_mut();
+        let image = world
+            .resource_mut::<Assets<Image>>()
+            .add(create_drawable_image(64));
+        let material = world
+            .resource_mut::<Assets<DrawableMaterial>>()
+            .add(DrawableMaterial::new(image));
+        let page = world
+            .spawn((
+                DrawableObject,
+                MeshMaterial3d(material),
+                DrawableContent::new(Vec2::ONE),
+            ))
+            .id();
+        (app, page)
+    }
+
+    fn pen(app: &mut App, page: Entity, position: Option<Vec2>, straight: bool, time: f64) {
+        app.world_mut().write_message(PenSample {
+            hit: position.map(|position| DrawableHit {
+                drawable_object: page,
+                position,
+                plane_scale: Vec2::ONE,
+            }),
+            straight,
+            time,
+        });
+        app.update();
+    }
+
+    #[test]
+    fn samples_become_strokes() {
+        let (mut app, page) = drawing_app();
+        for (index, x) in [0.2, 0.4, 0.6].into_iter().enumerate() {
+            pen(&mut app, page, Some(Vec2::new(x, 0.5)), false, index as f64);
+        }
+        pen(&mut app, page, None, false, 3.0);
+
+        let content = app.world().get::<DrawableContent>(page).unwrap();
+        let [stroke] = content.strokes.as_slice() else {
+            panic!("expected one stroke, got {}", content.strokes.len());
+        };
+        let points: Vec<_> = stroke.points.iter().map(|p| (p.position, p.time)).collect();
+        assert_eq!(
+            points,
+            [
+                (Vec2::new(0.2, 0.5), 0.0),
+                (Vec2::new(0.4, 0.5), 1.0),
+                (Vec2::new(0.6, 0.5), 2.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn straight_lines_snap_to_the_grid() {
+        let (mut app, page) = drawing_app();
+        app.world_mut()
+            .get_mut::<DrawableContent>(page)
+            .unwrap()
+            .template = PageTemplate {
+            kind: TemplateKind::Grid,
+            spacing: 0.25,
+            ..default()
+        };
+        for position in [(0.3, 0.45), (0.5, 0.6), (0.7, 0.8)] {
+            pen(&mut app, page, Some(position.into()), true, 0.0);
+        }
+        pen(&mut app, page, None, false, 0.0);
+
+        let content = app.world().get::<DrawableContent>(page).unwrap();
+        let points: Vec<_> = content.strokes[0]
+            .points
+            .iter()
+            .map(|p| p.position)
+            .collect();
+        assert_eq!(points, [Vec2::new(0.25, 0.5), Vec2::new(0.75, 0.75)]);
+    }
+}
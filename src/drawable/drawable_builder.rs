@@ -1,10 +1,14 @@
 use bevy::prelude::*;
 
-use super::{create_drawable_material, Drawable, DrawableMaterial, DrawableObject};
+use super::{
+    create_drawable_material, paint::stamp::BrushTips, stroke::DrawableContent, Cover, Drawable,
+    DrawableMaterial, DrawableObject,
+};
 
 pub fn add_drawable_system(
     mut commands: Commands,
     drawable_mesh_query: Query<(&Drawable, &Mesh3d, &Transform, Entity), Added<Drawable>>,
+    cover_query: Query<(), With<Cover>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut drawable_materials: ResMut<Assets<DrawableMaterial>>,
     asset_server: Res<AssetServer>,
@@ -26,14 +30,45 @@ pub fn add_drawable_system(
                     MeshMaterial3d(material_handle),
                     drawable.2.with_translation(Vec3::new(0.0, 0.01, 0.0)),
                     DrawableObject,
+                    DrawableContent::new(drawable.2.scale.xz()),
                 ))
                 .id();
 
+            if cover_query.contains(drawable.3) {
+                commands.entity(drawable_plane).insert(Cover);
+            }
             commands.entity(drawable.3).add_child(drawable_plane);
         }
     }
 }
 
+/// renders the strokes again when the resolution of a drawable changes
+pub fn resize_drawable_system(
+    drawable_query: Query<(&Drawable, &Children), Changed<Drawable>>,
+    content_query: Query<(&DrawableContent, &MeshMaterial3d<DrawableMaterial>)>,
+    mut drawable_materials: ResMut<Assets<DrawableMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    brush_tips: Res<BrushTips>,
+) {
+    for (drawable, children) in drawable_query.iter() {
+        for child in children.iter() {
+            let Ok((content, mesh_material)) = content_query.get(child) else {
+                continue;
+            };
+            let Some(material) = drawable_materials.get_mut(&mesh_material.0) else {
+                continue;
+            };
+            let current_resolution = images
+                .get(&material.draw_texture)
+                .map(|image| image.width() as usize);
+            if current_resolution != Some(drawable.resolution()) {
+                let image = content.render_to_image(drawable.resolution(), &brush_tips);
+                material.draw_texture = images.add(image);
+            }
+        }
+    }
+}
+
 /* pub fn test_drawable_system(
     world: &World,
     drawable_mesh_query: Query<Entity, Added<Drawable>>,
@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 
-use crate::drawable::{DrawableMaterial, DrawableObject};
+use crate::drawable::{
+    stroke::DrawableContent, DrawableMaterial, DrawableObject, SelectedDrawableFilter,
+};
 
 /// Message for saving the drawable image(s) to a file
 #[derive(Debug, Message)]
@@ -48,26 +50,16 @@ pub(super) fn save_drawable_image(
 #[derive(Debug, Message)]
 pub(crate) struct ClearDrawableImage;
 
-/// clears drawable image(s)
+/// clears drawable image(s) of the selected notebook, the image is cleared
+/// when the strokes are rendered again
 pub(super) fn clear_drawable_image(
     mut reader: MessageReader<ClearDrawableImage>,
-    drawable_query: Query<&MeshMaterial3d<DrawableMaterial>, With<DrawableObject>>,
-    // this is only mutable for change detection
-    mut drawable_materials: ResMut<Assets<DrawableMaterial>>,
-    mut images: ResMut<Assets<Image>>,
+    mut drawable_query: Query<&mut DrawableContent, SelectedDrawableFilter>,
 ) {
     for _ in reader.read() {
-        for drawable_mesh_mat in drawable_query.iter() {
-            if let Some(drawable_mat) = drawable_materials.get_mut(&drawable_mesh_mat.0) {
-                if let Some(ref mut image) = images.get_mut(&drawable_mat.draw_texture) {
-                    println!("clearing image");
-                    if let Some(ref mut image_data) = image.data {
-                        for x in image_data.iter_mut() {
-                            *x = 0;
-                        }
-                    }
-                }
-            }
+        for mut content in drawable_query.iter_mut() {
+            println!("clearing image");
+            content.clear();
         }
     }
 }
@@ -5,6 +5,8 @@ use bevy::{
     shader::ShaderRef,
 };
 
+use super::template::TemplateUniform;
+
 const DRAWABLE_SHADER_PATH: &str = "shaders/drawable_shader.wgsl";
 
 /// data for drawable shader
@@ -14,11 +16,17 @@ pub struct DrawableMaterial {
     #[texture(0)]
     #[sampler(1)]
     pub draw_texture: Handle<Image>,
+    /// the page background drawn below the ink
+    #[uniform(2)]
+    pub template: TemplateUniform,
 }
 
 impl DrawableMaterial {
     pub fn new(draw_texture: Handle<Image>) -> Self {
-        Self { draw_texture }
+        Self {
+            draw_texture,
+            template: TemplateUniform::default(),
+        }
     }
 }
 
@@ -43,7 +51,7 @@ pub fn create_drawable_material(
     DrawableMaterial::new(image_handle)
 }
 
-fn create_drawable_image(resolution: usize) -> Image {
+pub(crate) fn create_drawable_image(resolution: usize) -> Image {
     let texture_data = vec![0u8; resolution * 4];
 
     Image::new_fill(
@@ -0,0 +1,170 @@
+//! Vector export of the notebook pages
+//!
+//! Strokes and page templates are written as paths and raster layers are
+//! embedded as images, so exported pages can be opened without the app.
+
+use std::{fs, io::Cursor, path::Path};
+
+use bevy::prelude::*;
+use image::{ImageFormat, RgbaImage};
+use thiserror::Error;
+
+use super::{
+    document::NotebookPages,
+    stroke::{DrawableContent, Stroke},
+    template::TemplateMark,
+    PageFilter,
+};
+
+mod pdf;
+mod svg;
+
+pub(super) const EXPORT_FOLDER: &str = "./temp";
+
+/// Width of an exported page in points, the width of A4
+const PAGE_WIDTH: f32 = 595.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// one SVG file per page
+    Svg,
+    /// a single PDF with every page
+    Pdf,
+}
+
+/// Message for exporting every page of the notebook to [`EXPORT_FOLDER`]
+#[derive(Debug, Message)]
+pub struct ExportNotebook {
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("could not write export: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not encode raster layer: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+/// A page to export, positions and sizes are in points from the top left
+pub struct ExportPage<'a> {
+    content: &'a DrawableContent,
+    size: Vec2,
+}
+
+impl<'a> ExportPage<'a> {
+    pub fn new(content: &'a DrawableContent) -> Self {
+        let aspect = if content.plane_scale.x > 0.0 {
+            content.plane_scale.y / content.plane_scale.x
+        } else {
+            1.0
+        };
+        Self {
+            content,
+            size: Vec2::new(PAGE_WIDTH, PAGE_WIDTH * aspect),
+        }
+    }
+
+    fn paths(&self) -> impl Iterator<Item = ExportPath> + '_ {
+        self.content
+            .strokes
+            .iter()
+            .filter(|stroke| !stroke.points.is_empty())
+            .map(|stroke| ExportPath::new(stroke, self.size, self.content.plane_scale))
+    }
+
+    /// the lines and dots of the page template
+    fn template_paths(&self) -> impl Iterator<Item = ExportPath> + '_ {
+        let template = &self.content.template;
+        let colour = template.colour.to_srgba();
+        let scale = self.size.x;
+        template
+            .marks(self.size.y / self.size.x)
+            .into_iter()
+            .map(move |mark| {
+                let (points, width) = match mark {
+                    TemplateMark::Line(start, end) => (vec![start, end], template.line_width),
+                    TemplateMark::Dot(centre) => (vec![centre], template.line_width * 3.0),
+                };
+                ExportPath {
+                    points: points.into_iter().map(|point| point * scale).collect(),
+                    width: width * scale,
+                    colour: colour.with_alpha(1.0),
+                    opacity: colour.alpha,
+                }
+            })
+    }
+
+    fn raster(&self) -> Option<&RgbaImage> {
+        self.content.raster.as_ref()
+    }
+}
+
+/// A stroke as a line through its points with a round brush
+struct ExportPath {
+    points: Vec<Vec2>,
+    width: f32,
+    colour: Srgba,
+    opacity: f32,
+}
+
+impl ExportPath {
+    fn new(stroke: &Stroke, page_size: Vec2, plane_scale: Vec2) -> Self {
+        let settings = &stroke.paint_settings;
+        let colour = settings.colour.to_srgba();
+        Self {
+            points: stroke
+                .points
+                .iter()
+                .map(|point| point.position * page_size)
+                .collect(),
+            width: 2.0 * settings.radius / plane_scale.x * page_size.x,
+            colour: colour.with_alpha(1.0),
+            opacity: colour.alpha * settings.brush.opacity(),
+        }
+    }
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, ExportError> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+fn export_pages(pages: &[ExportPage], format: ExportFormat) -> Result<(), ExportError> {
+    fs::create_dir_all(EXPORT_FOLDER)?;
+    match format {
+        ExportFormat::Svg => {
+            for (index, page) in pages.iter().enumerate() {
+                let path =
+                    Path::new(EXPORT_FOLDER).join(format!("notebook_page_{}.svg", index + 1));
+                fs::write(&path, svg::page_to_svg(page)?)?;
+                info!("Exported page to {}", path.display());
+            }
+        }
+        ExportFormat::Pdf => {
+            let path = Path::new(EXPORT_FOLDER).join("notebook.pdf");
+            fs::write(&path, pdf::pages_to_pdf(pages))?;
+            info!("Exported notebook to {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// exports the pages in the order their drawables were spawned
+pub(super) fn export_notebook(
+    mut reader: MessageReader<ExportNotebook>,
+    drawable_query: Query<(Entity, &DrawableContent), PageFilter>,
+    notebook_pages: Res<NotebookPages>,
+) {
+    for message in reader.read() {
+        let mut drawables: Vec<_> = drawable_query.iter().collect();
+        drawables.sort_by_key(|(entity, _)| *entity);
+        let contents = notebook_pages.with_shown(drawables.into_iter().map(|(_, c)| c));
+        let pages: Vec<_> = contents.iter().map(ExportPage::new).collect();
+
+        if let Err(error) = export_pages(&pages, message.format) {
+            error!("Failed to export notebook: {error}");
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use image::RgbaImage;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use pdf_writer::{
+    types::{LineCapStyle, LineJoinStyle},
+    Content, Filter, Finish, Name, Pdf, Rect, Ref,
+};
+
+use super::{ExportPage, ExportPath};
+
+const RASTER_NAME: Name = Name(b"Raster");
+
+/// a PDF document with one page per [`ExportPage`]
+pub(super) fn pages_to_pdf(pages: &[ExportPage]) -> Vec<u8> {
+    let mut next_ref = Ref::new(1);
+    let catalog_id = next_ref.bump();
+    let page_tree_id = next_ref.bump();
+    let mut pdf = Pdf::new();
+
+    let mut page_ids = Vec::new();
+    for page in pages {
+        let page_id = next_ref.bump();
+        let content_id = next_ref.bump();
+        page_ids.push(page_id);
+        let (width, height) = (page.size.x, page.size.y);
+        let mut content = Content::new();
+
+        // one graphics state per distinct opacity
+        let mut opacities: Vec<f32> = Vec::new();
+        let mut draw = |content: &mut Content, path: &ExportPath| {
+            let state = match opacities.iter().position(|&o| o == path.opacity) {
+                Some(index) => index,
+                None => {
+                    opacities.push(path.opacity);
+                    opacities.len() - 1
+                }
+            };
+            content.save_state();
+            content.set_parameters(Name(graphics_state_name(state).as_bytes()));
+            write_path(content, path, height);
+            content.restore_state();
+        };
+
+        for path in page.template_paths() {
+            draw(&mut content, &path);
+        }
+        let raster_id = page.raster().map(|raster| {
+            let image_id = next_ref.bump();
+            let mask_id = next_ref.bump();
+            write_raster(&mut pdf, raster, image_id, mask_id);
+            content
+                .save_state()
+                .transform([width, 0.0, 0.0, height, 0.0, 0.0])
+                .x_object(RASTER_NAME)
+                .restore_state();
+            image_id
+        });
+        for path in page.paths() {
+            draw(&mut content, &path);
+        }
+        let state_ids: Vec<_> = opacities
+            .iter()
+            .map(|&opacity| {
+                let id = next_ref.bump();
+                pdf.ext_graphics(id)
+                    .stroking_alpha(opacity)
+                    .non_stroking_alpha(opacity);
+                id
+            })
+            .collect();
+
+        let mut pdf_page = pdf.page(page_id);
+        pdf_page
+            .media_box(Rect::new(0.0, 0.0, width, height))
+            .parent(page_tree_id)
+            .contents(content_id);
+        let mut resources = pdf_page.resources();
+        if let Some(raster_id) = raster_id {
+            resources.x_objects().pair(RASTER_NAME, raster_id);
+        }
+        let mut states = resources.ext_g_states();
+        for (index, id) in state_ids.into_iter().enumerate() {
+            states.pair(Name(graphics_state_name(index).as_bytes()), id);
+        }
+        states.finish();
+        resources.finish();
+        pdf_page.finish();
+
+        pdf.stream(content_id, &content.finish());
+    }
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .count(page_ids.len() as i32)
+        .kids(page_ids);
+    pdf.finish()
+}
+
+fn graphics_state_name(index: usize) -> String {
+    format!("GS{index}")
+}
+
+/// strokes `path`, PDF pages start at the bottom so y is flipped
+fn write_path(content: &mut Content, path: &ExportPath, page_height: f32) {
+    let colour = path.colour;
+    content
+        .set_stroke_rgb(colour.red, colour.green, colour.blue)
+        .set_line_width(path.width)
+        .set_line_cap(LineCapStyle::RoundCap)
+        .set_line_join(LineJoinStyle::RoundJoin);
+
+    let mut points = path.points.iter();
+    let Some(first) = points.next() else {
+        return;
+    };
+    content.move_to(first.x, page_height - first.y);
+    if path.points.len() == 1 {
+        // a zero length line with round caps is a dot
+        content.line_to(first.x, page_height - first.y);
+    }
+    for point in points {
+        content.line_to(point.x, page_height - point.y);
+    }
+    content.stroke();
+}
+
+/// the colour as a flate compressed image with the alpha as its soft mask
+fn write_raster(pdf: &mut Pdf, raster: &RgbaImage, image_id: Ref, mask_id: Ref) {
+    let (width, height) = (raster.width() as i32, raster.height() as i32);
+    let pixels = raster.pixels();
+    let rgb: Vec<u8> = pixels
+        .clone()
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    let alpha: Vec<u8> = pixels.map(|pixel| pixel[3]).collect();
+    let (rgb, alpha) = (
+        compress_to_vec_zlib(&rgb, 6),
+        compress_to_vec_zlib(&alpha, 6),
+    );
+
+    let mut image = pdf.image_xobject(image_id, &rgb);
+    image.filter(Filter::FlateDecode);
+    image.width(width);
+    image.height(height);
+    image.color_space().device_rgb();
+    image.bits_per_component(8);
+    image.s_mask(mask_id);
+    image.finish();
+
+    let mut mask = pdf.image_xobject(mask_id, &alpha);
+    mask.filter(Filter::FlateDecode);
+    mask.width(width);
+    mask.height(height);
+    mask.color_space().device_gray();
+    mask.bits_per_component(8);
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+    use image::RgbaImage;
+
+    use super::pages_to_pdf;
+    use crate::drawable::{export::ExportPage, stroke::DrawableContent};
+
+    #[test]
+    fn one_pdf_page_per_page() {
+        let mut with_raster = DrawableContent::new(Vec2::ONE);
+        with_raster.raster = Some(RgbaImage::new(4, 4));
+        let empty = DrawableContent::new(Vec2::ONE);
+
+        let pages = [ExportPage::new(&with_raster), ExportPage::new(&empty)];
+        let pdf = pages_to_pdf(&pages);
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with(b"%PDF"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/SMask"));
+    }
+}
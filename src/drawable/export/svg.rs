@@ -0,0 +1,112 @@
+use std::fmt::Write;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use super::{encode_png, ExportError, ExportPage, ExportPath};
+
+/// a standalone SVG document of the page
+pub(super) fn page_to_svg(page: &ExportPage) -> Result<String, ExportError> {
+    let (width, height) = (page.size.x, page.size.y);
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}pt" height="{height}pt" viewBox="0 0 {width} {height}">"#
+    );
+    svg.push('\n');
+
+    for path in page.template_paths() {
+        svg.push_str(&path_element(&path));
+        svg.push('\n');
+    }
+    if let Some(raster) = page.raster() {
+        let png = STANDARD.encode(encode_png(raster)?);
+        let _ = writeln!(
+            svg,
+            r#"<image x="0" y="0" width="{width}" height="{height}" preserveAspectRatio="none" href="data:image/png;base64,{png}"/>"#
+        );
+    }
+    for path in page.paths() {
+        svg.push_str(&path_element(&path));
+        svg.push('\n');
+    }
+
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+fn path_element(path: &ExportPath) -> String {
+    let mut data = String::new();
+    for (index, point) in path.points.iter().enumerate() {
+        let command = if index == 0 { 'M' } else { 'L' };
+        let _ = write!(data, "{command}{:.2} {:.2} ", point.x, point.y);
+    }
+    // a line to the same point makes single point strokes show up as dots
+    if let [point] = path.points.as_slice() {
+        let _ = write!(data, "L{:.2} {:.2} ", point.x, point.y);
+    }
+
+    let colour = path.colour.to_hex();
+    format!(
+        r#"<path d="{}" fill="none" stroke="{}" stroke-opacity="{:.3}" stroke-width="{:.2}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+        data.trim_end(),
+        &colour[..7],
+        path.opacity,
+        path.width,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use super::page_to_svg;
+    use crate::drawable::{
+        export::ExportPage,
+        paint::PaintSettings,
+        stroke::{DrawableContent, Stroke, StrokePoint},
+        template::{PageTemplate, TemplateKind},
+    };
+
+    #[test]
+    fn strokes_become_paths() {
+        let mut content = DrawableContent::new(Vec2::new(1.0, 2.0));
+        let stroke = Stroke {
+            id: content.next_stroke_id(),
+            points: [(0.0, 0.0), (1.0, 0.5)]
+                .into_iter()
+                .map(|(x, y)| StrokePoint {
+                    position: Vec2::new(x, y),
+                    pressure: 1.0,
+                    time: 0.0,
+                })
+                .collect(),
+            paint_settings: PaintSettings {
+                radius: 0.05,
+                colour: Color::srgb(1.0, 0.0, 0.0),
+                ..default()
+            },
+            started_at: 0.0,
+        };
+        content.push_painted(stroke);
+
+        let svg = page_to_svg(&ExportPage::new(&content)).unwrap();
+        assert!(svg.contains(r#"viewBox="0 0 595 1190""#));
+        assert!(svg.contains(r#"d="M0.00 0.00 L595.00 595.00""#));
+        assert!(svg.contains(r##"stroke="#FF0000""##));
+        assert!(svg.contains(r#"stroke-width="59.50""#));
+        assert!(!svg.contains("<image"));
+    }
+
+    #[test]
+    fn template_lines_are_exported() {
+        let mut content = DrawableContent::new(Vec2::ONE);
+        content.template = PageTemplate {
+            kind: TemplateKind::Lined,
+            spacing: 0.5,
+            ..default()
+        };
+
+        let svg = page_to_svg(&ExportPage::new(&content)).unwrap();
+        // lines at the top, middle and bottom of the page
+        assert_eq!(svg.matches("<path").count(), 3);
+        assert!(svg.contains(r#"d="M595.00 297.50 L0.00 297.50""#));
+    }
+}
@@ -0,0 +1,357 @@
+//! Guides for drawing precise lines
+//!
+//! R places a ruler on the page under the cursor, or moves it there, and
+//! Shift+R removes it. Q and E rotate it by 15 degrees, or by one degree with
+//! Shift held. Strokes started along the edge of the ruler are pulled onto it.
+//! Holding Shift when starting a stroke draws a straight line, and while
+//! snapping is on, toggled with N, its ends snap to the intersections of the
+//! page template.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use super::{
+    drawable::DrawableCursor,
+    paint::{brush::blend_over, PaintImage, PaintSettings},
+    selection::polygon_contains,
+    stroke::DrawableContent,
+    template::PageTemplate,
+};
+use crate::config::Settings;
+
+/// Length of the ruler in page widths
+const RULER_LENGTH: f32 = 0.8;
+/// Width of the ruler body in page widths
+const RULER_WIDTH: f32 = 0.08;
+/// Distance between ticks in page widths, every fifth tick is longer
+const TICK_SPACING: f32 = 0.01;
+/// How far from the ruler edge a stroke can start and still be pulled onto it,
+/// in page widths
+const SNAP_DISTANCE: f32 = 0.015;
+const ROTATE_STEP: f32 = PI / 12.0;
+const FINE_ROTATE_STEP: f32 = PI / 180.0;
+const BODY_COLOUR: Color = Color::srgba(0.95, 0.8, 0.3, 0.3);
+const EDGE_COLOUR: Color = Color::srgb(0.55, 0.4, 0.1);
+/// Width of the edge and ticks in pixels
+const EDGE_WIDTH: f32 = 1.5;
+
+/// A ruler lying on a page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RulerGuide {
+    pub drawable: Entity,
+    /// middle of the measuring edge, in page units
+    pub centre: Vec2,
+    /// angle of the edge in radians, clockwise from the top of the page
+    pub angle: f32,
+    /// height of the page over its width
+    pub aspect: f32,
+}
+
+impl RulerGuide {
+    fn page_to_widths(&self, position: Vec2) -> Vec2 {
+        Vec2::new(position.x, position.y * self.aspect)
+    }
+
+    fn widths_to_page(&self, position: Vec2) -> Vec2 {
+        Vec2::new(position.x, position.y / self.aspect)
+    }
+
+    fn direction(&self) -> Vec2 {
+        Vec2::from_angle(self.angle)
+    }
+
+    /// the ends of the measuring edge in page units
+    pub fn edge(&self) -> (Vec2, Vec2) {
+        let centre = self.page_to_widths(self.centre);
+        let half = self.direction() * RULER_LENGTH / 2.0;
+        (
+            self.widths_to_page(centre - half),
+            self.widths_to_page(centre + half),
+        )
+    }
+
+    /// the closest point to `position` on the measuring edge, in page units
+    pub fn project(&self, position: Vec2) -> Vec2 {
+        let centre = self.page_to_widths(self.centre);
+        let along = (self.page_to_widths(position) - centre)
+            .dot(self.direction())
+            .clamp(-RULER_LENGTH / 2.0, RULER_LENGTH / 2.0);
+        self.widths_to_page(centre + self.direction() * along)
+    }
+
+    /// distance from `position` to the measuring edge in page widths
+    pub fn distance(&self, position: Vec2) -> f32 {
+        self.page_to_widths(position)
+            .distance(self.page_to_widths(self.project(position)))
+    }
+
+    /// the corners of the ruler body in page units, the edge is first
+    fn body(&self) -> [Vec2; 4] {
+        let (start, end) = self.edge();
+        let offset = self.direction().perp() * RULER_WIDTH;
+        [
+            start,
+            end,
+            self.widths_to_page(self.page_to_widths(end) + offset),
+            self.widths_to_page(self.page_to_widths(start) + offset),
+        ]
+    }
+
+    /// the tick marks along the edge in page units
+    fn ticks(&self) -> Vec<(Vec2, Vec2)> {
+        let (start, _) = self.edge();
+        let start = self.page_to_widths(start);
+        let normal = self.direction().perp();
+        let count = (RULER_LENGTH / TICK_SPACING).round() as usize;
+        (0..=count)
+            .map(|tick| {
+                let length = if tick % 5 == 0 { 0.4 } else { 0.2 } * RULER_WIDTH;
+                let base = start + self.direction() * tick as f32 * TICK_SPACING;
+                (
+                    self.widths_to_page(base),
+                    self.widths_to_page(base + normal * length),
+                )
+            })
+            .collect()
+    }
+
+    fn render(&self, image: &mut Image) {
+        let size = UVec2::new(image.width(), image.height());
+        let body = self.body().map(|corner| corner * size.as_vec2());
+        let low = body.iter().fold(Vec2::INFINITY, |a, b| a.min(*b));
+        let high = body.iter().fold(Vec2::NEG_INFINITY, |a, b| a.max(*b));
+        let low = low.floor().max(Vec2::ZERO).as_uvec2();
+        let high = high.ceil().as_uvec2().min(size);
+        let colour = BODY_COLOUR.to_srgba().to_f32_array();
+        if let Some(data) = image.data.as_mut() {
+            for y in low.y..high.y {
+                for x in low.x..high.x {
+                    if polygon_contains(&body, UVec2::new(x, y).as_vec2() + 0.5) {
+                        let index = ((y * size.x + x) * 4) as usize;
+                        blend_over(&mut data[index..index + 4], colour, 1.0);
+                    }
+                }
+            }
+        }
+
+        let settings = PaintSettings {
+            radius: EDGE_WIDTH / 2.0 / image.width() as f32,
+            colour: EDGE_COLOUR,
+            ..default()
+        };
+        let to_pixel = |position: Vec2| {
+            let position = position * size.as_vec2();
+            (position.x as usize, position.y as usize)
+        };
+        for (start, end) in [self.edge()].into_iter().chain(self.ticks()) {
+            let ((x1, y1), (x2, y2)) = (to_pixel(start), to_pixel(end));
+            image.draw_thick_line_antialias(x1, y1, x2, y2, &settings, Vec2::ONE);
+        }
+    }
+}
+
+/// The ruler, there's at most one across all pages
+#[derive(Resource, Debug, Default)]
+pub struct Ruler {
+    pub guide: Option<RulerGuide>,
+}
+
+impl Ruler {
+    /// draws the ruler if it's on `drawable`
+    pub(super) fn render_preview(&self, drawable: Entity, image: &mut Image) {
+        if let Some(guide) = self.guide.as_ref().filter(|g| g.drawable == drawable) {
+            guide.render(image);
+        }
+    }
+}
+
+/// Whether straight lines snap to the page template
+#[derive(Resource, Debug)]
+pub struct Snapping {
+    pub to_grid: bool,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Self { to_grid: true }
+    }
+}
+
+/// How a stroke is constrained, decided when it starts
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StrokeGuide {
+    /// the stroke is a straight line from its first point to the cursor
+    pub straight: bool,
+    /// the ruler the stroke was started along
+    pub ruler: Option<RulerGuide>,
+    /// the template straight lines snap to, with the height of the page over
+    /// its width
+    pub grid: Option<(PageTemplate, f32)>,
+}
+
+impl StrokeGuide {
+    /// the guide for a stroke starting at `start` on `drawable`
+    pub fn new(
+        drawable: Entity,
+        start: Vec2,
+        straight: bool,
+        ruler: &Ruler,
+        snapping: &Snapping,
+        content: &DrawableContent,
+    ) -> Self {
+        let ruler = ruler
+            .guide
+            .filter(|guide| guide.drawable == drawable && guide.distance(start) <= SNAP_DISTANCE);
+        let aspect = content.plane_scale.y / content.plane_scale.x;
+        let grid = (straight && snapping.to_grid && aspect.is_finite())
+            .then_some((content.template, aspect));
+        Self {
+            straight,
+            ruler,
+            grid,
+        }
+    }
+
+    /// moves a point of the stroke onto the ruler or the grid, in page units
+    pub fn apply(&self, position: Vec2) -> Vec2 {
+        if let Some(ruler) = &self.ruler {
+            return ruler.project(position);
+        }
+        let Some((template, aspect)) = self.grid else {
+            return position;
+        };
+        template
+            .snap(Vec2::new(position.x, position.y * aspect))
+            .map(|snapped| Vec2::new(snapped.x, snapped.y / aspect))
+            .unwrap_or(position)
+    }
+}
+
+/// Places, rotates and removes the ruler and toggles snapping, see the
+/// [module docs](self)
+pub(super) fn ruler_system(
+    mut cursor: DrawableCursor,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut ruler: ResMut<Ruler>,
+    mut snapping: ResMut<Snapping>,
+    mut content_query: Query<&mut DrawableContent>,
+) {
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let previous = ruler.guide;
+    let keys = &settings.key_bindings;
+
+    if keyboard_input.just_pressed(keys.ruler) {
+        if shift {
+            ruler.guide = None;
+        } else if let Some(hit) = cursor.hit() {
+            ruler.guide = Some(RulerGuide {
+                drawable: hit.drawable_object,
+                centre: hit.position,
+                angle: previous.map_or(0.0, |guide| guide.angle),
+                aspect: hit.plane_scale.y / hit.plane_scale.x,
+            });
+        }
+    }
+
+    let step = if shift { FINE_ROTATE_STEP } else { ROTATE_STEP };
+    if let Some(guide) = ruler.guide.as_mut() {
+        if keyboard_input.just_pressed(keys.rotate_ruler_left) {
+            guide.angle -= step;
+        }
+        if keyboard_input.just_pressed(keys.rotate_ruler_right) {
+            guide.angle += step;
+        }
+    }
+
+    if keyboard_input.just_pressed(keys.snapping) {
+        snapping.to_grid = !snapping.to_grid;
+        info!("Snapping to the grid: {}", snapping.to_grid);
+    }
+
+    if ruler.guide != previous {
+        for guide in [previous, ruler.guide].into_iter().flatten() {
+            if let Ok(mut content) = content_query.get_mut(guide.drawable) {
+                content.request_render();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::FRAC_PI_4;
+
+    use bevy::prelude::*;
+
+    use super::{RulerGuide, Snapping, StrokeGuide, RULER_LENGTH};
+    use crate::drawable::{
+        stroke::DrawableContent,
+        template::{PageTemplate, TemplateKind},
+    };
+
+    fn ruler(angle: f32, aspect: f32) -> RulerGuide {
+        RulerGuide {
+            drawable: Entity::PLACEHOLDER,
+            centre: Vec2::splat(0.5),
+            angle,
+            aspect,
+        }
+    }
+
+    #[test]
+    fn points_project_onto_the_edge() {
+        let guide = ruler(0.0, 1.0);
+        assert_eq!(guide.project(Vec2::new(0.3, 0.7)), Vec2::new(0.3, 0.5));
+        // past the end of the ruler
+        assert_eq!(
+            guide.project(Vec2::new(2.0, 0.5)),
+            Vec2::new(0.5 + RULER_LENGTH / 2.0, 0.5)
+        );
+        assert!((guide.distance(Vec2::new(0.3, 0.7)) - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ruler_angles_are_on_the_page() {
+        // on a page twice as tall as it's wide, 45 degrees moves half as far
+        // down in page units
+        let guide = ruler(FRAC_PI_4, 2.0);
+        let (start, end) = guide.edge();
+        let delta = end - start;
+        assert!((delta.x - 2.0 * delta.y).abs() < 1e-5, "{delta}");
+        let projected = guide.project(Vec2::new(0.6, 0.9));
+        assert!((projected - guide.centre).perp_dot(delta).abs() < 1e-5);
+    }
+
+    #[test]
+    fn straight_lines_snap_to_the_grid() {
+        let mut content = DrawableContent::new(Vec2::new(1.0, 2.0));
+        content.template = PageTemplate {
+            kind: TemplateKind::Grid,
+            spacing: 0.1,
+            ..default()
+        };
+        let snapping = Snapping::default();
+        let guide = |straight| {
+            StrokeGuide::new(
+                Entity::PLACEHOLDER,
+                Vec2::ZERO,
+                straight,
+                &default(),
+                &snapping,
+                &content,
+            )
+        };
+
+        // 0.26 page heights is 0.52 page widths, which snaps to 0.5
+        let snapped = guide(true).apply(Vec2::new(0.31, 0.26));
+        assert!(
+            (snapped - Vec2::new(0.3, 0.25)).length() < 1e-6,
+            "{snapped}"
+        );
+        // freehand strokes aren't snapped
+        let point = Vec2::new(0.31, 0.26);
+        assert_eq!(guide(false).apply(point), point);
+    }
+}
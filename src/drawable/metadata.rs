@@ -0,0 +1,183 @@
+//! Titles, tags, times and bookmarks of pages, and the table of contents
+//!
+//! The table of contents is a page with the title and tags of every other
+//! page written on it, it's made again each time [`UpdateContents`] is sent.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ab_glyph::FontRef;
+use bevy::prelude::*;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::{show_pages, NotebookOpened, NotebookPages},
+    pages::PageControl,
+    stroke::DrawableContent,
+    template::SetPageTemplate,
+    text::{draw_text, TextSettings},
+    DefaultResolution, PageFilter,
+};
+
+/// Where the table of contents starts on the page, in page units
+const CONTENTS_MARGIN: f32 = 0.08;
+
+/// seconds since the Unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageMetadata {
+    pub title: String,
+    pub tags: Vec<String>,
+    /// seconds since the Unix epoch, 0 if it isn't known
+    pub created: u64,
+    /// seconds since the Unix epoch when the page was last drawn on
+    pub modified: u64,
+    pub bookmarked: bool,
+    /// the page is the table of contents
+    pub contents: bool,
+}
+
+impl PageMetadata {
+    /// metadata of a page created now
+    pub fn new() -> Self {
+        let now = unix_time();
+        Self {
+            created: now,
+            modified: now,
+            ..default()
+        }
+    }
+
+    /// the title, or the page number if it doesn't have one
+    pub fn display_title(&self, index: usize) -> String {
+        if self.title.trim().is_empty() {
+            format!("Page {}", index + 1)
+        } else {
+            self.title.clone()
+        }
+    }
+}
+
+/// Message for making the table of contents, it replaces the old one and is
+/// put first
+#[derive(Debug, Message)]
+pub struct UpdateContents;
+
+/// the text of the table of contents for `pages`, which are numbered as if
+/// the contents page is put before them
+fn contents_text(pages: &[DrawableContent]) -> String {
+    let mut text = String::from("Contents\n");
+    for (index, page) in pages.iter().enumerate() {
+        // the contents page is the first page
+        let index = index + 1;
+        text.push('\n');
+        let metadata = &page.metadata;
+        text.push_str(&format!(
+            "{}   {}",
+            index + 1,
+            metadata.display_title(index)
+        ));
+        for tag in &metadata.tags {
+            text.push_str(&format!("  #{tag}"));
+        }
+    }
+    text
+}
+
+/// stamps the modified time of pages that are drawn on
+pub(super) fn update_modified_times(
+    mut content_query: Query<&mut DrawableContent, (PageFilter, Changed<DrawableContent>)>,
+    mut page_reader: MessageReader<PageControl>,
+    mut opened_reader: MessageReader<NotebookOpened>,
+) {
+    // the shown pages were swapped, not changed
+    if page_reader.read().count() + opened_reader.read().count() > 0 {
+        return;
+    }
+    let now = unix_time();
+    for mut content in &mut content_query {
+        if !content.is_added() {
+            content.bypass_change_detection().metadata.modified = now;
+        }
+    }
+}
+
+pub(super) fn update_contents_system(
+    mut reader: MessageReader<UpdateContents>,
+    mut drawable_query: Query<(Entity, &mut DrawableContent), PageFilter>,
+    mut notebook_pages: ResMut<NotebookPages>,
+    mut template_writer: MessageWriter<SetPageTemplate>,
+    text_settings: Res<TextSettings>,
+    fonts: Res<Assets<Font>>,
+    default_resolution: Res<DefaultResolution>,
+) {
+    if reader.read().count() == 0 {
+        return;
+    }
+    let mut drawables: Vec<_> = drawable_query.iter_mut().collect();
+    drawables.sort_by_key(|(entity, _)| *entity);
+    let Some(plane_scale) = drawables.first().map(|(_, content)| content.plane_scale) else {
+        return;
+    };
+    let Some(font) = fonts
+        .get(&text_settings.font)
+        .and_then(|font| FontRef::try_from_slice(&font.data).ok())
+    else {
+        warn!("Couldn't write the table of contents, the font isn't loaded");
+        return;
+    };
+
+    let mut pages = notebook_pages.with_shown(drawables.iter().map(|(_, content)| &**content));
+    pages.retain(|page| !page.metadata.contents);
+
+    let resolution = default_resolution.0 as u32;
+    let mut raster = RgbaImage::new(resolution, resolution);
+    let size_px = text_settings.size / plane_scale.x * resolution as f32;
+    draw_text(
+        &mut raster,
+        UVec2::splat(resolution),
+        &font,
+        &contents_text(&pages),
+        Vec2::splat(CONTENTS_MARGIN * resolution as f32),
+        size_px,
+        Color::BLACK.to_srgba().to_f32_array(),
+    );
+    let mut contents = DrawableContent::new(plane_scale);
+    contents.raster = Some(raster);
+    contents.metadata.title = "Contents".to_string();
+    contents.metadata.contents = true;
+    pages.insert(0, contents);
+
+    show_pages(&mut drawables, &mut pages, 0, &mut template_writer);
+    notebook_pages.set_pages(pages, 0);
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use super::{contents_text, PageMetadata};
+    use crate::drawable::stroke::DrawableContent;
+
+    #[test]
+    fn contents_lists_titles_and_tags() {
+        let mut titled = DrawableContent::new(Vec2::ONE);
+        titled.metadata = PageMetadata {
+            title: "Chemistry".to_string(),
+            tags: vec!["school".to_string()],
+            ..PageMetadata::new()
+        };
+        let untitled = DrawableContent::new(Vec2::ONE);
+        assert_eq!(
+            contents_text(&[titled, untitled]),
+            "Contents\n\n2   Chemistry  #school\n3   Page 3"
+        );
+    }
+}
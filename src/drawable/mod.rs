@@ -1,26 +1,70 @@
+pub mod autosave;
+pub mod clipboard;
+pub mod document;
 pub mod drawable;
 pub mod drawable_builder;
 pub mod drawable_material;
 // for image related things to do with drawing
 mod drawable_image;
+pub mod export;
+pub mod guide;
+pub mod metadata;
+pub mod pages;
 mod paint;
+pub mod recording;
+pub mod remote;
+pub mod selection;
+pub mod stroke;
+pub mod sync;
+pub mod template;
+pub mod text;
+pub mod tool;
 
+use std::path::Path;
+
+use autosave::{autosave_system, Autosave, RecoveredSession, RECOVERY_FOLDER};
 use bevy::app::Plugin;
-use bevy::app::Update;
-use bevy::ecs::schedule::IntoScheduleConfigs;
+use bevy::app::{PostUpdate, Update};
+use bevy::ecs::schedule::common_conditions::{not, resource_equals, resource_exists};
+use bevy::ecs::schedule::{IntoScheduleConfigs, SystemCondition};
 use bevy::pbr::MaterialPlugin;
 use bevy::state::condition::in_state;
-use drawable_builder::add_drawable_system;
+use clipboard::{clipboard_system, Clipboard};
+use document::{
+    new_notebook, open_notebook, open_startup_notebook, save_notebook, track_unsaved_changes,
+    NewNotebook, NotebookOpened, NotebookPages, NotebookSaved, OpenNotebook, SaveNotebook,
+    StartupNotebook, UnsavedChanges,
+};
+use drawable_builder::{add_drawable_system, resize_drawable_system};
+use export::{export_notebook, ExportNotebook};
+use guide::{ruler_system, Ruler, Snapping};
+use metadata::{update_contents_system, update_modified_times, UpdateContents};
+use pages::{page_control_system, PageControl};
 use paint::PaintPlugin;
+use recording::{
+    finish_replay_system, record_input_system, recording_control_system, replay_system,
+    save_replay_frame, Recorder, RecordingControl, Replay,
+};
+use selection::{selection_tool_system, Selection};
+use stroke::{
+    apply_stroke_edits, render_edited_drawables, stroke_selection_system, EditStroke,
+    SelectedStroke,
+};
+use sync::{apply_remote_strokes, send_local_strokes, SyncConnection};
+use template::{apply_page_templates, cycle_page_template_system, SetPageTemplate};
+use text::{not_typing, text_tool_system, TextEditor, TextSettings};
+use tool::{select_tool_system, Tool};
 
 //re-export
 pub use crate::drawable::drawable::*;
 use crate::drawable::drawable_image::clear_drawable_image;
 use crate::drawable::drawable_image::save_drawable_image;
 pub use crate::drawable::drawable_material::*;
+use crate::notebook::desk::select_notebook_system;
 use crate::AppState;
 pub(crate) use drawable_image::ClearDrawableImage;
 pub(crate) use drawable_image::SaveDrawableImage;
+pub use paint::{stamp::BrushTips, Brush, PaintSettings};
 
 #[derive(Debug, Default)]
 pub struct DrawablePlugin {}
@@ -30,8 +74,130 @@ impl Plugin for DrawablePlugin {
         app.add_plugins(MaterialPlugin::<DrawableMaterial>::default());
         app.add_plugins(PaintPlugin::default());
 
-        app.add_systems(Update, add_drawable_system);
-        app.add_systems(Update, drawing_system.run_if(in_state(AppState::Playing)));
+        app.add_systems(Update, (add_drawable_system, resize_drawable_system));
+        app.init_resource::<Tool>();
+        app.init_resource::<DefaultResolution>();
+        app.add_systems(
+            Update,
+            select_tool_system.run_if(in_state(AppState::Playing).and(not_typing)),
+        );
+        app.init_resource::<Ruler>();
+        app.init_resource::<Snapping>();
+        app.add_systems(
+            Update,
+            ruler_system.run_if(in_state(AppState::Playing).and(not_typing)),
+        );
+        app.add_message::<PenSample>();
+        app.add_systems(
+            Update,
+            (pen_input_system.in_set(PenInputSet), drawing_system)
+                .chain()
+                .run_if(in_state(AppState::Playing).and(resource_equals(Tool::Pen))),
+        );
+
+        // drawing together with other apps
+        app.add_systems(
+            Update,
+            (
+                send_local_strokes.after(drawing_system),
+                apply_remote_strokes.before(render_edited_drawables),
+            )
+                .run_if(resource_exists::<SyncConnection>),
+        );
+
+        // recording and replaying input
+        app.add_message::<RecordingControl>();
+        app.init_resource::<Recorder>();
+        app.configure_sets(Update, PenInputSet.run_if(not(resource_exists::<Replay>)));
+        app.add_systems(
+            Update,
+            (
+                recording_control_system,
+                // the notebook is put back before it's saved, opened or swapped
+                finish_replay_system
+                    .before(replay_system)
+                    .before(save_notebook)
+                    .before(open_notebook)
+                    .before(select_notebook_system),
+                replay_system
+                    .before(drawing_system)
+                    .run_if(in_state(AppState::Playing).and(resource_exists::<Replay>)),
+                record_input_system,
+            ),
+        );
+        app.add_systems(PostUpdate, save_replay_frame);
+
+        // editing strokes
+        app.add_message::<EditStroke>();
+        app.init_resource::<SelectedStroke>();
+        app.init_resource::<Selection>();
+        app.init_resource::<Clipboard>();
+        app.init_resource::<TextEditor>();
+        app.init_resource::<TextSettings>();
+        app.add_systems(
+            Update,
+            (
+                stroke_selection_system.run_if(in_state(AppState::Playing).and(not_typing)),
+                selection_tool_system.run_if(in_state(AppState::Playing)),
+                clipboard_system.run_if(in_state(AppState::Playing).and(not_typing)),
+                text_tool_system.run_if(in_state(AppState::Playing)),
+                apply_stroke_edits,
+                render_edited_drawables,
+            )
+                .chain(),
+        );
+
+        app.add_message::<SetPageTemplate>();
+        app.add_systems(
+            Update,
+            (
+                cycle_page_template_system.run_if(in_state(AppState::Playing).and(not_typing)),
+                apply_page_templates,
+            )
+                .chain(),
+        );
+
+        app.add_message::<SaveNotebook>();
+        app.add_message::<OpenNotebook>();
+        app.add_message::<NotebookOpened>();
+        app.add_message::<NewNotebook>();
+        app.init_resource::<NotebookPages>();
+        app.init_resource::<UnsavedChanges>();
+        app.add_message::<PageControl>();
+        app.add_message::<UpdateContents>();
+        app.add_systems(
+            Update,
+            (
+                (
+                    save_notebook,
+                    (
+                        new_notebook,
+                        open_startup_notebook.run_if(resource_exists::<StartupNotebook>),
+                        open_notebook,
+                    )
+                        .chain(),
+                    page_control_system,
+                    update_contents_system,
+                ),
+                (track_unsaved_changes, update_modified_times),
+            )
+                .chain(),
+        );
+        app.add_message::<NotebookSaved>();
+        let autosave = Autosave::default();
+        if let Some(recovered) = RecoveredSession::find(Path::new(RECOVERY_FOLDER), &autosave) {
+            app.insert_resource(recovered);
+        }
+        app.insert_resource(autosave);
+        // replays draw on pages that aren't the notebook's
+        app.add_systems(
+            Update,
+            autosave_system
+                .after(save_notebook)
+                .run_if(not(resource_exists::<Replay>)),
+        );
+        app.add_message::<ExportNotebook>();
+        app.add_systems(Update, export_notebook);
 
         // debug stuff
         app.add_message::<SaveDrawableImage>();
@@ -0,0 +1,171 @@
+//! Going to, reordering, inserting and deleting the pages of the notebook
+//!
+//! The drawables show the pages from [`NotebookPages::first_shown`] on, going
+//! to another page swaps the pages shown on them.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::{show_pages, NotebookPages},
+    stroke::DrawableContent,
+    template::SetPageTemplate,
+    PageFilter,
+};
+
+/// Message for changing the pages of the notebook, pages are numbered from 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Message)]
+pub enum PageControl {
+    /// shows page `index` on the first drawable
+    GoTo(usize),
+    /// moves page `from` so it's page `to`
+    Move {
+        from: usize,
+        to: usize,
+    },
+    /// adds a blank page so it's page `index`, and goes to it
+    Insert(usize),
+    Delete(usize),
+}
+
+impl PageControl {
+    /// whether this changes the pages rather than which are shown
+    pub fn edits(&self) -> bool {
+        !matches!(self, PageControl::GoTo(_))
+    }
+}
+
+/// applies `control` to `pages`, returns the page to show first so the same
+/// page stays shown unless it's gone to, inserted or deleted
+fn edit_pages(pages: &mut Vec<DrawableContent>, shown: usize, control: PageControl) -> usize {
+    let last = pages.len().saturating_sub(1);
+    match control {
+        PageControl::GoTo(index) => index.min(last),
+        PageControl::Move { from, to } => {
+            if pages.is_empty() {
+                return 0;
+            }
+            let (from, to) = (from.min(last), to.min(last));
+            let page = pages.remove(from);
+            pages.insert(to, page);
+            if shown == from {
+                to
+            } else if from < shown && shown <= to {
+                shown - 1
+            } else if to <= shown && shown < from {
+                shown + 1
+            } else {
+                shown
+            }
+        }
+        PageControl::Insert(index) => {
+            let index = index.min(pages.len());
+            // a page like its neighbour, so it has the same size and template
+            let neighbour = pages.get(index.saturating_sub(1));
+            let mut page = DrawableContent::new(neighbour.map_or(Vec2::ONE, |n| n.plane_scale));
+            if let Some(neighbour) = neighbour {
+                page.template = neighbour.template;
+            }
+            pages.insert(index, page);
+            index
+        }
+        PageControl::Delete(index) => {
+            if index >= pages.len() {
+                return shown;
+            }
+            let page = pages.remove(index);
+            if pages.is_empty() {
+                // a notebook always has a page
+                let mut blank = DrawableContent::new(page.plane_scale);
+                blank.template = page.template;
+                pages.push(blank);
+            }
+            let shown = if index < shown { shown - 1 } else { shown };
+            shown.min(pages.len() - 1)
+        }
+    }
+}
+
+/// the shown pages, by their number in the notebook
+pub(super) fn shown_pages(
+    drawable_query: &Query<Entity, PageFilter>,
+    notebook_pages: &NotebookPages,
+) -> Vec<(usize, Entity)> {
+    let mut drawables: Vec<_> = drawable_query.iter().collect();
+    drawables.sort();
+    (notebook_pages.first_shown..).zip(drawables).collect()
+}
+
+pub(super) fn page_control_system(
+    mut reader: MessageReader<PageControl>,
+    mut drawable_query: Query<(Entity, &mut DrawableContent), PageFilter>,
+    mut notebook_pages: ResMut<NotebookPages>,
+    mut template_writer: MessageWriter<SetPageTemplate>,
+) {
+    for control in reader.read() {
+        let mut drawables: Vec<_> = drawable_query.iter_mut().collect();
+        if drawables.is_empty() {
+            continue;
+        }
+        drawables.sort_by_key(|(entity, _)| *entity);
+        let mut pages = notebook_pages.with_shown(drawables.iter().map(|(_, content)| &**content));
+        let first_shown = edit_pages(&mut pages, notebook_pages.first_shown, *control);
+        show_pages(
+            &mut drawables,
+            &mut pages,
+            first_shown,
+            &mut template_writer,
+        );
+        notebook_pages.set_pages(pages, first_shown);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use super::{edit_pages, PageControl};
+    use crate::drawable::stroke::DrawableContent;
+
+    /// pages told apart by their size
+    fn pages(count: usize) -> Vec<DrawableContent> {
+        (0..count)
+            .map(|index| DrawableContent::new(Vec2::splat(index as f32)))
+            .collect()
+    }
+
+    fn order(pages: &[DrawableContent]) -> Vec<f32> {
+        pages.iter().map(|page| page.plane_scale.x).collect()
+    }
+
+    #[test]
+    fn moving_keeps_the_shown_page() {
+        let mut notebook = pages(4);
+        let shown = edit_pages(&mut notebook, 2, PageControl::Move { from: 0, to: 3 });
+        assert_eq!(order(&notebook), [1.0, 2.0, 3.0, 0.0]);
+        assert_eq!(notebook[shown].plane_scale.x, 2.0);
+
+        let shown = edit_pages(&mut notebook, shown, PageControl::Move { from: 1, to: 0 });
+        assert_eq!(order(&notebook), [2.0, 1.0, 3.0, 0.0]);
+        assert_eq!(shown, 0);
+    }
+
+    #[test]
+    fn inserting_and_deleting() {
+        let mut notebook = pages(2);
+        let shown = edit_pages(&mut notebook, 0, PageControl::Insert(1));
+        assert_eq!(shown, 1);
+        assert_eq!(notebook.len(), 3);
+        // the new page is like the one before it
+        assert_eq!(notebook[1].plane_scale.x, 0.0);
+
+        let shown = edit_pages(&mut notebook, 2, PageControl::Delete(0));
+        assert_eq!(shown, 1);
+        assert_eq!(order(&notebook), [0.0, 1.0]);
+
+        edit_pages(&mut notebook, 0, PageControl::Delete(0));
+        let shown = edit_pages(&mut notebook, 0, PageControl::Delete(0));
+        assert_eq!(shown, 0);
+        assert_eq!(notebook.len(), 1);
+    }
+}
@@ -0,0 +1,440 @@
+//! Brush presets and the raster state of a stroke being painted with them
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    stamp::{BrushTips, Stamp, StampState},
+    PaintImage, PaintSettings, StrokeEngine,
+};
+
+/// The kind of brush strokes are painted with
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Brush {
+    /// hard round brush
+    #[default]
+    Round,
+    /// grainy pencil, the alpha is textured with noise
+    Pencil,
+    /// felt marker with soft edges
+    Marker,
+    /// flat translucent highlighter that doesn't build up within a stroke
+    Highlighter,
+    /// flat nib, the stroke is thin when moving along the nib and wide across it
+    Calligraphy {
+        /// angle of the nib in radians
+        nib_angle: f32,
+    },
+}
+
+impl Brush {
+    pub const PRESETS: [Brush; 5] = [
+        Brush::Round,
+        Brush::Pencil,
+        Brush::Marker,
+        Brush::Highlighter,
+        Brush::Calligraphy {
+            nib_angle: std::f32::consts::FRAC_PI_4,
+        },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Brush::Round => "Round",
+            Brush::Pencil => "Pencil",
+            Brush::Marker => "Marker",
+            Brush::Highlighter => "Highlighter",
+            Brush::Calligraphy { .. } => "Calligraphy",
+        }
+    }
+
+    pub(crate) fn opacity(&self) -> f32 {
+        match self {
+            Brush::Pencil => 0.9,
+            Brush::Marker => 0.85,
+            Brush::Highlighter => 0.35,
+            Brush::Round | Brush::Calligraphy { .. } => 1.0,
+        }
+    }
+
+    /// whether overlapping parts of one stroke get darker
+    fn accumulates(&self) -> bool {
+        !matches!(self, Brush::Highlighter)
+    }
+
+    /// radius of the brush when moving in `direction`
+    fn radius(&self, radius: f32, direction: Vec2) -> f32 {
+        match self {
+            Brush::Calligraphy { nib_angle } if direction != Vec2::ZERO => {
+                let nib = Vec2::from_angle(*nib_angle);
+                // moving along the nib only leaves a thin line
+                let across = nib.perp_dot(direction.normalize()).abs();
+                radius * across.max(0.2)
+            }
+            _ => radius,
+        }
+    }
+
+    /// how much a pixel at `distance` from the centre of the stroke is covered
+    fn coverage(&self, distance: f32, radius: f32, pixel: (i32, i32)) -> f32 {
+        let hard_edge = (radius - distance + 0.5).clamp(0.0, 1.0);
+        let coverage = match self {
+            Brush::Pencil => hard_edge * (0.35 + 0.65 * grain(pixel.0, pixel.1)),
+            Brush::Marker => 1.0 - smoothstep(radius * 0.5, radius, distance),
+            Brush::Round | Brush::Highlighter | Brush::Calligraphy { .. } => hard_edge,
+        };
+        coverage * self.opacity()
+    }
+}
+
+/// Raster state for a single stroke
+#[derive(Debug)]
+pub struct StrokeRaster {
+    canvas: StrokeCanvas,
+    /// only used by the stamp engine
+    stamp: Option<StampState>,
+    last_point: Option<Vec2>,
+}
+
+/// Combines the coverage of a stroke with the image
+#[derive(Debug, Default)]
+struct StrokeCanvas {
+    /// the image before the stroke, kept for brushes that don't accumulate
+    base: Option<Vec<u8>>,
+    /// highest coverage of each pixel within the stroke, for brushes that don't accumulate
+    coverage: Vec<f32>,
+}
+
+impl StrokeCanvas {
+    fn composite(&mut self, data: &mut [u8], index: usize, colour: [f32; 4], coverage: f32) {
+        let bytes = &mut data[index * 4..index * 4 + 4];
+        match &self.base {
+            None => blend_over(bytes, colour, coverage),
+            Some(base) => {
+                if coverage > self.coverage[index] {
+                    self.coverage[index] = coverage;
+                    bytes.copy_from_slice(&base[index * 4..index * 4 + 4]);
+                    blend_over(bytes, colour, coverage);
+                }
+            }
+        }
+    }
+}
+
+impl StrokeRaster {
+    pub fn new(image: &Image, paint_settings: &PaintSettings, brush_tips: &BrushTips) -> Self {
+        let canvas = if paint_settings.brush.accumulates() {
+            StrokeCanvas::default()
+        } else {
+            StrokeCanvas {
+                base: image.data.clone(),
+                coverage: vec![0.0; (image.width() * image.height()) as usize],
+            }
+        };
+        let stamp = match &paint_settings.engine {
+            StrokeEngine::Line => None,
+            StrokeEngine::Stamp(stamp_settings) => Some(StampState::new(
+                brush_tips.get(stamp_settings.tip.as_deref()),
+            )),
+        };
+        Self {
+            canvas,
+            stamp,
+            last_point: None,
+        }
+    }
+
+    /// paints the stroke up to `point`, in pixel coordinates
+    pub fn add_point(
+        &mut self,
+        image: &mut Image,
+        point: Vec2,
+        paint_settings: &PaintSettings,
+        plane_scale: Vec2,
+    ) {
+        let last_point = self.last_point.replace(point);
+        let scale_factor = image.width() as f32 / plane_scale.x;
+        let radius = paint_settings.radius * scale_factor;
+
+        if let (StrokeEngine::Stamp(stamp_settings), Some(stamp_state)) =
+            (&paint_settings.engine, &mut self.stamp)
+        {
+            let start = last_point.unwrap_or(point);
+            for stamp in stamp_state.stamps(start, point, radius * 2.0, stamp_settings) {
+                self.paint_stamp(image, &stamp, paint_settings);
+            }
+            return;
+        }
+
+        if paint_settings.brush == Brush::Round {
+            let (x, y) = to_pixel(point);
+            match last_point.map(to_pixel) {
+                None => image.draw_spot(x, y, paint_settings, plane_scale),
+                Some((last_x, last_y)) => image.draw_thick_line_antialias(
+                    last_x,
+                    last_y,
+                    x,
+                    y,
+                    paint_settings,
+                    plane_scale,
+                ),
+            }
+            return;
+        }
+
+        match last_point {
+            None => self.paint_segment(image, point, point, radius, paint_settings, true),
+            Some(last_point) => {
+                self.paint_segment(image, last_point, point, radius, paint_settings, false)
+            }
+        }
+    }
+
+    /// paints a capsule from `start` to `end`, when `include_start` is false the
+    /// start cap is left out so it isn't painted twice where segments join
+    fn paint_segment(
+        &mut self,
+        image: &mut Image,
+        start: Vec2,
+        end: Vec2,
+        radius: f32,
+        paint_settings: &PaintSettings,
+        include_start: bool,
+    ) {
+        let brush = paint_settings.brush;
+        let colour = paint_settings.colour.to_srgba().to_f32_array();
+        let width = image.width() as i32;
+        let height = image.height() as i32;
+
+        let direction = end - start;
+        let radius = brush.radius(radius, direction);
+        let length_sq = direction.length_squared();
+
+        let min = (start.min(end) - radius - 1.0).floor();
+        let max = (start.max(end) + radius + 1.0).ceil();
+        let min_x = (min.x as i32).max(0);
+        let min_y = (min.y as i32).max(0);
+        let max_x = (max.x as i32).min(width - 1);
+        let max_y = (max.y as i32).min(height - 1);
+
+        let Some(data) = image.data.as_mut() else {
+            return;
+        };
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let pixel = Vec2::new(x as f32, y as f32);
+                let t = if length_sq == 0.0 {
+                    0.0
+                } else {
+                    (pixel - start).dot(direction) / length_sq
+                };
+                if !include_start && t < 0.0 {
+                    continue;
+                }
+                let distance = pixel.distance(start + direction * t.clamp(0.0, 1.0));
+
+                let coverage = brush.coverage(distance, radius, (x, y));
+                if coverage <= 0.0 {
+                    continue;
+                }
+
+                let index = (y * width + x) as usize;
+                self.canvas.composite(data, index, colour, coverage);
+            }
+        }
+    }
+
+    fn paint_stamp(&mut self, image: &mut Image, stamp: &Stamp, paint_settings: &PaintSettings) {
+        let Some(stamp_state) = &self.stamp else {
+            return;
+        };
+        let colour = paint_settings.colour.to_srgba().to_f32_array();
+        let opacity = paint_settings.brush.opacity();
+        let width = image.width() as i32;
+        let height = image.height() as i32;
+
+        // the tip is square, so this covers it at any rotation
+        let reach = stamp.diameter * std::f32::consts::FRAC_1_SQRT_2 + 1.0;
+        let min_x = ((stamp.centre.x - reach).floor() as i32).max(0);
+        let min_y = ((stamp.centre.y - reach).floor() as i32).max(0);
+        let max_x = ((stamp.centre.x + reach).ceil() as i32).min(width - 1);
+        let max_y = ((stamp.centre.y + reach).ceil() as i32).min(height - 1);
+
+        let Some(data) = image.data.as_mut() else {
+            return;
+        };
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let coverage = stamp_state.coverage(stamp, Vec2::new(x as f32, y as f32)) * opacity;
+                if coverage <= 0.0 {
+                    continue;
+                }
+                let index = (y * width + x) as usize;
+                self.canvas.composite(data, index, colour, coverage);
+            }
+        }
+    }
+}
+
+fn to_pixel(point: Vec2) -> (usize, usize) {
+    (point.x.max(0.0) as usize, point.y.max(0.0) as usize)
+}
+
+/// alpha composites `colour` with `alpha` over an srgb rgba8 pixel
+pub(crate) fn blend_over(pixel: &mut [u8], colour: [f32; 4], alpha: f32) {
+    let alpha = alpha * colour[3];
+    let dst_alpha = pixel[3] as f32 / 255.0;
+    let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+    if out_alpha <= 0.0 {
+        return;
+    }
+    for channel in 0..3 {
+        let dst = pixel[channel] as f32 / 255.0;
+        let out = (colour[channel] * alpha + dst * dst_alpha * (1.0 - alpha)) / out_alpha;
+        pixel[channel] = (out * 255.0).round() as u8;
+    }
+    pixel[3] = (out_alpha * 255.0).round() as u8;
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// deterministic per pixel noise between 0 and 1
+fn grain(x: i32, y: i32) -> f32 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0x5bd1_e995);
+    hash ^= hash >> 15;
+    (hash & 0xffff) as f32 / 65535.0
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        asset::RenderAssetUsages,
+        prelude::*,
+        render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    };
+
+    use super::{Brush, StrokeRaster};
+    use crate::drawable::paint::{
+        stamp::{BrushTip, BrushTips, StampSettings},
+        PaintSettings, StrokeEngine,
+    };
+
+    const SIZE: u32 = 64;
+
+    fn blank_image() -> Image {
+        Image::new_fill(
+            Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::MAIN_WORLD,
+        )
+    }
+
+    fn paint(brush: Brush, points: &[(f32, f32)]) -> Image {
+        paint_with(
+            PaintSettings {
+                radius: 6.0,
+                brush,
+                ..default()
+            },
+            &BrushTips::default(),
+            points,
+        )
+    }
+
+    fn paint_with(settings: PaintSettings, brush_tips: &BrushTips, points: &[(f32, f32)]) -> Image {
+        let mut image = blank_image();
+        // one world unit per pixel
+        let plane_scale = Vec2::splat(SIZE as f32);
+
+        let mut stroke = StrokeRaster::new(&image, &settings, brush_tips);
+        for &(x, y) in points {
+            stroke.add_point(&mut image, Vec2::new(x, y), &settings, plane_scale);
+        }
+        image
+    }
+
+    fn alpha(image: &Image, x: u32, y: u32) -> u8 {
+        image.pixel_bytes(UVec3::new(x, y, 0)).unwrap()[3]
+    }
+
+    #[test]
+    fn highlighter_does_not_accumulate() {
+        let single = paint(Brush::Highlighter, &[(10.0, 32.0), (50.0, 32.0)]);
+        let scribbled = paint(
+            Brush::Highlighter,
+            &[(10.0, 32.0), (50.0, 32.0), (10.0, 32.0), (50.0, 32.0)],
+        );
+
+        assert!(alpha(&single, 30, 32) > 0);
+        assert_eq!(alpha(&single, 30, 32), alpha(&scribbled, 30, 32));
+    }
+
+    #[test]
+    fn marker_has_soft_edges() {
+        let image = paint(Brush::Marker, &[(32.0, 32.0)]);
+
+        let centre = alpha(&image, 32, 32);
+        let edge = alpha(&image, 37, 32);
+        assert!(edge > 0);
+        assert!(edge < centre);
+    }
+
+    #[test]
+    fn pencil_is_grainy() {
+        let image = paint(Brush::Pencil, &[(10.0, 32.0), (50.0, 32.0)]);
+
+        let alphas: Vec<_> = (15..45).map(|x| alpha(&image, x, 32)).collect();
+        assert!(alphas.iter().all(|&alpha| alpha > 0));
+        assert!(alphas.iter().any(|&alpha| alpha != alphas[0]));
+    }
+
+    #[test]
+    fn calligraphy_width_depends_on_direction() {
+        let brush = Brush::Calligraphy { nib_angle: 0.0 };
+        let along_nib = paint(brush, &[(10.0, 32.0), (50.0, 32.0)]);
+        let across_nib = paint(brush, &[(32.0, 10.0), (32.0, 50.0)]);
+
+        let thickness_along = (0..SIZE).filter(|&y| alpha(&along_nib, 30, y) > 0).count();
+        let thickness_across = (0..SIZE).filter(|&x| alpha(&across_nib, x, 30) > 0).count();
+        assert!(thickness_along < thickness_across);
+    }
+
+    #[test]
+    fn stamp_uses_named_tip() {
+        let mut brush_tips = BrushTips::default();
+        // a tip that only paints its left half
+        let mut half = blank_image();
+        for x in 0..SIZE / 2 {
+            for y in 0..SIZE {
+                half.set_color_at(x, y, Color::WHITE).unwrap();
+            }
+        }
+        brush_tips.insert("half", BrushTip::from_image(&half).unwrap());
+
+        let settings = PaintSettings {
+            radius: 8.0,
+            engine: StrokeEngine::Stamp(StampSettings {
+                tip: Some("half".to_owned()),
+                ..default()
+            }),
+            ..default()
+        };
+        let image = paint_with(settings, &brush_tips, &[(32.0, 32.0)]);
+
+        assert!(alpha(&image, 28, 32) > 0);
+        assert_eq!(alpha(&image, 36, 32), 0);
+    }
+}
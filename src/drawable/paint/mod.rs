@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 use paint_input::PaintInput;
+use serde::{Deserialize, Serialize};
 // handles painting on a texture
 
 use bevy::color::Alpha;
@@ -8,8 +9,17 @@ use bevy::{color::Color, image::Image, math::Vec2};
 use drawing_util::antialias_thick_line::draw_antialiased_thick_line;
 use drawing_util::{objects::Point, thick_line::ThickLine};
 
+use crate::{config::Settings, AppState};
+
+use super::text::not_typing;
+
+pub mod brush;
 mod drawing_util;
 pub mod paint_input;
+pub mod stamp;
+
+pub use brush::{Brush, StrokeRaster};
+use stamp::{convert_brush_tips, load_brush_tips_folder, BrushTips, StampSettings};
 
 #[derive(Debug, Default)]
 pub struct PaintPlugin {}
@@ -17,12 +27,26 @@ pub struct PaintPlugin {}
 impl Plugin for PaintPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.init_resource::<PaintInput>();
+        app.init_resource::<PaintSettings>();
+        app.init_resource::<BrushTips>();
+        app.add_systems(Startup, load_brush_tips_folder);
+        app.add_systems(Update, convert_brush_tips);
+        app.add_systems(
+            Update,
+            (select_brush_system, select_engine_system)
+                .run_if(in_state(AppState::Playing).and(not_typing)),
+        );
     }
 }
 
+/// The brush currently used for drawing
+#[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
 pub struct PaintSettings {
-    radius: f32,
-    colour: Color,
+    /// radius of the brush in world units
+    pub radius: f32,
+    pub colour: Color,
+    pub brush: Brush,
+    pub engine: StrokeEngine,
 }
 
 impl Default for PaintSettings {
@@ -30,10 +54,74 @@ impl Default for PaintSettings {
         Self {
             radius: 0.05,
             colour: Color::BLACK,
+            brush: Brush::default(),
+            engine: StrokeEngine::default(),
         }
     }
 }
 
+/// How strokes are turned into pixels
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum StrokeEngine {
+    /// rasterises the line between input samples with the brush
+    #[default]
+    Line,
+    /// places a brush tip at regular spacing along the stroke
+    Stamp(StampSettings),
+}
+
+/// selects one of the [`Brush::PRESETS`] with the number keys
+fn select_brush_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut paint_settings: ResMut<PaintSettings>,
+) {
+    const KEYS: [KeyCode; 5] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+    ];
+
+    for (key, brush) in KEYS.iter().zip(Brush::PRESETS) {
+        if keyboard_input.just_pressed(*key) {
+            paint_settings.brush = brush;
+        }
+    }
+}
+
+/// cycles between the line engine and stamping each brush tip with B
+fn select_engine_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut paint_settings: ResMut<PaintSettings>,
+    brush_tips: Res<BrushTips>,
+) {
+    if !keyboard_input.just_pressed(settings.key_bindings.brush_engine) {
+        return;
+    }
+
+    let mut engines = vec![
+        StrokeEngine::Line,
+        StrokeEngine::Stamp(StampSettings::default()),
+    ];
+    engines.extend(brush_tips.names().into_iter().map(|name| {
+        StrokeEngine::Stamp(StampSettings {
+            tip: Some(name.to_owned()),
+            follow_direction: true,
+            rotation_jitter: 0.2,
+            scatter: 0.1,
+            ..default()
+        })
+    }));
+
+    let current = engines
+        .iter()
+        .position(|engine| *engine == paint_settings.engine)
+        .unwrap_or(0);
+    paint_settings.engine = engines[(current + 1) % engines.len()].clone();
+}
+
 pub trait PaintImage {
     fn draw_spot(&mut self, x: usize, y: usize, paint_settings: &PaintSettings, plane_scale: Vec2);
 
@@ -1,7 +1,21 @@
 use bevy::prelude::*;
 
+use crate::drawable::{guide::StrokeGuide, stroke::Stroke};
+
+use super::StrokeRaster;
+
 #[derive(Debug, Default, Resource)]
 pub struct PaintInput {
-    pub last_input_location: Option<(usize, usize)>,
-    pub mouse_down: bool,
+    pub stroke: Option<ActiveStroke>,
+}
+
+/// The stroke currently being drawn
+#[derive(Debug)]
+pub struct ActiveStroke {
+    /// the drawable object the stroke is drawn on
+    pub drawable: Entity,
+    pub raster: StrokeRaster,
+    pub stroke: Stroke,
+    /// the ruler or grid the stroke snaps to
+    pub guide: StrokeGuide,
 }
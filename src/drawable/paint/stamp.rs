@@ -0,0 +1,364 @@
+//! Stamp engine, paints strokes by placing a brush tip image along the path
+//!
+//! Brush tips are loaded from the PNGs in `assets/brushes/`, white pixels
+//! paint and transparent pixels don't.
+
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use bevy::{asset::LoadedFolder, prelude::*};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+const BRUSH_TIPS_FOLDER: &str = "brushes";
+
+/// Size of the round tip used when a stroke doesn't name one
+const DEFAULT_TIP_SIZE: u32 = 64;
+
+/// How a stamp stroke places its tip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StampSettings {
+    /// name of the tip in [`BrushTips`], a soft round tip if `None`
+    pub tip: Option<String>,
+    /// distance between stamps as a fraction of the brush diameter
+    pub spacing: f32,
+    /// rotation of the tip in radians
+    pub rotation: f32,
+    /// whether the tip turns to follow the stroke
+    pub follow_direction: bool,
+    /// maximum random rotation in radians
+    pub rotation_jitter: f32,
+    /// maximum random offset across the stroke as a fraction of the diameter
+    pub scatter: f32,
+    /// maximum random change in size as a fraction of the diameter
+    pub size_jitter: f32,
+    /// maximum random reduction in opacity
+    pub opacity_jitter: f32,
+}
+
+impl Default for StampSettings {
+    fn default() -> Self {
+        Self {
+            tip: None,
+            spacing: 0.15,
+            rotation: 0.0,
+            follow_direction: false,
+            rotation_jitter: 0.0,
+            scatter: 0.0,
+            size_jitter: 0.0,
+            opacity_jitter: 0.0,
+        }
+    }
+}
+
+/// Alpha mask that gets stamped along a stroke
+#[derive(Debug, Clone)]
+pub struct BrushTip {
+    width: u32,
+    height: u32,
+    alpha: Vec<f32>,
+}
+
+impl BrushTip {
+    /// round tip that fades out towards the edge
+    pub fn round(size: u32) -> Self {
+        let half = size as f32 / 2.0;
+        let alpha = (0..size * size)
+            .map(|index| {
+                let x = (index % size) as f32 + 0.5 - half;
+                let y = (index / size) as f32 + 0.5 - half;
+                let distance = f32::hypot(x, y) / half;
+                (1.0 - distance * distance).clamp(0.0, 1.0)
+            })
+            .collect();
+        Self {
+            width: size,
+            height: size,
+            alpha,
+        }
+    }
+
+    /// uses the brightness times the alpha of each pixel
+    pub fn from_image(image: &Image) -> Option<Self> {
+        let (width, height) = (image.width(), image.height());
+        let mut alpha = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let colour = image.get_color_at(x, y).ok()?.to_linear();
+                let brightness = (colour.red + colour.green + colour.blue) / 3.0;
+                alpha.push(brightness * colour.alpha);
+            }
+        }
+        Some(Self {
+            width,
+            height,
+            alpha,
+        })
+    }
+
+    /// like [`BrushTip::from_image`] for images loaded without Bevy
+    pub fn from_rgba(image: &RgbaImage) -> Self {
+        let alpha = image
+            .pixels()
+            .map(|pixel| {
+                let [red, green, blue, alpha] = pixel.0;
+                let colour = Color::srgba_u8(red, green, blue, alpha).to_linear();
+                (colour.red + colour.green + colour.blue) / 3.0 * colour.alpha
+            })
+            .collect();
+        Self {
+            width: image.width(),
+            height: image.height(),
+            alpha,
+        }
+    }
+
+    /// bilinear sample, `u` and `v` between 0 and 1
+    fn sample(&self, u: f32, v: f32) -> f32 {
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return 0.0;
+        }
+        let x = (u * self.width as f32 - 0.5).max(0.0);
+        let y = (v * self.height as f32 - 0.5).max(0.0);
+        let x0 = (x as u32).min(self.width - 1);
+        let y0 = (y as u32).min(self.height - 1);
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let (fx, fy) = (x.fract(), y.fract());
+
+        let at = |x: u32, y: u32| self.alpha[(y * self.width + x) as usize];
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+/// The brush tips that stamp strokes can use, by file name without extension
+#[derive(Resource, Debug)]
+pub struct BrushTips {
+    default_tip: Arc<BrushTip>,
+    tips: HashMap<String, Arc<BrushTip>>,
+    folder: Option<Handle<LoadedFolder>>,
+}
+
+impl Default for BrushTips {
+    fn default() -> Self {
+        Self {
+            default_tip: Arc::new(BrushTip::round(DEFAULT_TIP_SIZE)),
+            tips: HashMap::new(),
+            folder: None,
+        }
+    }
+}
+
+impl BrushTips {
+    /// the named tip, or the default one if there's no tip with that name
+    pub fn get(&self, name: Option<&str>) -> Arc<BrushTip> {
+        name.and_then(|name| self.tips.get(name))
+            .unwrap_or(&self.default_tip)
+            .clone()
+    }
+
+    /// names of the loaded tips in alphabetical order
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.tips.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// loads the PNGs in `folder` without the asset server, for rendering
+    /// without the app
+    pub fn from_folder(folder: &Path) -> Self {
+        let mut brush_tips = Self::default();
+        let Ok(entries) = fs::read_dir(folder) else {
+            warn!("Couldn't read brush tips from {}", folder.display());
+            return brush_tips;
+        };
+        for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+            if path.extension().is_none_or(|extension| extension != "png") {
+                continue;
+            }
+            let Some(name) = path.file_stem().map(|stem| stem.to_string_lossy()) else {
+                continue;
+            };
+            match image::open(&path) {
+                Ok(image) => {
+                    let tip = BrushTip::from_rgba(&image.to_rgba8());
+                    brush_tips.tips.insert(name.into_owned(), Arc::new(tip));
+                }
+                Err(error) => warn!("Couldn't use brush tip {name}: {error}"),
+            }
+        }
+        brush_tips
+    }
+
+    #[cfg(test)]
+    pub fn insert(&mut self, name: &str, tip: BrushTip) {
+        self.tips.insert(name.to_owned(), Arc::new(tip));
+    }
+}
+
+pub(super) fn load_brush_tips_folder(
+    mut brush_tips: ResMut<BrushTips>,
+    asset_server: Res<AssetServer>,
+) {
+    brush_tips.folder = Some(asset_server.load_folder(BRUSH_TIPS_FOLDER));
+}
+
+/// converts the brush tip images once the folder has loaded
+pub(super) fn convert_brush_tips(
+    mut brush_tips: ResMut<BrushTips>,
+    asset_server: Res<AssetServer>,
+    folders: Res<Assets<LoadedFolder>>,
+    images: Res<Assets<Image>>,
+) {
+    let Some(folder_handle) = &brush_tips.folder else {
+        return;
+    };
+    if !asset_server.is_loaded_with_dependencies(folder_handle) {
+        return;
+    }
+    let Some(folder) = folders.get(folder_handle) else {
+        return;
+    };
+
+    let mut tips = HashMap::new();
+    for handle in &folder.handles {
+        let Some(name) = handle
+            .path()
+            .and_then(|path| path.path().file_stem())
+            .map(|stem| stem.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        let Ok(image_handle) = handle.clone().try_typed::<Image>() else {
+            continue;
+        };
+        match images.get(&image_handle).and_then(BrushTip::from_image) {
+            Some(tip) => {
+                tips.insert(name, Arc::new(tip));
+            }
+            None => warn!("Couldn't use brush tip {name}"),
+        }
+    }
+
+    brush_tips.tips = tips;
+    brush_tips.folder = None;
+}
+
+/// Where the next stamp of a stroke goes and the random state for jitter
+#[derive(Debug)]
+pub struct StampState {
+    tip: Arc<BrushTip>,
+    distance_to_next: f32,
+    rng: u32,
+}
+
+/// A single stamp to paint
+pub struct Stamp {
+    pub centre: Vec2,
+    pub diameter: f32,
+    pub rotation: f32,
+    pub opacity: f32,
+}
+
+impl StampState {
+    pub fn new(tip: Arc<BrushTip>) -> Self {
+        Self {
+            tip,
+            distance_to_next: 0.0,
+            rng: 0x9e37_79b9,
+        }
+    }
+
+    /// The stamps along the segment from `start` to `end`, the spacing is
+    /// carried over between segments so stamps are even along the stroke.
+    pub fn stamps(
+        &mut self,
+        start: Vec2,
+        end: Vec2,
+        diameter: f32,
+        settings: &StampSettings,
+    ) -> Vec<Stamp> {
+        let spacing = (diameter * settings.spacing).max(1.0);
+        let length = start.distance(end);
+        let direction = (end - start).normalize_or(Vec2::X);
+
+        let mut stamps = Vec::new();
+        let mut position = self.distance_to_next;
+        while position <= length {
+            let centre = start + direction * position;
+            stamps.push(self.jittered_stamp(centre, direction, diameter, settings));
+            position += spacing;
+        }
+        self.distance_to_next = position - length;
+        stamps
+    }
+
+    fn jittered_stamp(
+        &mut self,
+        centre: Vec2,
+        direction: Vec2,
+        diameter: f32,
+        settings: &StampSettings,
+    ) -> Stamp {
+        let mut rotation = settings.rotation + settings.rotation_jitter * self.random_signed();
+        if settings.follow_direction {
+            rotation += direction.to_angle();
+        }
+        let scatter = direction.perp() * settings.scatter * diameter * self.random_signed();
+        let diameter = diameter * (1.0 + settings.size_jitter * self.random_signed()).max(0.0);
+        let opacity = 1.0 - settings.opacity_jitter * (self.random_signed() * 0.5 + 0.5);
+
+        Stamp {
+            centre: centre + scatter,
+            diameter,
+            rotation,
+            opacity,
+        }
+    }
+
+    /// coverage of a pixel by `stamp`
+    pub fn coverage(&self, stamp: &Stamp, pixel: Vec2) -> f32 {
+        let local = Vec2::from_angle(-stamp.rotation).rotate(pixel - stamp.centre);
+        let uv = local / stamp.diameter + Vec2::splat(0.5);
+        self.tip.sample(uv.x, uv.y) * stamp.opacity
+    }
+
+    /// xorshift, between -1 and 1
+    fn random_signed(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::Vec2;
+
+    use super::{BrushTip, StampSettings, StampState};
+
+    #[test]
+    fn stamps_are_evenly_spaced_across_segments() {
+        let settings = StampSettings {
+            spacing: 0.5,
+            ..Default::default()
+        };
+        let mut state = StampState::new(BrushTip::round(8).into());
+
+        // diameter 10 and spacing 0.5 puts a stamp every 5 pixels
+        let mut stamps = state.stamps(Vec2::ZERO, Vec2::new(7.0, 0.0), 10.0, &settings);
+        stamps.extend(state.stamps(Vec2::new(7.0, 0.0), Vec2::new(21.0, 0.0), 10.0, &settings));
+
+        let positions: Vec<_> = stamps.iter().map(|stamp| stamp.centre.x).collect();
+        assert_eq!(positions, vec![0.0, 5.0, 10.0, 15.0, 20.0]);
+    }
+
+    #[test]
+    fn round_tip_fades_out() {
+        let tip = BrushTip::round(16);
+        assert!(tip.sample(0.5, 0.5) > tip.sample(0.8, 0.5));
+        assert_eq!(tip.sample(0.0, 0.0), 0.0);
+    }
+}
@@ -0,0 +1,913 @@
+//! Recording drawing sessions and replaying them
+//!
+//! While recording, pen samples, tool and brush changes, ruler and template
+//! changes, page changes and notebook animations are logged with the time since
+//! the recording started. Stopping saves the log to [`RECORDING_PATH`]. A replay
+//! swaps the notebook for blank pages with the recorded templates, on the page
+//! the recording started on, and feeds the log back through [`drawing_system`]
+//! at an adjustable speed, so it draws exactly what was drawn. The notebook,
+//! tool and brush are put back when the replay ends, or as soon as the
+//! notebook is saved, opened or another one is selected. Replays can also save
+//! a numbered PNG of the pages every frame for time-lapses.
+//!
+//! [`drawing_system`]: super::drawing_system
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    document::{show_pages, NotebookPages, OpenNotebook, SaveNotebook, UnsavedChanges},
+    drawable::{DrawableHit, PageFilter, PenSample},
+    drawable_material::DrawableMaterial,
+    guide::{Ruler, RulerGuide, Snapping},
+    pages::{shown_pages, PageControl},
+    paint::{paint_input::PaintInput, PaintSettings},
+    stroke::DrawableContent,
+    template::{PageTemplate, SetPageTemplate},
+    tool::Tool,
+};
+use crate::notebook::{animation::NotebookInput, desk::SelectNotebook};
+
+const RECORDING_PATH: &str = "./temp/recording.ron";
+const FRAMES_FOLDER: &str = "./temp/frames";
+/// Frames per second of replay time when exporting frames
+const FRAME_RATE: f64 = 30.0;
+
+/// Something that happened while recording, pages are numbered from 0 like in
+/// the notebook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedInput {
+    /// the notebook when recording started, the templates of its pages and
+    /// the first page shown
+    Start {
+        templates: Vec<PageTemplate>,
+        first_shown: usize,
+    },
+    /// the pen is on a page, `position` is in page units
+    Pen {
+        page: usize,
+        position: Vec2,
+        plane_scale: Vec2,
+        straight: bool,
+    },
+    /// the pen was lifted or left the pages
+    PenUp,
+    Tool(Tool),
+    PaintSettings(PaintSettings),
+    Ruler(Option<RecordedRuler>),
+    Snapping(bool),
+    Template {
+        page: usize,
+        template: PageTemplate,
+    },
+    /// turning the page forward, from recordings made before other notebook
+    /// inputs were recorded
+    TurnPage,
+    Notebook(NotebookInput),
+    Page(PageControl),
+}
+
+/// A [`RulerGuide`] with the page it's on
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRuler {
+    page: usize,
+    centre: Vec2,
+    angle: f32,
+    aspect: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimedInput {
+    /// seconds since the recording started
+    pub time: f64,
+    pub input: RecordedInput,
+}
+
+/// A recorded drawing session
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputLog {
+    pub inputs: Vec<TimedInput>,
+}
+
+#[derive(Debug, Error)]
+pub enum RecordingError {
+    #[error("could not access recording: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write recording: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("could not read recording: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+}
+
+impl InputLog {
+    pub fn save(&self, path: &Path) -> Result<(), RecordingError> {
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RecordingError> {
+        Ok(ron::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn push(&mut self, time: f64, input: RecordedInput) {
+        self.inputs.push(TimedInput { time, input });
+    }
+}
+
+/// Message for controlling recording and replay
+#[derive(Debug, Clone, Copy, Message)]
+pub enum RecordingControl {
+    /// starts recording, or stops and saves the recording
+    ToggleRecording,
+    /// replays the saved recording `speed` times as fast as it was drawn
+    Replay { speed: f64 },
+    /// replays the saved recording and saves every frame to [`FRAMES_FOLDER`]
+    ExportFrames { speed: f64 },
+}
+
+/// The recording in progress
+#[derive(Resource, Debug, Default)]
+pub struct Recorder {
+    log: Option<InputLog>,
+    started_at: f64,
+    /// whether the pen was on a page in the last logged sample
+    pen_down: bool,
+    /// the last logged state, so only changes are logged
+    tool: Option<Tool>,
+    paint_settings: Option<PaintSettings>,
+    ruler: Option<Option<RecordedRuler>>,
+    snapping: Option<bool>,
+}
+
+impl Recorder {
+    pub fn is_recording(&self) -> bool {
+        self.log.is_some()
+    }
+}
+
+/// The notebook and drawing state a replay was started over
+#[derive(Debug)]
+struct ReplacedNotebook {
+    pages: Vec<DrawableContent>,
+    first_shown: usize,
+    path: Option<PathBuf>,
+    unsaved: bool,
+    tool: Tool,
+    paint_settings: PaintSettings,
+    ruler: Option<RulerGuide>,
+    snapping: bool,
+}
+
+/// A recording being replayed
+#[derive(Resource, Debug)]
+pub struct Replay {
+    log: InputLog,
+    /// put back when the replay ends
+    replaced: Option<ReplacedNotebook>,
+    /// index of the next input to replay
+    next: usize,
+    /// seconds of the recording replayed so far
+    clock: f64,
+    speed: f64,
+    /// the number of the next frame to save, when exporting frames
+    frame: Option<u32>,
+}
+
+impl Replay {
+    pub fn new(log: InputLog, speed: f64) -> Self {
+        Self {
+            log,
+            replaced: None,
+            next: 0,
+            clock: 0.0,
+            speed,
+            frame: None,
+        }
+    }
+
+    /// moves the replay on by `delta` seconds of real time and returns the
+    /// inputs that happened in that time
+    pub fn advance(&mut self, delta: f64) -> &[TimedInput] {
+        // exported frames are evenly spaced in the recording
+        let delta = if self.frame.is_some() {
+            1.0 / FRAME_RATE
+        } else {
+            delta
+        };
+        self.clock += delta * self.speed;
+        let start = self.next;
+        while self
+            .log
+            .inputs
+            .get(self.next)
+            .is_some_and(|input| input.time <= self.clock)
+        {
+            self.next += 1;
+        }
+        &self.log.inputs[start..self.next]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.log.inputs.len()
+    }
+}
+
+/// blank pages with the templates and first shown page of the notebook the
+/// log was recorded in, older logs without a [`RecordedInput::Start`] start
+/// on the first page
+fn start_pages(log: &InputLog, plane_scale: Vec2) -> (Vec<DrawableContent>, usize) {
+    let start = log.inputs.iter().find_map(|timed| match &timed.input {
+        RecordedInput::Start {
+            templates,
+            first_shown,
+        } => Some((templates, *first_shown)),
+        _ => None,
+    });
+    let Some((templates, first_shown)) = start else {
+        return (Vec::new(), 0);
+    };
+    let pages = templates
+        .iter()
+        .map(|template| {
+            let mut page = DrawableContent::new(plane_scale);
+            page.template = *template;
+            page
+        })
+        .collect();
+    (pages, first_shown)
+}
+
+/// The selected notebook and the drawing state, which a replay swaps for a
+/// fresh notebook
+#[derive(SystemParam)]
+pub(super) struct ReplayedNotebook<'w, 's> {
+    drawable_query: Query<'w, 's, (Entity, &'static mut DrawableContent), PageFilter>,
+    notebook_pages: ResMut<'w, NotebookPages>,
+    unsaved: ResMut<'w, UnsavedChanges>,
+    tool: ResMut<'w, Tool>,
+    paint_settings: ResMut<'w, PaintSettings>,
+    ruler: ResMut<'w, Ruler>,
+    snapping: ResMut<'w, Snapping>,
+    paint_input: ResMut<'w, PaintInput>,
+    template_writer: MessageWriter<'w, SetPageTemplate>,
+    page_writer: MessageWriter<'w, PageControl>,
+}
+
+impl ReplayedNotebook<'_, '_> {
+    /// swaps the notebook for blank pages like the ones `log` was recorded on
+    fn replace(&mut self, log: &InputLog) -> ReplacedNotebook {
+        let mut drawables: Vec<_> = self.drawable_query.iter_mut().collect();
+        drawables.sort_by_key(|(entity, _)| *entity);
+        // the stroke being drawn is kept with the pages
+        if let Some(active) = self.paint_input.stroke.take() {
+            if let Some((_, content)) = drawables
+                .iter_mut()
+                .find(|(entity, _)| *entity == active.drawable)
+            {
+                content.push_painted(active.stroke);
+            }
+        }
+        let replaced = ReplacedNotebook {
+            pages: self
+                .notebook_pages
+                .with_shown(drawables.iter().map(|(_, content)| &**content)),
+            first_shown: self.notebook_pages.first_shown,
+            path: self.notebook_pages.path.take(),
+            unsaved: self.unsaved.0,
+            tool: *self.tool,
+            paint_settings: self.paint_settings.clone(),
+            ruler: self.ruler.guide.take(),
+            snapping: self.snapping.to_grid,
+        };
+
+        let plane_scale = drawables
+            .first()
+            .map_or(Vec2::ONE, |(_, content)| content.plane_scale);
+        let (mut pages, first_shown) = start_pages(log, plane_scale);
+        show_pages(
+            &mut drawables,
+            &mut pages,
+            first_shown,
+            &mut self.template_writer,
+        );
+        self.notebook_pages.set_pages(pages, first_shown);
+        replaced
+    }
+
+    /// puts back the notebook and drawing state a replay was started over
+    fn restore(&mut self, replaced: ReplacedNotebook) {
+        // a stroke the replay was drawing is left out
+        self.paint_input.stroke = None;
+        let mut drawables: Vec<_> = self.drawable_query.iter_mut().collect();
+        drawables.sort_by_key(|(entity, _)| *entity);
+        let mut pages = replaced.pages;
+        show_pages(
+            &mut drawables,
+            &mut pages,
+            replaced.first_shown,
+            &mut self.template_writer,
+        );
+        self.notebook_pages.set_pages(pages, replaced.first_shown);
+        self.notebook_pages.path = replaced.path;
+        // so the pages being swapped back isn't taken for an edit
+        self.page_writer
+            .write(PageControl::GoTo(replaced.first_shown));
+        self.unsaved.0 = replaced.unsaved;
+        *self.tool = replaced.tool;
+        *self.paint_settings = replaced.paint_settings;
+        self.ruler.guide = replaced.ruler;
+        self.snapping.to_grid = replaced.snapping;
+    }
+}
+
+/// the drawable showing page `page` of the notebook
+fn shown_page(shown: &[(usize, Entity)], page: usize) -> Option<Entity> {
+    shown
+        .iter()
+        .find(|(number, _)| *number == page)
+        .map(|(_, entity)| *entity)
+}
+
+/// Starts and stops recording and starts replays
+#[allow(clippy::too_many_arguments)]
+pub(super) fn recording_control_system(
+    mut commands: Commands,
+    mut reader: MessageReader<RecordingControl>,
+    mut recorder: ResMut<Recorder>,
+    replay: Option<Res<Replay>>,
+    time: Res<Time>,
+    mut notebook: ReplayedNotebook,
+) {
+    for message in reader.read() {
+        let (speed, export_frames) = match *message {
+            RecordingControl::ToggleRecording => {
+                if let Some(log) = recorder.log.take() {
+                    match log.save(Path::new(RECORDING_PATH)) {
+                        Ok(()) => info!("Saved recording to {RECORDING_PATH}"),
+                        Err(error) => error!("Failed to save recording: {error}"),
+                    }
+                } else if replay.is_none() {
+                    *recorder = Recorder {
+                        log: Some(InputLog::default()),
+                        started_at: time.elapsed_secs_f64(),
+                        ..default()
+                    };
+                    info!("Recording started");
+                }
+                continue;
+            }
+            RecordingControl::Replay { speed } => (speed, false),
+            RecordingControl::ExportFrames { speed } => (speed, true),
+        };
+        if recorder.is_recording() || replay.is_some() {
+            warn!("Can't replay while recording or replaying");
+            continue;
+        }
+        let log = match InputLog::load(Path::new(RECORDING_PATH)) {
+            Ok(log) => log,
+            Err(error) => {
+                error!("Failed to load recording: {error}");
+                continue;
+            }
+        };
+
+        let mut replay = Replay::new(log, speed);
+        if export_frames {
+            replay.frame = Some(0);
+            if let Err(error) = fs::create_dir_all(FRAMES_FOLDER) {
+                error!("Failed to create {FRAMES_FOLDER}: {error}");
+                continue;
+            }
+        }
+        replay.replaced = Some(notebook.replace(&replay.log));
+        commands.insert_resource(replay);
+    }
+}
+
+/// logs the inputs while recording
+#[allow(clippy::too_many_arguments)]
+pub(super) fn record_input_system(
+    mut recorder: ResMut<Recorder>,
+    time: Res<Time>,
+    mut pen_reader: MessageReader<PenSample>,
+    mut template_reader: MessageReader<SetPageTemplate>,
+    mut notebook_reader: MessageReader<NotebookInput>,
+    mut page_reader: MessageReader<PageControl>,
+    drawable_query: Query<Entity, PageFilter>,
+    content_query: Query<&DrawableContent>,
+    notebook_pages: Res<NotebookPages>,
+    tool: Res<Tool>,
+    paint_settings: Res<PaintSettings>,
+    ruler: Res<Ruler>,
+    snapping: Res<Snapping>,
+) {
+    let recorder = &mut *recorder;
+    let Some(log) = recorder.log.as_mut() else {
+        pen_reader.clear();
+        template_reader.clear();
+        notebook_reader.clear();
+        page_reader.clear();
+        return;
+    };
+    let now = time.elapsed_secs_f64() - recorder.started_at;
+    let shown = shown_pages(&drawable_query, &notebook_pages);
+    let page_index = |entity: Entity| {
+        shown
+            .iter()
+            .find(|(_, drawable)| *drawable == entity)
+            .map(|(page, _)| *page)
+    };
+
+    // the state when recording starts
+    if log.inputs.is_empty() {
+        let contents = shown
+            .iter()
+            .filter_map(|(_, drawable)| content_query.get(*drawable).ok());
+        let templates = notebook_pages
+            .with_shown(contents)
+            .iter()
+            .map(|page| page.template)
+            .collect();
+        log.push(
+            0.0,
+            RecordedInput::Start {
+                templates,
+                first_shown: notebook_pages.first_shown,
+            },
+        );
+    }
+
+    if recorder.tool != Some(*tool) {
+        recorder.tool = Some(*tool);
+        log.push(now, RecordedInput::Tool(*tool));
+    }
+    if recorder.paint_settings.as_ref() != Some(&paint_settings) {
+        recorder.paint_settings = Some(paint_settings.clone());
+        log.push(now, RecordedInput::PaintSettings(paint_settings.clone()));
+    }
+    let recorded_ruler = ruler.guide.and_then(|guide| {
+        Some(RecordedRuler {
+            page: page_index(guide.drawable)?,
+            centre: guide.centre,
+            angle: guide.angle,
+            aspect: guide.aspect,
+        })
+    });
+    if recorder.ruler != Some(recorded_ruler) {
+        recorder.ruler = Some(recorded_ruler);
+        log.push(now, RecordedInput::Ruler(recorded_ruler));
+    }
+    if recorder.snapping != Some(snapping.to_grid) {
+        recorder.snapping = Some(snapping.to_grid);
+        log.push(now, RecordedInput::Snapping(snapping.to_grid));
+    }
+
+    for message in template_reader.read() {
+        if let Some(page) = page_index(message.drawable) {
+            log.push(
+                now,
+                RecordedInput::Template {
+                    page,
+                    template: message.template,
+                },
+            );
+        }
+    }
+    for input in notebook_reader.read() {
+        log.push(now, RecordedInput::Notebook(*input));
+    }
+    for sample in pen_reader.read() {
+        let pen = sample.hit.and_then(|hit| {
+            Some(RecordedInput::Pen {
+                page: page_index(hit.drawable_object)?,
+                position: hit.position,
+                plane_scale: hit.plane_scale,
+                straight: sample.straight,
+            })
+        });
+        match pen {
+            Some(pen) => log.push(now, pen),
+            // one pen up is enough
+            None if recorder.pen_down => log.push(now, RecordedInput::PenUp),
+            None => {}
+        }
+        recorder.pen_down = sample.hit.is_some();
+    }
+    // after the pen, its samples are on the pages shown before the change
+    for control in page_reader.read() {
+        log.push(now, RecordedInput::Page(*control));
+    }
+}
+
+/// feeds the replayed inputs to the drawing systems
+#[allow(clippy::too_many_arguments)]
+pub(super) fn replay_system(
+    mut replay: ResMut<Replay>,
+    time: Res<Time>,
+    drawable_query: Query<Entity, PageFilter>,
+    notebook_pages: Res<NotebookPages>,
+    mut pen_writer: MessageWriter<PenSample>,
+    mut template_writer: MessageWriter<SetPageTemplate>,
+    mut notebook_writer: MessageWriter<NotebookInput>,
+    mut page_writer: MessageWriter<PageControl>,
+    mut tool: ResMut<Tool>,
+    mut paint_settings: ResMut<PaintSettings>,
+    mut ruler: ResMut<Ruler>,
+    mut snapping: ResMut<Snapping>,
+) {
+    let shown = shown_pages(&drawable_query, &notebook_pages);
+    for timed in replay.advance(time.delta_secs_f64()) {
+        match &timed.input {
+            // the pages are set up when the replay starts
+            RecordedInput::Start { .. } => {}
+            RecordedInput::Pen {
+                page,
+                position,
+                plane_scale,
+                straight,
+            } => {
+                let Some(page) = shown_page(&shown, *page) else {
+                    continue;
+                };
+                pen_writer.write(PenSample {
+                    hit: Some(DrawableHit {
+                        drawable_object: page,
+                        position: *position,
+                        plane_scale: *plane_scale,
+                    }),
+                    straight: *straight,
+                    time: timed.time,
+                });
+            }
+            RecordedInput::PenUp => {
+                pen_writer.write(PenSample {
+                    hit: None,
+                    straight: false,
+                    time: timed.time,
+                });
+            }
+            RecordedInput::Tool(recorded) => *tool = *recorded,
+            RecordedInput::PaintSettings(recorded) => *paint_settings = recorded.clone(),
+            RecordedInput::Ruler(recorded) => {
+                ruler.guide = recorded.and_then(|recorded| {
+                    Some(RulerGuide {
+                        drawable: shown_page(&shown, recorded.page)?,
+                        centre: recorded.centre,
+                        angle: recorded.angle,
+                        aspect: recorded.aspect,
+                    })
+                });
+            }
+            RecordedInput::Snapping(to_grid) => snapping.to_grid = *to_grid,
+            RecordedInput::Template { page, template } => {
+                if let Some(page) = shown_page(&shown, *page) {
+                    template_writer.write(SetPageTemplate {
+                        drawable: page,
+                        template: *template,
+                    });
+                }
+            }
+            RecordedInput::TurnPage => {
+                notebook_writer.write(NotebookInput::TurnForward);
+            }
+            RecordedInput::Notebook(input) => {
+                notebook_writer.write(*input);
+            }
+            RecordedInput::Page(control) => {
+                page_writer.write(*control);
+            }
+        }
+    }
+
+    if replay.is_finished() {
+        pen_writer.write(PenSample {
+            hit: None,
+            straight: false,
+            time: replay.clock,
+        });
+    }
+}
+
+/// ends the replay the frame after its last input, or as soon as the notebook
+/// is saved, opened or another one is selected, and puts the notebook back
+pub(super) fn finish_replay_system(
+    mut commands: Commands,
+    replay: Option<ResMut<Replay>>,
+    mut save_reader: MessageReader<SaveNotebook>,
+    mut open_reader: MessageReader<OpenNotebook>,
+    mut select_reader: MessageReader<SelectNotebook>,
+    mut notebook: ReplayedNotebook,
+) {
+    let interrupted =
+        save_reader.read().count() + open_reader.read().count() + select_reader.read().count() > 0;
+    let Some(mut replay) = replay else {
+        return;
+    };
+    if !replay.is_finished() && !interrupted {
+        return;
+    }
+    if let Some(replaced) = replay.replaced.take() {
+        notebook.restore(replaced);
+    }
+    commands.remove_resource::<Replay>();
+    if interrupted {
+        info!("Replay stopped");
+    } else {
+        info!("Replay finished");
+    }
+}
+
+/// saves the pages side by side when exporting frames of a replay
+pub(super) fn save_replay_frame(
+    replay: Option<ResMut<Replay>>,
+    drawable_query: Query<(Entity, &MeshMaterial3d<DrawableMaterial>), PageFilter>,
+    drawable_materials: Res<Assets<DrawableMaterial>>,
+    images: Res<Assets<Image>>,
+) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+    let Some(frame) = replay.frame else {
+        return;
+    };
+    replay.frame = Some(frame + 1);
+
+    let mut drawables: Vec<_> = drawable_query.iter().collect();
+    drawables.sort_by_key(|(entity, _)| *entity);
+    let pages: Vec<RgbaImage> = drawables
+        .into_iter()
+        .filter_map(|(_, material)| {
+            let material = drawable_materials.get(&material.0)?;
+            let image = images.get(&material.draw_texture)?;
+            Some(image.clone().try_into_dynamic().ok()?.to_rgba8())
+        })
+        .collect();
+
+    let path = Path::new(FRAMES_FOLDER).join(format!("frame_{frame:05}.png"));
+    if let Err(error) = frame_image(&pages).save(&path) {
+        error!("Failed to save {}: {error}", path.display());
+    }
+}
+
+/// the pages next to each other on white
+fn frame_image(pages: &[RgbaImage]) -> RgbaImage {
+    let width = pages.iter().map(|page| page.width()).sum::<u32>().max(1);
+    let height = pages.iter().map(|page| page.height()).max().unwrap_or(1);
+    let mut frame = RgbaImage::from_pixel(width, height, image::Rgba([255; 4]));
+    let mut x = 0;
+    for page in pages {
+        image::imageops::overlay(&mut frame, page, x, 0);
+        x += i64::from(page.width());
+    }
+    frame
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+    use super::{
+        finish_replay_system, record_input_system, replay_system, InputLog, RecordedInput,
+        Recorder, Replay, ReplayedNotebook, TimedInput,
+    };
+    use crate::{
+        drawable::{
+            document::{NotebookPages, OpenNotebook, SaveNotebook, UnsavedChanges},
+            drawable::{DrawableHit, PenSample},
+            guide::{Ruler, Snapping},
+            pages::PageControl,
+            paint::{paint_input::PaintInput, PaintSettings},
+            stroke::{DrawableContent, Stroke, StrokeId},
+            template::{PageTemplate, SetPageTemplate},
+            tool::Tool,
+            DrawableObject, InSelectedNotebook,
+        },
+        notebook::{animation::NotebookInput, desk::SelectNotebook},
+    };
+
+    fn log() -> InputLog {
+        let pen = |x| RecordedInput::Pen {
+            page: 0,
+            position: Vec2::new(x, 0.5),
+            plane_scale: Vec2::ONE,
+            straight: false,
+        };
+        InputLog {
+            inputs: [
+                (0.0, RecordedInput::Tool(Tool::Pen)),
+                (0.5, pen(0.1)),
+                (1.0, pen(0.2)),
+                (1.5, RecordedInput::PenUp),
+                (2.0, RecordedInput::TurnPage),
+            ]
+            .into_iter()
+            .map(|(time, input)| TimedInput { time, input })
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn logs_round_trip_through_ron() {
+        let log = log();
+        let text = ron::to_string(&log).unwrap();
+        assert_eq!(ron::from_str::<InputLog>(&text).unwrap(), log);
+    }
+
+    #[test]
+    fn replays_follow_the_speed() {
+        let mut replay = Replay::new(log(), 2.0);
+        // half a second at double speed is the first second of the recording
+        assert_eq!(replay.advance(0.5).len(), 3);
+        assert_eq!(replay.advance(0.1).len(), 0);
+        assert_eq!(replay.advance(0.4).len(), 2);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn pages_are_recorded_by_their_number() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<Tool>()
+            .init_resource::<PaintSettings>()
+            .init_resource::<Ruler>()
+            .init_resource::<Snapping>()
+            .init_resource::<NotebookPages>()
+            .insert_resource(Recorder {
+                log: Some(InputLog::default()),
+                ..default()
+            })
+            .add_message::<PenSample>()
+            .add_message::<SetPageTemplate>()
+            .add_message::<NotebookInput>()
+            .add_message::<PageControl>()
+            .add_systems(Update, record_input_system);
+        let mut spawn_page = || {
+            app.world_mut()
+                .spawn((
+                    DrawableObject,
+                    InSelectedNotebook,
+                    DrawableContent::new(Vec2::ONE),
+                ))
+                .id()
+        };
+        let (first, second) = (spawn_page(), spawn_page());
+        // the drawables show pages 2 and 3, in the order of their entities
+        let shown = first.max(second);
+        let pages = vec![DrawableContent::new(Vec2::ONE); 5];
+        app.world_mut()
+            .resource_mut::<NotebookPages>()
+            .set_pages(pages, 2);
+
+        app.world_mut().write_message(PenSample {
+            hit: Some(DrawableHit {
+                drawable_object: shown,
+                position: Vec2::splat(0.5),
+                plane_scale: Vec2::ONE,
+            }),
+            straight: false,
+            time: 0.0,
+        });
+        app.world_mut().write_message(PageControl::GoTo(0));
+        app.update();
+        let log = app
+            .world_mut()
+            .resource_mut::<Recorder>()
+            .log
+            .take()
+            .unwrap();
+        let inputs: Vec<_> = log.inputs.iter().map(|timed| &timed.input).collect();
+        assert!(matches!(
+            inputs[0],
+            RecordedInput::Start { templates, first_shown: 2 } if templates.len() == 5
+        ));
+        assert!(inputs.contains(&&RecordedInput::Page(PageControl::GoTo(0))));
+        let pen = inputs
+            .iter()
+            .position(|input| matches!(input, RecordedInput::Pen { page: 3, .. }))
+            .unwrap();
+        assert!(matches!(inputs[pen + 1], RecordedInput::Page(_)));
+
+        // replayed with page 1 shown first, page 3 is on neither drawable
+        app.world_mut().resource_mut::<NotebookPages>().first_shown = 1;
+        app.insert_resource(Replay::new(log.clone(), 1.0))
+            .add_systems(Update, replay_system);
+        app.update();
+        let samples: Vec<_> = app
+            .world_mut()
+            .resource_mut::<Messages<PenSample>>()
+            .drain()
+            .collect();
+        assert!(samples.iter().all(|sample| sample.hit.is_none()));
+
+        app.world_mut().resource_mut::<NotebookPages>().first_shown = 2;
+        app.insert_resource(Replay::new(log, 1.0));
+        app.update();
+        let samples: Vec<_> = app
+            .world_mut()
+            .resource_mut::<Messages<PenSample>>()
+            .drain()
+            .collect();
+        let hit = samples.iter().find_map(|sample| sample.hit).unwrap();
+        assert_eq!(hit.drawable_object, shown);
+    }
+
+    #[test]
+    fn replays_put_the_notebook_back() {
+        let mut app = App::new();
+        app.init_resource::<NotebookPages>()
+            .insert_resource(UnsavedChanges(true))
+            .init_resource::<Tool>()
+            .init_resource::<PaintSettings>()
+            .init_resource::<Ruler>()
+            .init_resource::<Snapping>()
+            .init_resource::<PaintInput>()
+            .add_message::<SetPageTemplate>()
+            .add_message::<PageControl>()
+            .add_message::<SaveNotebook>()
+            .add_message::<OpenNotebook>()
+            .add_message::<SelectNotebook>()
+            .add_systems(Update, finish_replay_system);
+        let mut drawn = DrawableContent::new(Vec2::ONE);
+        drawn.push_painted(Stroke {
+            id: StrokeId(1),
+            points: Vec::new(),
+            paint_settings: PaintSettings::default(),
+            started_at: 0.0,
+        });
+        let page = app
+            .world_mut()
+            .spawn((DrawableObject, InSelectedNotebook, drawn))
+            .id();
+        let mut notebook_pages = app.world_mut().resource_mut::<NotebookPages>();
+        notebook_pages.set_pages(vec![DrawableContent::new(Vec2::ONE); 3], 1);
+        notebook_pages.path = Some(PathBuf::from("notebook.ron"));
+
+        // a replay that isn't over yet, on a notebook of two pages
+        let log = InputLog {
+            inputs: vec![
+                TimedInput {
+                    time: 0.0,
+                    input: RecordedInput::Start {
+                        templates: vec![PageTemplate::default(); 2],
+                        first_shown: 0,
+                    },
+                },
+                TimedInput {
+                    time: 10.0,
+                    input: RecordedInput::PenUp,
+                },
+            ],
+        };
+        app.world_mut()
+            .run_system_once(
+                move |mut commands: Commands, mut notebook: ReplayedNotebook| {
+                    let mut replay = Replay::new(log.clone(), 1.0);
+                    replay.replaced = Some(notebook.replace(&replay.log));
+                    commands.insert_resource(replay);
+                },
+            )
+            .unwrap();
+        let world = app.world_mut();
+        assert!(world
+            .get::<DrawableContent>(page)
+            .unwrap()
+            .strokes
+            .is_empty());
+        assert_eq!(world.resource::<NotebookPages>().count(1), 2);
+        assert_eq!(world.resource::<NotebookPages>().path, None);
+        world.resource_mut::<PaintSettings>().radius = 0.2;
+        world.insert_resource(UnsavedChanges(false));
+        app.update();
+        assert!(app.world().contains_resource::<Replay>());
+
+        // saving stops the replay first
+        app.world_mut().write_message(SaveNotebook {
+            path: PathBuf::from("notebook.ron"),
+        });
+        app.update();
+        let world = app.world();
+        assert!(!world.contains_resource::<Replay>());
+        assert_eq!(world.get::<DrawableContent>(page).unwrap().strokes.len(), 1);
+        let notebook_pages = world.resource::<NotebookPages>();
+        assert_eq!(notebook_pages.first_shown, 1);
+        assert_eq!(notebook_pages.count(1), 3);
+        assert_eq!(notebook_pages.path, Some(PathBuf::from("notebook.ron")));
+        assert_eq!(world.resource::<PaintSettings>(), &PaintSettings::default());
+        assert!(world.resource::<UnsavedChanges>().0);
+    }
+}
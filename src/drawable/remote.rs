@@ -0,0 +1,540 @@
+//! Methods for scripting the app over the Bevy Remote Protocol
+//!
+//! Pages are numbered from 0 like in the notebook document and positions are
+//! in page units, between 0 and 1 on both axes. Only the pages that are shown
+//! can be drawn on or cleared, the others can be listed and exported. Brushes
+//! and strokes are bounded like the ones of peers in a drawing session. For
+//! example with `curl`:
+//!
+//! ```sh
+//! curl -X POST http://localhost:15702 -d '{"jsonrpc": "2.0", "id": 1,
+//!     "method": "elements/draw_stroke",
+//!     "params": {"page": 0, "points": [[0.2, 0.2], [0.8, 0.5]], "colour": "#ff0000"}}'
+//! ```
+
+use std::{fs, io::Cursor, path::Path};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bevy::{
+    prelude::*,
+    remote::{error_codes, BrpError, BrpResult, RemotePlugin},
+};
+use image::ImageFormat;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use super::{
+    document::{render_page, NotebookPages, PageDocument},
+    export::EXPORT_FOLDER,
+    paint::{stamp::BrushTips, Brush, PaintSettings, StrokeEngine},
+    stroke::{DrawableContent, Stroke, StrokePoint},
+    sync::{MAX_RADIUS, MAX_STROKE_POINTS},
+    DefaultResolution, PageFilter, MAX_RESOLUTION,
+};
+use crate::notebook::animation::NotebookInput;
+
+/// The remote plugin with the `elements/*` methods added
+pub fn remote_plugin() -> RemotePlugin {
+    RemotePlugin::default()
+        .with_method("elements/list_pages", list_pages)
+        .with_method("elements/draw_stroke", draw_stroke)
+        .with_method("elements/clear_page", clear_page)
+        .with_method("elements/export_page", export_page)
+        .with_method("elements/set_brush", set_brush)
+        .with_method("elements/turn_page", turn_page)
+        .with_method("elements/open_notebook", open_notebook)
+}
+
+fn parse<T: DeserializeOwned + Default>(params: Option<Value>) -> BrpResult<T> {
+    match params {
+        None | Some(Value::Null) => Ok(T::default()),
+        Some(params) => {
+            serde_json::from_value(params).map_err(|error| invalid_params(error.to_string()))
+        }
+    }
+}
+
+/// `radius` if it's a brush radius peers could also draw with
+fn bounded_radius(radius: f32) -> BrpResult<f32> {
+    if (0.0..=MAX_RADIUS).contains(&radius) {
+        Ok(radius)
+    } else {
+        Err(invalid_params(format!(
+            "the radius {radius} isn't between 0 and {MAX_RADIUS}"
+        )))
+    }
+}
+
+fn invalid_params(message: impl Into<String>) -> BrpError {
+    BrpError {
+        code: error_codes::INVALID_PARAMS,
+        message: message.into(),
+        data: None,
+    }
+}
+
+/// the drawable showing page `index`
+fn page_entity(
+    drawable_query: &Query<Entity, PageFilter>,
+    notebook_pages: &NotebookPages,
+    index: usize,
+) -> BrpResult<Entity> {
+    let mut drawables: Vec<_> = drawable_query.iter().collect();
+    drawables.sort();
+    let count = notebook_pages.count(drawables.len());
+    if index >= count {
+        return Err(BrpError {
+            code: error_codes::ENTITY_NOT_FOUND,
+            message: format!("there's no page {index}, there are {count} pages"),
+            data: None,
+        });
+    }
+    index
+        .checked_sub(notebook_pages.first_shown)
+        .and_then(|shown| drawables.get(shown).copied())
+        .ok_or_else(|| invalid_params(format!("page {index} isn't shown, turn to it first")))
+}
+
+fn parse_colour(colour: &str) -> BrpResult<Color> {
+    Srgba::hex(colour)
+        .map(Color::from)
+        .map_err(|error| invalid_params(format!("invalid colour `{colour}`: {error}")))
+}
+
+/// `elements/list_pages`, returns the number of strokes and the size of each
+/// page, and the drawable entity of the shown ones
+fn list_pages(
+    In(_): In<Option<Value>>,
+    drawable_query: Query<(Entity, &DrawableContent), PageFilter>,
+    notebook_pages: Res<NotebookPages>,
+) -> BrpResult {
+    let mut drawables: Vec<_> = drawable_query.iter().collect();
+    drawables.sort_by_key(|(entity, _)| *entity);
+    let entities = drawables.iter().map(|(entity, _)| *entity);
+    let shown: Vec<_> = (notebook_pages.first_shown..).zip(entities).collect();
+    let pages = notebook_pages.with_shown(drawables.iter().map(|(_, content)| *content));
+    let pages: Vec<_> = pages
+        .iter()
+        .enumerate()
+        .map(|(index, content)| {
+            let entity = shown
+                .iter()
+                .find(|(page, _)| *page == index)
+                .map(|(_, entity)| entity.to_bits());
+            json!({
+                "page": index,
+                "entity": entity,
+                "strokes": content.strokes.len(),
+                "plane_scale": [content.plane_scale.x, content.plane_scale.y],
+                "title": content.metadata.title,
+                "tags": content.metadata.tags,
+                "bookmarked": content.metadata.bookmarked,
+            })
+        })
+        .collect();
+    Ok(json!(pages))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DrawStrokeParams {
+    page: usize,
+    points: Vec<[f32; 2]>,
+    /// seconds between points, for the stroke timing
+    interval: f32,
+    /// brush radius in world units, the current brush's if not set
+    radius: Option<f32>,
+    /// hex colour, the current brush's if not set
+    colour: Option<String>,
+}
+
+/// `elements/draw_stroke`, draws a stroke through `points`, returns its id
+fn draw_stroke(
+    In(params): In<Option<Value>>,
+    drawable_query: Query<Entity, PageFilter>,
+    mut content_query: Query<&mut DrawableContent>,
+    notebook_pages: Res<NotebookPages>,
+    paint_settings: Res<PaintSettings>,
+    time: Res<Time>,
+) -> BrpResult {
+    let params: DrawStrokeParams = parse(params)?;
+    if params.points.is_empty() {
+        return Err(invalid_params("a stroke needs at least one point"));
+    }
+    if params.points.len() > MAX_STROKE_POINTS {
+        return Err(invalid_params(format!(
+            "a stroke can have at most {MAX_STROKE_POINTS} points"
+        )));
+    }
+    if !params
+        .points
+        .iter()
+        .flatten()
+        .all(|value| value.is_finite())
+    {
+        return Err(invalid_params("the points need to be numbers"));
+    }
+    if !(params.interval.is_finite() && params.interval >= 0.0) {
+        return Err(invalid_params("the interval can't be negative"));
+    }
+    let entity = page_entity(&drawable_query, &notebook_pages, params.page)?;
+    let mut content = content_query.get_mut(entity).map_err(BrpError::internal)?;
+
+    let mut paint_settings = paint_settings.clone();
+    if let Some(radius) = params.radius {
+        paint_settings.radius = bounded_radius(radius)?;
+    }
+    if let Some(colour) = &params.colour {
+        paint_settings.colour = parse_colour(colour)?;
+    }
+    let id = content.next_stroke_id();
+    content.push_painted(Stroke {
+        id,
+        points: params
+            .points
+            .iter()
+            .enumerate()
+            .map(|(index, [x, y])| StrokePoint {
+                position: Vec2::new(*x, *y).clamp(Vec2::ZERO, Vec2::ONE),
+                pressure: 1.0,
+                time: index as f32 * params.interval,
+            })
+            .collect(),
+        paint_settings,
+        started_at: time.elapsed_secs_f64(),
+    });
+    content.request_render();
+    Ok(json!({ "stroke": id.0 }))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PageParams {
+    /// every shown page if not set
+    page: Option<usize>,
+}
+
+/// `elements/clear_page`, removes the strokes and raster layer of a page
+fn clear_page(
+    In(params): In<Option<Value>>,
+    drawable_query: Query<Entity, PageFilter>,
+    mut content_query: Query<&mut DrawableContent>,
+    notebook_pages: Res<NotebookPages>,
+) -> BrpResult {
+    let params: PageParams = parse(params)?;
+    let pages = match params.page {
+        Some(page) => vec![page_entity(&drawable_query, &notebook_pages, page)?],
+        None => drawable_query.iter().collect(),
+    };
+    for entity in pages {
+        if let Ok(mut content) = content_query.get_mut(entity) {
+            content.clear();
+        }
+    }
+    Ok(Value::Null)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ExportPageParams {
+    page: usize,
+    /// the default resolution if not set, at most [`MAX_RESOLUTION`]
+    resolution: Option<usize>,
+    /// name of the PNG to write in the export folder, it's returned as base64
+    /// if not set
+    file_name: Option<String>,
+}
+
+/// `elements/export_page`, renders a page to a PNG
+fn export_page(
+    In(params): In<Option<Value>>,
+    drawable_query: Query<Entity, PageFilter>,
+    content_query: Query<&DrawableContent>,
+    notebook_pages: Res<NotebookPages>,
+    brush_tips: Res<BrushTips>,
+    default_resolution: Res<DefaultResolution>,
+) -> BrpResult {
+    let params: ExportPageParams = parse(params)?;
+    let content = match page_entity(&drawable_query, &notebook_pages, params.page) {
+        Ok(entity) => content_query.get(entity).map_err(BrpError::internal)?,
+        // the pages that aren't shown are up to date in the notebook's pages
+        Err(error) => notebook_pages.get(params.page).ok_or(error)?,
+    };
+
+    let page = PageDocument::from_content(content).map_err(BrpError::internal)?;
+    let resolution = params
+        .resolution
+        .unwrap_or(default_resolution.0)
+        .clamp(1, MAX_RESOLUTION);
+    let image = render_page(&page, resolution, &brush_tips).map_err(BrpError::internal)?;
+    match params.file_name {
+        Some(file_name) => {
+            // only files straight in the export folder, no other paths
+            if Path::new(&file_name).file_name() != Some(file_name.as_ref()) {
+                return Err(invalid_params(format!("`{file_name}` isn't a file name")));
+            }
+            fs::create_dir_all(EXPORT_FOLDER).map_err(BrpError::internal)?;
+            let path = Path::new(EXPORT_FOLDER).join(file_name);
+            image.save(&path).map_err(BrpError::internal)?;
+            Ok(json!({ "path": path }))
+        }
+        None => {
+            let mut png = Vec::new();
+            image
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .map_err(BrpError::internal)?;
+            Ok(json!({ "png": STANDARD.encode(png) }))
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SetBrushParams {
+    radius: Option<f32>,
+    colour: Option<String>,
+    brush: Option<Brush>,
+    engine: Option<StrokeEngine>,
+}
+
+/// `elements/set_brush`, changes the brush, returns the new paint settings
+fn set_brush(
+    In(params): In<Option<Value>>,
+    mut paint_settings: ResMut<PaintSettings>,
+) -> BrpResult {
+    let params: SetBrushParams = parse(params)?;
+    if let Some(radius) = params.radius {
+        paint_settings.radius = bounded_radius(radius)?;
+    }
+    if let Some(colour) = &params.colour {
+        paint_settings.colour = parse_colour(colour)?;
+    }
+    if let Some(brush) = params.brush {
+        paint_settings.brush = brush;
+    }
+    if let Some(engine) = params.engine {
+        paint_settings.engine = engine;
+    }
+    serde_json::to_value(&*paint_settings).map_err(BrpError::internal)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TurnPageParams {
+    back: bool,
+}
+
+/// `elements/turn_page`, plays the page turning animation, forward unless
+/// `back` is true
+fn turn_page(
+    In(params): In<Option<Value>>,
+    mut notebook_writer: MessageWriter<NotebookInput>,
+) -> BrpResult {
+    let params: TurnPageParams = parse(params)?;
+    notebook_writer.write(if params.back {
+        NotebookInput::TurnBack
+    } else {
+        NotebookInput::TurnForward
+    });
+    Ok(Value::Null)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct OpenNotebookParams {
+    open: bool,
+}
+
+impl Default for OpenNotebookParams {
+    fn default() -> Self {
+        Self { open: true }
+    }
+}
+
+/// `elements/open_notebook`, opens the notebook, or closes it if `open` is
+/// false
+fn open_notebook(
+    In(params): In<Option<Value>>,
+    mut notebook_writer: MessageWriter<NotebookInput>,
+) -> BrpResult {
+    let params: OpenNotebookParams = parse(params)?;
+    notebook_writer.write(if params.open {
+        NotebookInput::Open
+    } else {
+        NotebookInput::Close
+    });
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod test {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+    use serde_json::json;
+
+    use super::{clear_page, draw_stroke, export_page, set_brush};
+    use crate::drawable::{
+        document::NotebookPages, paint::PaintSettings, stroke::DrawableContent,
+        sync::MAX_STROKE_POINTS, BrushTips, DefaultResolution, DrawableObject, InSelectedNotebook,
+    };
+
+    fn world() -> (World, Entity) {
+        let mut world = World::new();
+        world.init_resource::<PaintSettings>();
+        world.init_resource::<Time>();
+        world.init_resource::<BrushTips>();
+        world.init_resource::<DefaultResolution>();
+        world.init_resource::<NotebookPages>();
+        let page = world
+            .spawn((
+                DrawableObject,
+                InSelectedNotebook,
+                DrawableContent::new(Vec2::ONE),
+            ))
+            .id();
+        (world, page)
+    }
+
+    #[test]
+    fn strokes_are_drawn_and_cleared() {
+        let (mut world, page) = world();
+        let params = json!({"points": [[0.1, 0.2], [0.3, 0.4]], "colour": "#ff0000"});
+        let result = world
+            .run_system_once_with(draw_stroke, Some(params))
+            .unwrap()
+            .unwrap();
+        assert_eq!(result, json!({"stroke": 1}));
+
+        let content = world.get::<DrawableContent>(page).unwrap();
+        let stroke = &content.strokes[0];
+        assert_eq!(stroke.points[1].position, Vec2::new(0.3, 0.4));
+        assert_eq!(stroke.paint_settings.colour, Color::srgb(1.0, 0.0, 0.0));
+
+        world
+            .run_system_once_with(clear_page, Some(json!({"page": 0})))
+            .unwrap()
+            .unwrap();
+        assert!(world
+            .get::<DrawableContent>(page)
+            .unwrap()
+            .strokes
+            .is_empty());
+    }
+
+    #[test]
+    fn bad_params_are_errors() {
+        let (mut world, _) = world();
+        let missing_page = json!({"page": 3, "points": [[0.0, 0.0]]});
+        assert!(world
+            .run_system_once_with(draw_stroke, Some(missing_page))
+            .unwrap()
+            .is_err());
+        assert!(world
+            .run_system_once_with(set_brush, Some(json!({"colour": "red?"})))
+            .unwrap()
+            .is_err());
+
+        let settings = world
+            .run_system_once_with(set_brush, Some(json!({"radius": 0.1})))
+            .unwrap()
+            .unwrap();
+        assert_eq!(settings["radius"], json!(0.1f32));
+    }
+
+    #[test]
+    fn strokes_are_bounded() {
+        let (mut world, page) = world();
+        let too_many = vec![[0.5, 0.5]; MAX_STROKE_POINTS + 1];
+        for params in [
+            json!({"points": [[0.5, 0.5]], "radius": 1e6}),
+            json!({"points": [[0.5, 0.5]], "radius": -0.1}),
+            json!({"points": too_many}),
+            json!({"points": [[0.5, 1e39]]}),
+            json!({"points": [[0.5, 0.5]], "interval": -1.0}),
+        ] {
+            assert!(world
+                .run_system_once_with(draw_stroke, Some(params))
+                .unwrap()
+                .is_err());
+        }
+        assert!(world
+            .run_system_once_with(set_brush, Some(json!({"radius": 1e39})))
+            .unwrap()
+            .is_err());
+        assert_eq!(
+            world.resource::<PaintSettings>().radius,
+            PaintSettings::default().radius
+        );
+
+        // points off the page are moved onto its edge
+        let params = json!({"points": [[-2.0, 0.5], [0.5, 3.0]]});
+        world
+            .run_system_once_with(draw_stroke, Some(params))
+            .unwrap()
+            .unwrap();
+        let content = world.get::<DrawableContent>(page).unwrap();
+        let positions: Vec<_> = content.strokes[0]
+            .points
+            .iter()
+            .map(|point| point.position)
+            .collect();
+        assert_eq!(positions, [Vec2::new(0.0, 0.5), Vec2::new(0.5, 1.0)]);
+    }
+
+    #[test]
+    fn exports_are_bounded() {
+        let (mut world, _) = world();
+        let escaping = json!({"file_name": "../page.png"});
+        assert!(world
+            .run_system_once_with(export_page, Some(escaping))
+            .unwrap()
+            .is_err());
+
+        let result = world
+            .run_system_once_with(export_page, Some(json!({"resolution": 0})))
+            .unwrap()
+            .unwrap();
+        let png = STANDARD.decode(result["png"].as_str().unwrap()).unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+        assert_eq!((image.width(), image.height()), (1, 1));
+    }
+
+    #[test]
+    fn pages_are_numbered_like_the_notebook() {
+        let (mut world, first) = world();
+        let second = world
+            .spawn((
+                DrawableObject,
+                InSelectedNotebook,
+                DrawableContent::new(Vec2::ONE),
+            ))
+            .id();
+        // the drawables show pages 2 and 3, in the order of their entities
+        let shown = first.max(second);
+        let pages = vec![DrawableContent::new(Vec2::ONE); 4];
+        world.resource_mut::<NotebookPages>().set_pages(pages, 2);
+
+        let params = json!({"page": 3, "points": [[0.5, 0.5]]});
+        world
+            .run_system_once_with(draw_stroke, Some(params))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            world.get::<DrawableContent>(shown).unwrap().strokes.len(),
+            1
+        );
+
+        // page 0 isn't shown, page 4 doesn't exist
+        for page in [0, 4] {
+            let params = json!({"page": page, "points": [[0.5, 0.5]]});
+            assert!(world
+                .run_system_once_with(draw_stroke, Some(params))
+                .unwrap()
+                .is_err());
+        }
+        let exported = world
+            .run_system_once_with(export_page, Some(json!({"page": 0, "resolution": 8})))
+            .unwrap();
+        assert!(exported.is_ok());
+    }
+}
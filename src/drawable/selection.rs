@@ -0,0 +1,739 @@
+//! Selecting part of a page to move, scale or rotate it
+//!
+//! A rectangle or lasso region is lifted into a [`FloatingSelection`], which
+//! takes the strokes completely inside the region and the raster layer pixels
+//! under it off the page. The selection is shown with handles: dragging inside
+//! moves it, the corners scale it and the handle above it rotates it. It goes
+//! back onto the page when clicking outside it, pressing Enter or switching
+//! tools, Escape puts it back where it was.
+
+use bevy::prelude::*;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    drawable::DrawableCursor,
+    paint::{brush::blend_over, stamp::BrushTips, PaintImage, PaintSettings},
+    stroke::{DrawableContent, Stroke},
+    tool::Tool,
+};
+
+/// Distance from a handle that still grabs it, in page units
+const HANDLE_RADIUS: f32 = 0.02;
+/// Distance of the rotate handle above the selection, in page units
+const ROTATE_HANDLE_OFFSET: f32 = 0.05;
+/// Distance the cursor has to move to add a point to a lasso, in page units
+const LASSO_SPACING: f32 = 0.005;
+const OUTLINE_COLOUR: Color = Color::srgb(0.2, 0.5, 1.0);
+/// Width of the outline in pixels
+const OUTLINE_WIDTH: f32 = 1.5;
+/// Diameter of the handles in pixels
+const HANDLE_SIZE: f32 = 6.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelectionShape {
+    #[default]
+    Rectangle,
+    Lasso,
+}
+
+/// A region being dragged out with the selection tool
+#[derive(Debug)]
+struct SelectionRegion {
+    drawable: Entity,
+    shape: SelectionShape,
+    /// the corners of a rectangle or the path of a lasso, in page units
+    points: Vec<Vec2>,
+}
+
+impl SelectionRegion {
+    fn polygon(&self) -> Vec<Vec2> {
+        match (self.shape, self.points.as_slice()) {
+            (SelectionShape::Rectangle, [start, .., end]) => vec![
+                *start,
+                Vec2::new(end.x, start.y),
+                *end,
+                Vec2::new(start.x, end.y),
+            ],
+            (SelectionShape::Rectangle, _) => Vec::new(),
+            (SelectionShape::Lasso, points) => points.to_vec(),
+        }
+    }
+
+    fn add_point(&mut self, position: Vec2) {
+        match self.shape {
+            SelectionShape::Rectangle => {
+                self.points.truncate(1);
+                self.points.push(position);
+            }
+            SelectionShape::Lasso => {
+                let moved = self
+                    .points
+                    .last()
+                    .is_none_or(|last| last.distance(position) >= LASSO_SPACING);
+                if moved {
+                    self.points.push(position);
+                }
+            }
+        }
+    }
+}
+
+/// even-odd test of whether `point` is inside `polygon`
+pub(super) fn polygon_contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (index, &a) in polygon.iter().enumerate() {
+        let b = polygon[(index + polygon.len() - 1) % polygon.len()];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Move, rotation and uniform scale of a selection around its centre.
+///
+/// Rotation and scale happen in plane units, so a rotated selection isn't
+/// skewed on pages that aren't square.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectionTransform {
+    /// in page units
+    pub translation: Vec2,
+    /// in radians
+    pub rotation: f32,
+    pub scale: f32,
+}
+
+impl Default for SelectionTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vec2::ZERO,
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl SelectionTransform {
+    fn apply(&self, point: Vec2, centre: Vec2, plane_scale: Vec2) -> Vec2 {
+        let local = (point - centre) * plane_scale;
+        let local = Vec2::from_angle(self.rotation).rotate(local) * self.scale;
+        centre + self.translation + local / plane_scale
+    }
+
+    fn inverse(&self, point: Vec2, centre: Vec2, plane_scale: Vec2) -> Vec2 {
+        let local = (point - centre - self.translation) * plane_scale;
+        let local = Vec2::from_angle(-self.rotation).rotate(local) / self.scale;
+        centre + local / plane_scale
+    }
+}
+
+/// Pixels lifted from a raster layer
+#[derive(Debug, Clone)]
+struct FloatingRaster {
+    pixels: RgbaImage,
+    /// page position of the top left pixel
+    origin: Vec2,
+    /// size of the raster layer the pixels came from
+    source_size: UVec2,
+}
+
+impl FloatingRaster {
+    fn pixel_size(&self) -> Vec2 {
+        Vec2::ONE / self.source_size.as_vec2()
+    }
+
+    fn bounds(&self) -> Rect {
+        let size = UVec2::new(self.pixels.width(), self.pixels.height()).as_vec2();
+        Rect::from_corners(self.origin, self.origin + size * self.pixel_size())
+    }
+}
+
+/// Bilinear sample of `image` at a position in pixels, pixels outside the
+/// image are transparent so the edges stay soft
+fn sample_bilinear(image: &RgbaImage, position: Vec2) -> [f32; 4] {
+    let position = position - Vec2::splat(0.5);
+    let base = position.floor();
+    let fraction = position - base;
+
+    let mut premultiplied = [0.0; 4];
+    for (dx, dy, weight) in [
+        (0, 0, (1.0 - fraction.x) * (1.0 - fraction.y)),
+        (1, 0, fraction.x * (1.0 - fraction.y)),
+        (0, 1, (1.0 - fraction.x) * fraction.y),
+        (1, 1, fraction.x * fraction.y),
+    ] {
+        let (x, y) = (base.x as i64 + dx, base.y as i64 + dy);
+        if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+            continue;
+        }
+        let pixel = image.get_pixel(x as u32, y as u32).0;
+        let alpha = pixel[3] as f32 / 255.0 * weight;
+        for channel in 0..3 {
+            premultiplied[channel] += pixel[channel] as f32 / 255.0 * alpha;
+        }
+        premultiplied[3] += alpha;
+    }
+
+    let alpha = premultiplied[3];
+    if alpha <= 0.0 {
+        return [0.0; 4];
+    }
+    [
+        premultiplied[0] / alpha,
+        premultiplied[1] / alpha,
+        premultiplied[2] / alpha,
+        alpha,
+    ]
+}
+
+/// Part of a page that has been lifted off to be transformed
+#[derive(Debug, Clone)]
+pub struct FloatingSelection {
+    pub drawable: Entity,
+    strokes: Vec<Stroke>,
+    raster: Option<FloatingRaster>,
+    /// bounds before transforming, in page units
+    bounds: Rect,
+    plane_scale: Vec2,
+    pub transform: SelectionTransform,
+}
+
+impl FloatingSelection {
+    /// Takes the strokes completely inside `polygon` and the raster pixels
+    /// under it off the page, `None` if there's nothing there.
+    pub(super) fn lift(
+        content: &mut DrawableContent,
+        drawable: Entity,
+        polygon: &[Vec2],
+    ) -> Option<Self> {
+        if polygon.len() < 3 {
+            return None;
+        }
+        let plane_scale = content.plane_scale;
+
+        let inside: Vec<_> = content
+            .strokes
+            .iter()
+            .filter(|stroke| {
+                stroke
+                    .points
+                    .iter()
+                    .all(|point| polygon_contains(polygon, point.position))
+            })
+            .map(|stroke| stroke.id)
+            .collect();
+        let strokes: Vec<_> = inside
+            .into_iter()
+            .filter_map(|id| content.remove(id))
+            .collect();
+
+        let raster = content
+            .raster
+            .as_mut()
+            .and_then(|raster| lift_raster(raster, polygon));
+        if raster.is_some() {
+            content.request_render();
+        }
+
+        let mut bounds = raster.as_ref().map(FloatingRaster::bounds);
+        for stroke in &strokes {
+            let radius = stroke.paint_settings.radius / plane_scale;
+            for point in &stroke.points {
+                let point_bounds =
+                    Rect::from_corners(point.position - radius, point.position + radius);
+                bounds = Some(bounds.map_or(point_bounds, |bounds| bounds.union(point_bounds)));
+            }
+        }
+
+        Some(Self {
+            drawable,
+            strokes,
+            raster,
+            bounds: bounds?,
+            plane_scale,
+            transform: SelectionTransform::default(),
+        })
+    }
+
+    /// floats an image in the middle of the page, `source_size` is the size of
+    /// the page image the pixels are meant for
+    #[cfg(feature = "system-clipboard")]
+    pub(super) fn from_image(
+        drawable: Entity,
+        pixels: RgbaImage,
+        source_size: UVec2,
+        plane_scale: Vec2,
+    ) -> Self {
+        let size = UVec2::new(pixels.width(), pixels.height()).as_vec2() / source_size.as_vec2();
+        let raster = FloatingRaster {
+            pixels,
+            origin: Vec2::splat(0.5) - size / 2.0,
+            source_size,
+        };
+        Self {
+            drawable,
+            bounds: raster.bounds(),
+            strokes: Vec::new(),
+            raster: Some(raster),
+            plane_scale,
+            transform: SelectionTransform::default(),
+        }
+    }
+
+    fn centre(&self) -> Vec2 {
+        self.bounds.center()
+    }
+
+    fn apply(&self, point: Vec2) -> Vec2 {
+        self.transform.apply(point, self.centre(), self.plane_scale)
+    }
+
+    fn transformed_strokes(&self) -> impl Iterator<Item = Stroke> + '_ {
+        self.strokes.iter().map(|stroke| {
+            let mut stroke = stroke.clone();
+            for point in &mut stroke.points {
+                point.position = self.apply(point.position);
+            }
+            stroke.paint_settings.radius *= self.transform.scale;
+            stroke
+        })
+    }
+
+    /// corners of the transformed bounds, clockwise from the top left
+    pub(super) fn corners(&self) -> [Vec2; 4] {
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+            .map(|corner| self.apply(corner))
+    }
+
+    fn rotate_handle(&self) -> Vec2 {
+        let top = Vec2::new(
+            self.bounds.center().x,
+            self.bounds.min.y - ROTATE_HANDLE_OFFSET,
+        );
+        self.apply(top)
+    }
+
+    fn handle_at(&self, position: Vec2) -> Option<SelectionDragKind> {
+        if self.rotate_handle().distance(position) <= HANDLE_RADIUS {
+            return Some(SelectionDragKind::Rotate);
+        }
+        if self
+            .corners()
+            .iter()
+            .any(|corner| corner.distance(position) <= HANDLE_RADIUS)
+        {
+            return Some(SelectionDragKind::Scale);
+        }
+        let local = self
+            .transform
+            .inverse(position, self.centre(), self.plane_scale);
+        self.bounds
+            .contains(local)
+            .then_some(SelectionDragKind::Move)
+    }
+
+    /// resamples the transformed raster pixels over `data`, an rgba8 image
+    fn composite_raster(&self, data: &mut [u8], size: UVec2) {
+        let Some(raster) = &self.raster else {
+            return;
+        };
+        let raster_bounds = raster.bounds();
+        let (min, max) = (raster_bounds.min, raster_bounds.max);
+        let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+            .map(|corner| self.apply(corner) * size.as_vec2());
+        let low = corners.iter().fold(Vec2::INFINITY, |a, b| a.min(*b));
+        let high = corners.iter().fold(Vec2::NEG_INFINITY, |a, b| a.max(*b));
+        let low = low.floor().max(Vec2::ZERO).as_uvec2();
+        let high = high.ceil().as_uvec2().min(size);
+
+        for y in low.y..high.y {
+            for x in low.x..high.x {
+                let page = (UVec2::new(x, y).as_vec2() + 0.5) / size.as_vec2();
+                let source = self
+                    .transform
+                    .inverse(page, self.centre(), self.plane_scale);
+                let colour = sample_bilinear(
+                    &raster.pixels,
+                    (source - raster.origin) / raster.pixel_size(),
+                );
+                if colour[3] > 0.0 {
+                    let index = ((y * size.x + x) * 4) as usize;
+                    blend_over(&mut data[index..index + 4], colour, 1.0);
+                }
+            }
+        }
+    }
+
+    /// puts the transformed selection onto the page
+    pub(super) fn commit(self, content: &mut DrawableContent) {
+        if let Some(raster) = &self.raster {
+            let size = raster.source_size;
+            let target = content
+                .raster
+                .get_or_insert_with(|| RgbaImage::new(size.x, size.y));
+            let size = UVec2::new(target.width(), target.height());
+            self.composite_raster(target, size);
+        }
+        for mut stroke in self.transformed_strokes() {
+            // pasted strokes still have the ids of the ones they were copied from
+            stroke.id = content.next_stroke_id();
+            content.push_painted(stroke);
+        }
+        content.request_render();
+    }
+
+    /// draws the transformed selection onto `image`
+    pub(super) fn render(&self, image: &mut Image, brush_tips: &BrushTips) {
+        let size = UVec2::new(image.width(), image.height());
+        if let Some(data) = image.data.as_mut() {
+            self.composite_raster(data, size);
+        }
+        for stroke in self.transformed_strokes() {
+            stroke.render(image, self.plane_scale, brush_tips);
+        }
+    }
+
+    /// draws the transformed selection with its outline and handles
+    fn render_preview(&self, image: &mut Image, brush_tips: &BrushTips) {
+        self.render(image, brush_tips);
+
+        let corners = self.corners();
+        draw_outline(image, &corners);
+        let top_centre = (corners[0] + corners[1]) / 2.0;
+        draw_outline(image, &[top_centre, self.rotate_handle()]);
+        let handle_settings = outline_settings(image, HANDLE_SIZE);
+        for handle in corners.into_iter().chain([self.rotate_handle()]) {
+            let (x, y) = to_pixel(image, handle);
+            image.draw_spot(x, y, &handle_settings, Vec2::ONE);
+        }
+    }
+}
+
+/// pulls the pixels inside `polygon` out of `raster`
+fn lift_raster(raster: &mut RgbaImage, polygon: &[Vec2]) -> Option<FloatingRaster> {
+    let size = UVec2::new(raster.width(), raster.height());
+    let low = polygon.iter().fold(Vec2::INFINITY, |a, b| a.min(*b));
+    let high = polygon.iter().fold(Vec2::NEG_INFINITY, |a, b| a.max(*b));
+    let low = (low * size.as_vec2()).floor().max(Vec2::ZERO).as_uvec2();
+    let high = (high * size.as_vec2()).ceil().as_uvec2().min(size);
+    if low.x >= high.x || low.y >= high.y {
+        return None;
+    }
+
+    let mut pixels = RgbaImage::new(high.x - low.x, high.y - low.y);
+    let mut lifted_any = false;
+    for y in low.y..high.y {
+        for x in low.x..high.x {
+            let page = (UVec2::new(x, y).as_vec2() + 0.5) / size.as_vec2();
+            let pixel = raster.get_pixel_mut(x, y);
+            if pixel[3] == 0 || !polygon_contains(polygon, page) {
+                continue;
+            }
+            pixels.put_pixel(x - low.x, y - low.y, *pixel);
+            *pixel = image::Rgba([0; 4]);
+            lifted_any = true;
+        }
+    }
+
+    lifted_any.then(|| FloatingRaster {
+        pixels,
+        origin: low.as_vec2() / size.as_vec2(),
+        source_size: size,
+    })
+}
+
+fn to_pixel(image: &Image, position: Vec2) -> (usize, usize) {
+    let position = position * Vec2::new(image.width() as f32, image.height() as f32);
+    (position.x as usize, position.y as usize)
+}
+
+/// settings for drawing a line `width` pixels wide on a plane with a scale of one
+fn outline_settings(image: &Image, width: f32) -> PaintSettings {
+    PaintSettings {
+        radius: width / 2.0 / image.width() as f32,
+        colour: OUTLINE_COLOUR,
+        ..default()
+    }
+}
+
+/// draws lines between `points`, closing the shape if there are more than two
+fn draw_outline(image: &mut Image, points: &[Vec2]) {
+    let settings = outline_settings(image, OUTLINE_WIDTH);
+    let segments = points.len() - usize::from(points.len() <= 2);
+    for index in 0..segments {
+        let (x1, y1) = to_pixel(image, points[index]);
+        let (x2, y2) = to_pixel(image, points[(index + 1) % points.len()]);
+        image.draw_thick_line_antialias(x1, y1, x2, y2, &settings, Vec2::ONE);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SelectionDragKind {
+    Move,
+    Scale,
+    Rotate,
+}
+
+#[derive(Debug)]
+struct SelectionDrag {
+    kind: SelectionDragKind,
+    start: Vec2,
+    start_transform: SelectionTransform,
+}
+
+/// State of the selection tool
+#[derive(Resource, Debug, Default)]
+pub struct Selection {
+    region: Option<SelectionRegion>,
+    pub floating: Option<FloatingSelection>,
+    drag: Option<SelectionDrag>,
+}
+
+impl Selection {
+    /// draws the selection preview if it's on `drawable`
+    pub(super) fn render_preview(
+        &self,
+        drawable: Entity,
+        image: &mut Image,
+        brush_tips: &BrushTips,
+    ) {
+        if let Some(region) = self.region.as_ref().filter(|r| r.drawable == drawable) {
+            let polygon = region.polygon();
+            if polygon.len() >= 2 {
+                draw_outline(image, &polygon);
+            }
+        }
+        if let Some(floating) = self.floating.as_ref().filter(|f| f.drawable == drawable) {
+            floating.render_preview(image, brush_tips);
+        }
+    }
+
+    /// removes the floating selection without putting it back on the page
+    pub(super) fn discard(&mut self) -> Option<FloatingSelection> {
+        self.drag = None;
+        self.floating.take()
+    }
+
+    /// puts the floating selection back on the page and floats `floating` instead
+    pub(super) fn replace(
+        &mut self,
+        floating: FloatingSelection,
+        content_query: &mut Query<&mut DrawableContent>,
+    ) {
+        self.commit(content_query, false);
+        self.floating = Some(floating);
+    }
+
+    fn commit(&mut self, content_query: &mut Query<&mut DrawableContent>, reset: bool) {
+        self.drag = None;
+        let Some(mut floating) = self.floating.take() else {
+            return;
+        };
+        if reset {
+            floating.transform = SelectionTransform::default();
+        }
+        if let Ok(mut content) = content_query.get_mut(floating.drawable) {
+            floating.commit(&mut content);
+        }
+    }
+}
+
+/// Drags out selection regions and transforms floating selections, see the
+/// [module docs](self)
+pub(super) fn selection_tool_system(
+    mut cursor: DrawableCursor,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    tool: Res<Tool>,
+    mut selection: ResMut<Selection>,
+    mut content_query: Query<&mut DrawableContent>,
+) {
+    let Tool::Select(shape) = *tool else {
+        selection.region = None;
+        selection.commit(&mut content_query, false);
+        return;
+    };
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        selection.commit(&mut content_query, false);
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        selection.commit(&mut content_query, true);
+    }
+
+    let hit = buttons
+        .pressed(MouseButton::Left)
+        .then(|| cursor.hit())
+        .flatten();
+
+    if buttons.just_pressed(MouseButton::Left) {
+        if let Some(floating) = &selection.floating {
+            let handle = hit
+                .filter(|hit| hit.drawable_object == floating.drawable)
+                .and_then(|hit| Some((floating.handle_at(hit.position)?, hit.position)));
+            match handle {
+                Some((kind, start)) => {
+                    selection.drag = Some(SelectionDrag {
+                        kind,
+                        start,
+                        start_transform: floating.transform,
+                    });
+                }
+                None => selection.commit(&mut content_query, false),
+            }
+        }
+        if selection.floating.is_none() {
+            selection.region = hit.map(|hit| SelectionRegion {
+                drawable: hit.drawable_object,
+                shape,
+                points: vec![hit.position],
+            });
+        }
+    } else if let Some(hit) = hit {
+        let selection = &mut *selection;
+        if let Some(region) = selection
+            .region
+            .as_mut()
+            .filter(|region| region.drawable == hit.drawable_object)
+        {
+            region.add_point(hit.position);
+            if let Ok(mut content) = content_query.get_mut(region.drawable) {
+                content.request_render();
+            }
+        }
+        if let (Some(floating), Some(drag)) = (&mut selection.floating, &selection.drag) {
+            if floating.drawable == hit.drawable_object {
+                drag_floating(floating, drag, hit.position);
+                if let Ok(mut content) = content_query.get_mut(floating.drawable) {
+                    content.request_render();
+                }
+            }
+        }
+    }
+
+    if buttons.just_released(MouseButton::Left) {
+        selection.drag = None;
+        if let Some(region) = selection.region.take() {
+            if let Ok(mut content) = content_query.get_mut(region.drawable) {
+                selection.floating =
+                    FloatingSelection::lift(&mut content, region.drawable, &region.polygon());
+                content.request_render();
+            }
+        }
+    }
+}
+
+fn drag_floating(floating: &mut FloatingSelection, drag: &SelectionDrag, position: Vec2) {
+    let start = drag.start_transform;
+    let plane_scale = floating.plane_scale;
+    // the centre after moving, in plane units
+    let centre = (floating.centre() + start.translation) * plane_scale;
+    let from = drag.start * plane_scale - centre;
+    let to = position * plane_scale - centre;
+
+    floating.transform = match drag.kind {
+        SelectionDragKind::Move => SelectionTransform {
+            translation: start.translation + position - drag.start,
+            ..start
+        },
+        SelectionDragKind::Scale => SelectionTransform {
+            scale: start.scale * to.length() / from.length().max(f32::EPSILON),
+            ..start
+        },
+        SelectionDragKind::Rotate => SelectionTransform {
+            rotation: start.rotation + from.angle_to(to),
+            ..start
+        },
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+    use image::{Rgba, RgbaImage};
+
+    use super::{FloatingSelection, SelectionTransform};
+    use crate::drawable::{
+        paint::PaintSettings,
+        stroke::{DrawableContent, Stroke, StrokePoint},
+    };
+
+    fn push_stroke(content: &mut DrawableContent, points: &[(f32, f32)]) {
+        let stroke = Stroke {
+            id: content.next_stroke_id(),
+            points: points
+                .iter()
+                .map(|&(x, y)| StrokePoint {
+                    position: Vec2::new(x, y),
+                    pressure: 1.0,
+                    time: 0.0,
+                })
+                .collect(),
+            paint_settings: PaintSettings {
+                radius: 0.01,
+                ..default()
+            },
+            started_at: 0.0,
+        };
+        content.push_painted(stroke);
+    }
+
+    #[test]
+    fn lasso_lifts_strokes_inside() {
+        let mut content = DrawableContent::new(Vec2::ONE);
+        push_stroke(&mut content, &[(0.2, 0.2), (0.3, 0.3)]);
+        push_stroke(&mut content, &[(0.2, 0.2), (0.8, 0.8)]);
+
+        let triangle = [
+            Vec2::new(0.1, 0.1),
+            Vec2::new(0.6, 0.1),
+            Vec2::new(0.1, 0.6),
+        ];
+        let floating = FloatingSelection::lift(&mut content, Entity::PLACEHOLDER, &triangle)
+            .expect("a stroke is inside");
+
+        assert_eq!(floating.strokes.len(), 1);
+        assert_eq!(content.strokes.len(), 1);
+    }
+
+    #[test]
+    fn transform_inverse_round_trips() {
+        let transform = SelectionTransform {
+            translation: Vec2::new(0.1, -0.2),
+            rotation: 0.7,
+            scale: 1.5,
+        };
+        let (centre, plane_scale) = (Vec2::new(0.4, 0.5), Vec2::new(1.0, 2.0));
+        let point = Vec2::new(0.3, 0.9);
+
+        let moved = transform.apply(point, centre, plane_scale);
+        let back = transform.inverse(moved, centre, plane_scale);
+        assert!(back.distance(point) < 1e-5, "{back} != {point}");
+    }
+
+    #[test]
+    fn moving_raster_selection() {
+        let mut content = DrawableContent::new(Vec2::ONE);
+        let mut raster = RgbaImage::new(10, 10);
+        raster.put_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        content.raster = Some(raster);
+
+        let square = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.5, 0.0),
+            Vec2::new(0.5, 0.5),
+            Vec2::new(0.0, 0.5),
+        ];
+        let mut floating =
+            FloatingSelection::lift(&mut content, Entity::PLACEHOLDER, &square).unwrap();
+        floating.transform.translation = Vec2::new(0.5, 0.3);
+        floating.commit(&mut content);
+
+        let raster = content.raster.unwrap();
+        assert_eq!(raster.get_pixel(2, 2)[3], 0);
+        assert_eq!(*raster.get_pixel(7, 5), Rgba([255, 0, 0, 255]));
+    }
+}
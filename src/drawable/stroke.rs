@@ -0,0 +1,404 @@
+//! Strokes stored as geometry
+//!
+//! Every stroke drawn on a drawable is kept as a [`Stroke`] in its
+//! [`DrawableContent`], the image shown on the drawable is rendered from them.
+//! This means strokes can be edited individually and rendered again at any
+//! resolution.
+
+use bevy::prelude::*;
+use image::{imageops::FilterType, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    clipboard::ctrl_pressed,
+    drawable::DrawableCursor,
+    drawable_material::{create_drawable_image, DrawableMaterial},
+    guide::Ruler,
+    metadata::PageMetadata,
+    paint::{paint_input::PaintInput, stamp::BrushTips, PaintSettings, StrokeRaster},
+    selection::Selection,
+    template::PageTemplate,
+    text::TextEditor,
+};
+use crate::config::Settings;
+
+/// A sampled point of a stroke
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StrokePoint {
+    /// position on the page, between 0 and 1 on both axes
+    pub position: Vec2,
+    pub pressure: f32,
+    /// seconds since the start of the stroke
+    pub time: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StrokeId(pub u64);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stroke {
+    pub id: StrokeId,
+    pub points: Vec<StrokePoint>,
+    pub paint_settings: PaintSettings,
+    /// seconds since the app started when the stroke was started
+    pub started_at: f64,
+}
+
+impl Stroke {
+    /// paints the whole stroke onto `image`
+    pub fn render(&self, image: &mut Image, plane_scale: Vec2, brush_tips: &BrushTips) {
+        let size = Vec2::new(image.width() as f32, image.height() as f32);
+        let mut raster = StrokeRaster::new(image, &self.paint_settings, brush_tips);
+        for point in &self.points {
+            raster.add_point(
+                image,
+                point.position * size,
+                &self.paint_settings,
+                plane_scale,
+            );
+        }
+    }
+
+    /// distance from `position` to the closest part of the stroke, in page units
+    fn distance_to(&self, position: Vec2) -> f32 {
+        let segments = self
+            .points
+            .windows(2)
+            .map(|points| (points[0].position, points[1].position));
+        let single_point = self
+            .points
+            .first()
+            .map(|point| (point.position, point.position));
+
+        segments
+            .chain(single_point)
+            .map(|(start, end)| {
+                let direction = end - start;
+                let length_sq = direction.length_squared();
+                let t = if length_sq == 0.0 {
+                    0.0
+                } else {
+                    ((position - start).dot(direction) / length_sq).clamp(0.0, 1.0)
+                };
+                position.distance(start + direction * t)
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+/// The strokes of a drawable object, the image of the drawable is rendered from these
+#[derive(Component, Debug, Default, Clone)]
+pub struct DrawableContent {
+    pub strokes: Vec<Stroke>,
+    next_id: u64,
+    /// scale of the drawable plane, brush sizes are in world units
+    pub plane_scale: Vec2,
+    /// pixels that aren't strokes, like pasted images, drawn below the strokes
+    pub raster: Option<RgbaImage>,
+    /// background of the page, drawn by the shader below the image
+    pub template: PageTemplate,
+    pub metadata: PageMetadata,
+    needs_render: bool,
+}
+
+impl DrawableContent {
+    pub fn new(plane_scale: Vec2) -> Self {
+        Self {
+            plane_scale,
+            metadata: PageMetadata::new(),
+            ..default()
+        }
+    }
+
+    pub fn next_stroke_id(&mut self) -> StrokeId {
+        self.next_id += 1;
+        StrokeId(self.next_id)
+    }
+
+    /// adds a stroke that has already been painted on the image
+    pub fn push_painted(&mut self, stroke: Stroke) {
+        self.next_id = self.next_id.max(stroke.id.0);
+        self.strokes.push(stroke);
+    }
+
+    pub(super) fn stroke_mut(&mut self, id: StrokeId) -> Option<&mut Stroke> {
+        self.strokes.iter_mut().find(|stroke| stroke.id == id)
+    }
+
+    /// the topmost stroke within its brush radius of `position`
+    pub fn stroke_at(&self, position: Vec2) -> Option<StrokeId> {
+        self.strokes
+            .iter()
+            .rev()
+            .find(|stroke| {
+                let radius = stroke.paint_settings.radius / self.plane_scale.x;
+                stroke.distance_to(position) <= radius
+            })
+            .map(|stroke| stroke.id)
+    }
+
+    pub fn translate(&mut self, id: StrokeId, offset: Vec2) {
+        if let Some(stroke) = self.stroke_mut(id) {
+            for point in &mut stroke.points {
+                point.position += offset;
+            }
+            self.needs_render = true;
+        }
+    }
+
+    pub fn recolour(&mut self, id: StrokeId, colour: Color) {
+        if let Some(stroke) = self.stroke_mut(id) {
+            stroke.paint_settings.colour = colour;
+            self.needs_render = true;
+        }
+    }
+
+    pub fn remove(&mut self, id: StrokeId) -> Option<Stroke> {
+        let index = self.strokes.iter().position(|stroke| stroke.id == id)?;
+        self.needs_render = true;
+        Some(self.strokes.remove(index))
+    }
+
+    /// renders the image again at the end of the frame
+    pub fn request_render(&mut self) {
+        self.needs_render = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.raster = None;
+        self.needs_render = true;
+    }
+
+    /// clears `image` to the raster layer and paints every stroke onto it
+    pub fn render(&self, image: &mut Image, brush_tips: &BrushTips) {
+        let (width, height) = (image.width(), image.height());
+        if let Some(data) = image.data.as_mut() {
+            match &self.raster {
+                Some(raster) if raster.dimensions() == (width, height) => {
+                    data.copy_from_slice(raster.as_raw());
+                }
+                Some(raster) => {
+                    let resized =
+                        image::imageops::resize(raster, width, height, FilterType::Triangle);
+                    data.copy_from_slice(resized.as_raw());
+                }
+                None => data.fill(0),
+            }
+        }
+        for stroke in &self.strokes {
+            stroke.render(image, self.plane_scale, brush_tips);
+        }
+    }
+
+    /// renders the strokes to a new image with the given resolution
+    pub fn render_to_image(&self, resolution: usize, brush_tips: &BrushTips) -> Image {
+        let mut image = create_drawable_image(resolution);
+        self.render(&mut image, brush_tips);
+        image
+    }
+}
+
+/// An edit to a single stroke of a drawable object
+#[derive(Message, Debug, Clone)]
+pub struct EditStroke {
+    pub drawable: Entity,
+    pub stroke: StrokeId,
+    pub edit: StrokeEdit,
+}
+
+#[derive(Debug, Clone)]
+pub enum StrokeEdit {
+    /// moves the stroke by an offset in page units
+    Move(Vec2),
+    Recolour(Color),
+    Delete,
+}
+
+pub(super) fn apply_stroke_edits(
+    mut reader: MessageReader<EditStroke>,
+    mut content_query: Query<&mut DrawableContent>,
+) {
+    for edit in reader.read() {
+        let Ok(mut content) = content_query.get_mut(edit.drawable) else {
+            continue;
+        };
+        match edit.edit {
+            StrokeEdit::Move(offset) => content.translate(edit.stroke, offset),
+            StrokeEdit::Recolour(colour) => content.recolour(edit.stroke, colour),
+            StrokeEdit::Delete => {
+                content.remove(edit.stroke);
+            }
+        }
+    }
+}
+
+/// renders the images of drawables whose strokes were edited, with the stroke
+/// being drawn, the selection, the text being typed and the ruler on top
+#[allow(clippy::too_many_arguments)]
+pub(super) fn render_edited_drawables(
+    mut content_query: Query<(
+        Entity,
+        &mut DrawableContent,
+        &MeshMaterial3d<DrawableMaterial>,
+    )>,
+    // this is only mutable for change detection to work
+    mut drawable_materials: ResMut<Assets<DrawableMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    brush_tips: Res<BrushTips>,
+    selection: Res<Selection>,
+    text_editor: Res<TextEditor>,
+    fonts: Res<Assets<Font>>,
+    ruler: Res<Ruler>,
+    paint_input: Res<PaintInput>,
+) {
+    for (entity, mut content, mesh_material) in &mut content_query {
+        if !content.needs_render {
+            continue;
+        }
+        content.needs_render = false;
+
+        if let Some(material) = drawable_materials.get_mut(&mesh_material.0) {
+            if let Some(image) = images.get_mut(&material.draw_texture) {
+                content.render(image, &brush_tips);
+                if let Some(active) = paint_input.stroke.as_ref() {
+                    if active.drawable == entity {
+                        active
+                            .stroke
+                            .render(image, content.plane_scale, &brush_tips);
+                    }
+                }
+                selection.render_preview(entity, image, &brush_tips);
+                text_editor.render_preview(entity, image, &fonts);
+                ruler.render_preview(entity, image);
+            }
+        }
+    }
+}
+
+/// The stroke picked with the right mouse button
+#[derive(Resource, Debug, Default)]
+pub struct SelectedStroke(pub Option<(Entity, StrokeId)>);
+
+/// Right click picks a stroke, which can then be moved with the arrow keys,
+/// recoloured to the brush colour with C and deleted with Delete.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn stroke_selection_system(
+    mut cursor: DrawableCursor,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    content_query: Query<&DrawableContent>,
+    paint_settings: Res<PaintSettings>,
+    mut selected: ResMut<SelectedStroke>,
+    mut edit_writer: MessageWriter<EditStroke>,
+) {
+    const NUDGE: f32 = 0.005;
+
+    if buttons.just_pressed(MouseButton::Right) {
+        selected.0 = cursor.hit().and_then(|hit| {
+            let content = content_query.get(hit.drawable_object).ok()?;
+            let stroke = content.stroke_at(hit.position)?;
+            Some((hit.drawable_object, stroke))
+        });
+    }
+
+    let Some((drawable, stroke)) = selected.0 else {
+        return;
+    };
+    let mut edit = |edit| {
+        edit_writer.write(EditStroke {
+            drawable,
+            stroke,
+            edit,
+        });
+    };
+
+    let arrows = [
+        (KeyCode::ArrowLeft, Vec2::new(-NUDGE, 0.0)),
+        (KeyCode::ArrowRight, Vec2::new(NUDGE, 0.0)),
+        (KeyCode::ArrowUp, Vec2::new(0.0, -NUDGE)),
+        (KeyCode::ArrowDown, Vec2::new(0.0, NUDGE)),
+    ];
+    for (key, offset) in arrows {
+        if keyboard_input.just_pressed(key) {
+            edit(StrokeEdit::Move(offset));
+        }
+    }
+    // Ctrl+C copies instead
+    let keys = &settings.key_bindings;
+    if keyboard_input.just_pressed(keys.recolour) && !ctrl_pressed(&keyboard_input) {
+        edit(StrokeEdit::Recolour(paint_settings.colour));
+    }
+    if keyboard_input.just_pressed(keys.delete) {
+        edit(StrokeEdit::Delete);
+        selected.0 = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use super::{DrawableContent, Stroke, StrokePoint};
+    use crate::drawable::paint::{stamp::BrushTips, Brush, PaintSettings};
+
+    fn stroke(content: &mut DrawableContent, points: &[(f32, f32)]) -> Stroke {
+        Stroke {
+            id: content.next_stroke_id(),
+            points: points
+                .iter()
+                .map(|&(x, y)| StrokePoint {
+                    position: Vec2::new(x, y),
+                    pressure: 1.0,
+                    time: 0.0,
+                })
+                .collect(),
+            paint_settings: PaintSettings {
+                radius: 0.05,
+                brush: Brush::Marker,
+                ..default()
+            },
+            started_at: 0.0,
+        }
+    }
+
+    fn alpha(image: &Image, x: u32, y: u32) -> u8 {
+        image.pixel_bytes(UVec3::new(x, y, 0)).unwrap()[3]
+    }
+
+    #[test]
+    fn renders_at_any_resolution() {
+        let mut content = DrawableContent::new(Vec2::ONE);
+        let line = stroke(&mut content, &[(0.2, 0.5), (0.8, 0.5)]);
+        content.push_painted(line);
+
+        let brush_tips = BrushTips::default();
+        let small = content.render_to_image(64, &brush_tips);
+        let large = content.render_to_image(256, &brush_tips);
+
+        assert!(alpha(&small, 32, 32) > 0);
+        assert!(alpha(&large, 128, 128) > 0);
+        assert_eq!(alpha(&large, 128, 20), 0);
+    }
+
+    #[test]
+    fn edit_single_stroke() {
+        let mut content = DrawableContent::new(Vec2::ONE);
+        let top = stroke(&mut content, &[(0.2, 0.2), (0.8, 0.2)]);
+        let bottom = stroke(&mut content, &[(0.2, 0.8), (0.8, 0.8)]);
+        let (top_id, bottom_id) = (top.id, bottom.id);
+        content.push_painted(top);
+        content.push_painted(bottom);
+
+        assert_eq!(content.stroke_at(Vec2::new(0.5, 0.21)), Some(top_id));
+        assert_eq!(content.stroke_at(Vec2::new(0.5, 0.5)), None);
+
+        content.translate(top_id, Vec2::new(0.0, 0.3));
+        assert_eq!(content.stroke_at(Vec2::new(0.5, 0.5)), Some(top_id));
+
+        content.remove(bottom_id);
+        assert_eq!(content.stroke_at(Vec2::new(0.5, 0.8)), None);
+        assert_eq!(content.strokes.len(), 1);
+    }
+}
@@ -0,0 +1,706 @@
+//! Drawing in the same notebook from several machines
+//!
+//! One app hosts with `--host <address>` and the others join it with
+//! `--join <address>`. Strokes drawn with the pen are sent to the other peers
+//! while they're being drawn, as newline separated JSON over TCP, and the host
+//! forwards what each peer sends to the rest. Pages are matched by their
+//! number in the notebook, so every peer should have the same notebook open,
+//! and strokes on pages a peer isn't showing are left out there. Only new
+//! strokes are synced, not edits, text or templates.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{BuildHasher, RandomState},
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    document::NotebookPages,
+    drawable_material::DrawableMaterial,
+    pages::shown_pages,
+    paint::{paint_input::PaintInput, stamp::BrushTips, PaintSettings, StrokeRaster},
+    stroke::{DrawableContent, Stroke, StrokeId, StrokePoint},
+    PageFilter,
+};
+
+/// Longest message line read from a peer, peers sending longer ones are
+/// disconnected
+const MAX_LINE_LENGTH: usize = 1 << 20;
+
+/// How long writing to a peer can take before it's dropped, so a stalled
+/// peer doesn't hold up the others
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Largest brush radius a peer can draw with
+pub(super) const MAX_RADIUS: f32 = 1.0;
+
+/// Most points a stroke from a peer can have
+pub(super) const MAX_STROKE_POINTS: usize = 10_000;
+
+/// Identifies an app taking part in a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PeerId(pub u64);
+
+/// A change to a stroke being drawn, stroke ids are the sending peer's
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StrokeEvent {
+    /// a stroke was started on page `page`, counting from 0
+    Start {
+        page: usize,
+        stroke: StrokeId,
+        paint_settings: PaintSettings,
+    },
+    /// the points of the stroke from index `from` on were replaced by `points`
+    Points {
+        stroke: StrokeId,
+        from: usize,
+        points: Vec<StrokePoint>,
+    },
+    End {
+        stroke: StrokeId,
+    },
+    /// the connection to the peer closed, ending its strokes
+    Left,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncMessage {
+    pub peer: PeerId,
+    pub event: StrokeEvent,
+}
+
+/// The streams to the other peers, writing a line to them is atomic
+#[derive(Debug, Clone, Default)]
+struct Peers(Arc<Mutex<Vec<(SocketAddr, TcpStream)>>>);
+
+impl Peers {
+    /// writes `line` to every peer but `except`, dropping peers that can't be
+    /// written to
+    fn send(&self, line: &str, except: Option<SocketAddr>) {
+        let mut peers = self.0.lock().unwrap();
+        peers.retain_mut(|(address, stream)| {
+            Some(*address) == except
+                || stream
+                    .write_all(line.as_bytes())
+                    .and_then(|()| stream.write_all(b"\n"))
+                    .is_ok()
+        });
+    }
+
+    /// starts reading messages from `stream`, forwarding them to the other
+    /// peers if `relay` is set
+    fn add(&self, stream: TcpStream, incoming: Sender<SyncMessage>, relay: bool) -> io::Result<()> {
+        let address = stream.peer_addr()?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        self.0.lock().unwrap().push((address, stream));
+        info!("Connected to {address}");
+
+        let peers = self.clone();
+        thread::spawn(move || {
+            let mut line = String::new();
+            // the host forwards other peers' messages, so a stream can carry
+            // several peers
+            let mut seen = HashSet::new();
+            loop {
+                match read_line(&mut reader, &mut line) {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(error) => {
+                        warn!("Failed to read from {address}: {error}");
+                        break;
+                    }
+                }
+                let message: SyncMessage = match serde_json::from_str(&line) {
+                    Ok(message) => message,
+                    Err(error) => {
+                        warn!("Ignoring invalid message from {address}: {error}");
+                        continue;
+                    }
+                };
+                if relay {
+                    peers.send(&line, Some(address));
+                }
+                seen.insert(message.peer);
+                if incoming.send(message).is_err() {
+                    break;
+                }
+            }
+            peers.0.lock().unwrap().retain(|(peer, _)| *peer != address);
+            info!("Disconnected from {address}");
+            for peer in seen {
+                let message = SyncMessage {
+                    peer,
+                    event: StrokeEvent::Left,
+                };
+                if relay {
+                    match serde_json::to_string(&message) {
+                        Ok(line) => peers.send(&line, None),
+                        Err(error) => error!("Failed to serialise stroke event: {error}"),
+                    }
+                }
+                let _ = incoming.send(message);
+            }
+        });
+        Ok(())
+    }
+}
+
+/// A connection to a drawing session, exists while hosting or joined
+#[derive(Resource, Debug)]
+pub struct SyncConnection {
+    pub peer: PeerId,
+    /// the address of the host
+    pub address: SocketAddr,
+    /// the streams, for tests to check who's connected
+    #[cfg(test)]
+    peers: Peers,
+    outgoing: Sender<SyncMessage>,
+    incoming: Mutex<Receiver<SyncMessage>>,
+}
+
+impl SyncConnection {
+    /// starts a session other apps can join at `address`
+    pub fn host(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let (connection, peers, incoming) = Self::new(listener.local_addr()?);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| peers.add(stream, incoming.clone(), true));
+                if let Err(error) = result {
+                    warn!("Failed to accept peer: {error}");
+                }
+            }
+        });
+        info!("Hosting drawing session at {}", connection.address);
+        Ok(connection)
+    }
+
+    /// joins the session hosted at `address`
+    pub fn join(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        let (connection, peers, incoming) = Self::new(stream.peer_addr()?);
+        peers.add(stream, incoming, false)?;
+        Ok(connection)
+    }
+
+    /// the connection, the peers to add streams to and the sender for
+    /// messages from them
+    fn new(address: SocketAddr) -> (Self, Peers, Sender<SyncMessage>) {
+        let (outgoing, outgoing_receiver) = mpsc::channel::<SyncMessage>();
+        let (incoming_sender, incoming) = mpsc::channel();
+        let peers = Peers::default();
+        let writer = peers.clone();
+        thread::spawn(move || {
+            for message in outgoing_receiver {
+                match serde_json::to_string(&message) {
+                    Ok(line) => writer.send(&line, None),
+                    Err(error) => error!("Failed to serialise stroke event: {error}"),
+                }
+            }
+        });
+        let connection = Self {
+            peer: PeerId(RandomState::new().hash_one(address)),
+            address,
+            #[cfg(test)]
+            peers: peers.clone(),
+            outgoing,
+            incoming: Mutex::new(incoming),
+        };
+        (connection, peers, incoming_sender)
+    }
+
+    pub fn send(&self, event: StrokeEvent) {
+        // the writer thread only stops when the connection is dropped
+        let _ = self.outgoing.send(SyncMessage {
+            peer: self.peer,
+            event,
+        });
+    }
+
+    /// how many peers are connected, the joined host or the peers that joined
+    #[cfg(test)]
+    fn peer_count(&self) -> usize {
+        self.peers.0.lock().unwrap().len()
+    }
+
+    /// the messages received since the last call
+    pub fn receive(&self) -> Vec<SyncMessage> {
+        self.incoming.lock().unwrap().try_iter().collect()
+    }
+}
+
+/// reads a line without its newline into `line`, `Ok(false)` once the stream
+/// has ended
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<bool> {
+    line.clear();
+    let mut limited = reader.by_ref().take(MAX_LINE_LENGTH as u64 + 1);
+    if limited.read_line(line)? == 0 {
+        return Ok(false);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+    } else if line.len() > MAX_LINE_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "line is too long",
+        ));
+    }
+    Ok(true)
+}
+
+/// keeps the points a peer sent on the page, with at most
+/// [`MAX_STROKE_POINTS`] in the stroke once they're added from `from` on,
+/// dropping points that aren't finite as clamping would leave them NaN
+fn clamp_points(points: &mut Vec<StrokePoint>, from: usize) {
+    points.retain(|point| {
+        point.position.is_finite() && point.pressure.is_finite() && point.time.is_finite()
+    });
+    points.truncate(MAX_STROKE_POINTS.saturating_sub(from));
+    for point in points {
+        point.position = point.position.clamp(Vec2::ZERO, Vec2::ONE);
+        point.pressure = point.pressure.clamp(0.0, 1.0);
+    }
+}
+
+/// sends the points of `stroke` that differ from `sent`
+fn send_points(connection: &SyncConnection, stroke: &Stroke, sent: &mut Vec<StrokePoint>) {
+    let from = sent
+        .iter()
+        .zip(&stroke.points)
+        .take_while(|(sent, point)| sent == point)
+        .count();
+    if from == sent.len() && from == stroke.points.len() {
+        return;
+    }
+    sent.truncate(from);
+    sent.extend_from_slice(&stroke.points[from..]);
+    connection.send(StrokeEvent::Points {
+        stroke: stroke.id,
+        from,
+        points: stroke.points[from..].to_vec(),
+    });
+}
+
+/// The local stroke being sent, with the points sent so far
+#[derive(Debug)]
+pub(super) struct SentStroke {
+    drawable: Entity,
+    stroke: StrokeId,
+    points: Vec<StrokePoint>,
+}
+
+/// sends the stroke being drawn to the other peers
+pub(super) fn send_local_strokes(
+    connection: Res<SyncConnection>,
+    paint_input: Res<PaintInput>,
+    drawable_query: Query<Entity, PageFilter>,
+    content_query: Query<&DrawableContent>,
+    notebook_pages: Res<NotebookPages>,
+    mut sent: Local<Option<SentStroke>>,
+) {
+    let active = paint_input.stroke.as_ref();
+    let ended = sent.as_ref().is_some_and(|sent| {
+        active.is_none_or(|active| {
+            (active.drawable, active.stroke.id) != (sent.drawable, sent.stroke)
+        })
+    });
+    if ended {
+        let mut ended = sent.take().unwrap();
+        // the last points can be added in the frame the stroke ends
+        let finished = content_query.get(ended.drawable).ok().and_then(|content| {
+            content
+                .strokes
+                .iter()
+                .find(|stroke| stroke.id == ended.stroke)
+        });
+        if let Some(stroke) = finished {
+            send_points(&connection, stroke, &mut ended.points);
+        }
+        connection.send(StrokeEvent::End {
+            stroke: ended.stroke,
+        });
+    }
+
+    let Some(active) = active else {
+        return;
+    };
+    if sent.is_none() {
+        let Some((page, _)) = shown_pages(&drawable_query, &notebook_pages)
+            .into_iter()
+            .find(|(_, drawable)| *drawable == active.drawable)
+        else {
+            return;
+        };
+        connection.send(StrokeEvent::Start {
+            page,
+            stroke: active.stroke.id,
+            paint_settings: active.stroke.paint_settings.clone(),
+        });
+        *sent = Some(SentStroke {
+            drawable: active.drawable,
+            stroke: active.stroke.id,
+            points: Vec::new(),
+        });
+    }
+    if let Some(sent) = sent.as_mut() {
+        send_points(&connection, &active.stroke, &mut sent.points);
+    }
+}
+
+/// A stroke another peer is drawing
+#[derive(Debug)]
+pub(super) struct RemoteStroke {
+    drawable: Entity,
+    /// the id of the stroke on this peer
+    stroke: StrokeId,
+    /// paints new points onto the image, until points are replaced
+    raster: Option<StrokeRaster>,
+}
+
+/// adds the strokes other peers are drawing to the pages, painting them the
+/// same way as strokes drawn here
+#[allow(clippy::too_many_arguments)]
+pub(super) fn apply_remote_strokes(
+    connection: Res<SyncConnection>,
+    drawable_query: Query<Entity, PageFilter>,
+    notebook_pages: Res<NotebookPages>,
+    mut content_query: Query<(
+        &mut DrawableContent,
+        Option<&MeshMaterial3d<DrawableMaterial>>,
+    )>,
+    // this is only mutable for change detection to work
+    mut drawable_materials: ResMut<Assets<DrawableMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    brush_tips: Res<BrushTips>,
+    time: Res<Time>,
+    mut remote_strokes: Local<HashMap<(PeerId, StrokeId), RemoteStroke>>,
+) {
+    for SyncMessage { peer, event } in connection.receive() {
+        match event {
+            StrokeEvent::Start {
+                page,
+                stroke,
+                mut paint_settings,
+            } => {
+                // clamping leaves NaN as it is
+                if !paint_settings.radius.is_finite() {
+                    warn!("Ignoring stroke with radius {}", paint_settings.radius);
+                    continue;
+                }
+                paint_settings.radius = paint_settings.radius.clamp(0.0, MAX_RADIUS);
+                let Some((_, drawable)) = shown_pages(&drawable_query, &notebook_pages)
+                    .into_iter()
+                    .find(|(shown, _)| *shown == page)
+                else {
+                    warn!("Peer drew on page {page}, which isn't shown here");
+                    continue;
+                };
+                let Ok((mut content, mesh_material)) = content_query.get_mut(drawable) else {
+                    continue;
+                };
+                let image = mesh_material
+                    .and_then(|material| drawable_materials.get_mut(&material.0))
+                    .and_then(|material| images.get(&material.draw_texture));
+                let raster =
+                    image.map(|image| StrokeRaster::new(image, &paint_settings, &brush_tips));
+                let id = content.next_stroke_id();
+                content.push_painted(Stroke {
+                    id,
+                    points: Vec::new(),
+                    paint_settings,
+                    started_at: time.elapsed_secs_f64(),
+                });
+                remote_strokes.insert(
+                    (peer, stroke),
+                    RemoteStroke {
+                        drawable,
+                        stroke: id,
+                        raster,
+                    },
+                );
+            }
+            StrokeEvent::Points {
+                stroke,
+                from,
+                mut points,
+            } => {
+                let Some(remote) = remote_strokes.get_mut(&(peer, stroke)) else {
+                    continue;
+                };
+                let Ok((mut content, mesh_material)) = content_query.get_mut(remote.drawable)
+                else {
+                    continue;
+                };
+                let plane_scale = content.plane_scale;
+                let Some(stroke) = content.stroke_mut(remote.stroke) else {
+                    continue;
+                };
+                clamp_points(&mut points, from.min(stroke.points.len()));
+                let image = mesh_material
+                    .and_then(|material| drawable_materials.get_mut(&material.0))
+                    .and_then(|material| images.get_mut(&material.draw_texture));
+                match (remote.raster.as_mut(), image) {
+                    (Some(raster), Some(image)) if from == stroke.points.len() => {
+                        let size = Vec2::new(image.width() as f32, image.height() as f32);
+                        for point in &points {
+                            raster.add_point(
+                                image,
+                                point.position * size,
+                                &stroke.paint_settings,
+                                plane_scale,
+                            );
+                        }
+                        stroke.points.extend(points);
+                    }
+                    _ => {
+                        stroke.points.truncate(from);
+                        stroke.points.extend(points);
+                        remote.raster = None;
+                        content.request_render();
+                    }
+                }
+            }
+            StrokeEvent::End { stroke } => {
+                let Some(remote) = remote_strokes.remove(&(peer, stroke)) else {
+                    continue;
+                };
+                // strokes drawn at the same time can paint over each other
+                // while they're drawn
+                if let Ok((mut content, _)) = content_query.get_mut(remote.drawable) {
+                    content.request_render();
+                }
+            }
+            StrokeEvent::Left => {
+                remote_strokes.retain(|(left, _), remote| {
+                    if *left != peer {
+                        return true;
+                    }
+                    if let Ok((mut content, _)) = content_query.get_mut(remote.drawable) {
+                        content.request_render();
+                    }
+                    false
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::Shutdown, thread, time::Duration};
+
+    use bevy::prelude::*;
+
+    use super::{
+        apply_remote_strokes, clamp_points, read_line, send_local_strokes, StrokeEvent,
+        SyncConnection, SyncMessage, MAX_LINE_LENGTH, MAX_STROKE_POINTS,
+    };
+    use crate::drawable::{
+        document::NotebookPages,
+        drawable_material::DrawableMaterial,
+        paint::{paint_input::PaintInput, stamp::BrushTips, PaintSettings},
+        stroke::{DrawableContent, StrokeId, StrokePoint},
+        DrawableObject, InSelectedNotebook,
+    };
+
+    /// waits for the host to accept `count` peers
+    fn wait_for_peers(host: &SyncConnection, count: usize) {
+        for _ in 0..500 {
+            if host.peer_count() >= count {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("the host never accepted {count} peers");
+    }
+
+    /// waits for messages from another thread
+    fn receive(connection: &SyncConnection, count: usize) -> Vec<SyncMessage> {
+        let mut messages = Vec::new();
+        for _ in 0..500 {
+            messages.extend(connection.receive());
+            if messages.len() >= count {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        messages
+    }
+
+    fn point(x: f32) -> StrokePoint {
+        StrokePoint {
+            position: Vec2::new(x, 0.5),
+            pressure: 1.0,
+            time: 0.0,
+        }
+    }
+
+    #[test]
+    fn the_host_forwards_events_between_peers() {
+        let host = SyncConnection::host("127.0.0.1:0").unwrap();
+        let first = SyncConnection::join(host.address).unwrap();
+        let second = SyncConnection::join(host.address).unwrap();
+        // the host has to accept both before anything is forwarded
+        wait_for_peers(&host, 2);
+
+        let event = StrokeEvent::End {
+            stroke: StrokeId(7),
+        };
+        first.send(event.clone());
+        for connection in [&host, &second] {
+            let messages = receive(connection, 1);
+            assert_eq!(
+                messages,
+                [SyncMessage {
+                    peer: first.peer,
+                    event: event.clone()
+                }]
+            );
+        }
+        assert!(first.receive().is_empty());
+    }
+
+    #[test]
+    fn peers_that_leave_are_announced() {
+        let host = SyncConnection::host("127.0.0.1:0").unwrap();
+        let first = SyncConnection::join(host.address).unwrap();
+        let second = SyncConnection::join(host.address).unwrap();
+        wait_for_peers(&host, 2);
+
+        let end = StrokeEvent::End {
+            stroke: StrokeId(7),
+        };
+        first.send(end.clone());
+        assert_eq!(receive(&second, 1).len(), 1);
+        for (_, stream) in first.peers.0.lock().unwrap().iter() {
+            stream.shutdown(Shutdown::Both).unwrap();
+        }
+
+        let left = SyncMessage {
+            peer: first.peer,
+            event: StrokeEvent::Left,
+        };
+        assert_eq!(
+            receive(&host, 2),
+            [
+                SyncMessage {
+                    peer: first.peer,
+                    event: end
+                },
+                left.clone()
+            ]
+        );
+        assert_eq!(receive(&second, 1), [left]);
+    }
+
+    /// an app with a page that applies the strokes of `connection`
+    fn sync_app(connection: SyncConnection) -> (App, Entity) {
+        let mut app = App::new();
+        app.insert_resource(connection)
+            .init_resource::<Assets<Image>>()
+            .init_resource::<Assets<DrawableMaterial>>()
+            .init_resource::<BrushTips>()
+            .init_resource::<PaintInput>()
+            .init_resource::<NotebookPages>()
+            .init_resource::<Time>()
+            .add_systems(Update, (send_local_strokes, apply_remote_strokes));
+        let page = app
+            .world_mut()
+            .spawn((
+                DrawableObject,
+                InSelectedNotebook,
+                DrawableContent::new(Vec2::ONE),
+            ))
+            .id();
+        (app, page)
+    }
+
+    #[test]
+    fn remote_strokes_are_added_to_the_page() {
+        let host = SyncConnection::host("127.0.0.1:0").unwrap();
+        let peer = SyncConnection::join(host.address).unwrap();
+        wait_for_peers(&host, 1);
+        let (mut app, page) = sync_app(host);
+        // the page shows page 2 of the notebook, page 0 isn't shown
+        app.world_mut().resource_mut::<NotebookPages>().first_shown = 2;
+
+        let nan_radius = PaintSettings {
+            radius: f32::NAN,
+            ..default()
+        };
+        for (page, stroke, paint_settings) in [
+            (0, StrokeId(1), PaintSettings::default()),
+            (2, StrokeId(2), nan_radius),
+            (2, StrokeId(3), PaintSettings::default()),
+        ] {
+            peer.send(StrokeEvent::Start {
+                page,
+                stroke,
+                paint_settings,
+            });
+        }
+        for (from, points) in [(0, vec![point(0.1), point(0.2)]), (1, vec![point(0.3)])] {
+            peer.send(StrokeEvent::Points {
+                stroke: StrokeId(3),
+                from,
+                points,
+            });
+        }
+        peer.send(StrokeEvent::End {
+            stroke: StrokeId(3),
+        });
+
+        for _ in 0..500 {
+            app.update();
+            let content = app.world().get::<DrawableContent>(page).unwrap();
+            let positions: Vec<_> = content
+                .strokes
+                .iter()
+                .flat_map(|stroke| stroke.points.iter().map(|p| p.position.x))
+                .collect();
+            if positions == [0.1, 0.3] {
+                assert_eq!(content.strokes.len(), 1);
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("the stroke never arrived");
+    }
+
+    #[test]
+    fn long_lines_are_errors() {
+        let mut line = String::new();
+        let mut reader = "first\nsecond".as_bytes();
+        assert!(read_line(&mut reader, &mut line).unwrap());
+        assert_eq!(line, "first");
+        assert!(read_line(&mut reader, &mut line).unwrap());
+        assert_eq!(line, "second");
+        assert!(!read_line(&mut reader, &mut line).unwrap());
+
+        let long = "x".repeat(MAX_LINE_LENGTH + 10);
+        assert!(read_line(&mut long.as_bytes(), &mut line).is_err());
+    }
+
+    #[test]
+    fn remote_points_are_clamped() {
+        let mut points = vec![point(-3.0), point(f32::NAN), point(0.5), point(7.0)];
+        clamp_points(&mut points, 0);
+        let xs: Vec<_> = points.iter().map(|point| point.position.x).collect();
+        assert_eq!(xs, [0.0, 0.5, 1.0]);
+
+        clamp_points(&mut points, MAX_STROKE_POINTS - 1);
+        assert_eq!(points.len(), 1);
+    }
+}
@@ -0,0 +1,356 @@
+//! Procedural page backgrounds, like ruled lines or a dot grid
+//!
+//! The template of a page is drawn by `drawable_shader.wgsl` below the ink,
+//! so it never ends up in the drawable image. [`PageTemplate::marks`] gives
+//! the same lines as geometry for exports.
+
+use bevy::{prelude::*, render::render_resource::ShaderType};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    clipboard::ctrl_pressed, drawable::DrawableCursor, stroke::DrawableContent, DrawableMaterial,
+};
+use crate::config::Settings;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemplateKind {
+    #[default]
+    Blank,
+    Lined,
+    Grid,
+    DotGrid,
+    Isometric,
+    MusicStaff,
+}
+
+impl TemplateKind {
+    pub const ALL: [TemplateKind; 6] = [
+        TemplateKind::Blank,
+        TemplateKind::Lined,
+        TemplateKind::Grid,
+        TemplateKind::DotGrid,
+        TemplateKind::Isometric,
+        TemplateKind::MusicStaff,
+    ];
+
+    /// the number the shader uses for this kind
+    fn shader_index(&self) -> u32 {
+        match self {
+            TemplateKind::Blank => 0,
+            TemplateKind::Lined => 1,
+            TemplateKind::Grid => 2,
+            TemplateKind::DotGrid => 3,
+            TemplateKind::Isometric => 4,
+            TemplateKind::MusicStaff => 5,
+        }
+    }
+}
+
+/// Number of line spacings from the top of one music staff to the next
+const STAFF_PERIOD: f32 = 9.0;
+/// Number of line spacings above the first music staff
+const STAFF_MARGIN: f32 = 2.0;
+
+/// The background of a page, lengths are in page widths
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PageTemplate {
+    pub kind: TemplateKind,
+    /// distance between lines
+    pub spacing: f32,
+    pub line_width: f32,
+    pub colour: Color,
+}
+
+impl Default for PageTemplate {
+    fn default() -> Self {
+        Self {
+            kind: TemplateKind::Blank,
+            spacing: 0.04,
+            line_width: 0.0015,
+            colour: Color::srgba(0.45, 0.6, 0.85, 0.6),
+        }
+    }
+}
+
+/// A line or dot of a template, in page widths from the top left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemplateMark {
+    Line(Vec2, Vec2),
+    /// the diameter is three times the line width
+    Dot(Vec2),
+}
+
+impl PageTemplate {
+    /// The lines and dots of the template, `aspect` is the height of the page
+    /// over its width. Keep this in sync with `drawable_shader.wgsl`.
+    pub fn marks(&self, aspect: f32) -> Vec<TemplateMark> {
+        let size = Vec2::new(1.0, aspect);
+        let spacing = self.spacing;
+        if spacing <= 0.0 {
+            return Vec::new();
+        }
+        let lines = |normal: Vec2| {
+            // the range of offsets of lines with this normal that cross the page
+            let corners = [Vec2::ZERO, Vec2::X, Vec2::new(0.0, aspect), size];
+            let offsets = corners.map(|corner| corner.dot(normal));
+            let low = offsets.iter().copied().fold(f32::INFINITY, f32::min);
+            let high = offsets.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            ((low / spacing).ceil() as i32..=(high / spacing).floor() as i32)
+                .filter_map(move |index| clip_line(normal, index as f32 * spacing, size))
+                .map(|(start, end)| TemplateMark::Line(start, end))
+        };
+
+        match self.kind {
+            TemplateKind::Blank => Vec::new(),
+            TemplateKind::Lined => lines(Vec2::Y).collect(),
+            TemplateKind::Grid => lines(Vec2::Y).chain(lines(Vec2::X)).collect(),
+            TemplateKind::DotGrid => {
+                let columns = (1.0 / spacing).floor() as i32;
+                let rows = (aspect / spacing).floor() as i32;
+                (0..=rows)
+                    .flat_map(|row| {
+                        (0..=columns).map(move |column| {
+                            TemplateMark::Dot(Vec2::new(column as f32, row as f32) * spacing)
+                        })
+                    })
+                    .collect()
+            }
+            TemplateKind::Isometric => {
+                let slope = f32::sqrt(3.0) / 2.0;
+                lines(Vec2::X)
+                    .chain(lines(Vec2::new(0.5, slope)))
+                    .chain(lines(Vec2::new(-0.5, slope)))
+                    .collect()
+            }
+            TemplateKind::MusicStaff => {
+                let mut marks = Vec::new();
+                let mut top = STAFF_MARGIN * spacing;
+                while top + 4.0 * spacing <= aspect {
+                    for line in 0..5 {
+                        let y = top + line as f32 * spacing;
+                        marks.push(TemplateMark::Line(Vec2::new(0.0, y), Vec2::new(1.0, y)));
+                    }
+                    top += STAFF_PERIOD * spacing;
+                }
+                marks
+            }
+        }
+    }
+
+    /// The closest grid intersection to `point`, both in page widths from the
+    /// top left. Lined pages only snap to the closest line, and templates
+    /// without a grid don't snap.
+    pub fn snap(&self, point: Vec2) -> Option<Vec2> {
+        let spacing = self.spacing;
+        if spacing <= 0.0 {
+            return None;
+        }
+        match self.kind {
+            TemplateKind::Blank | TemplateKind::MusicStaff => None,
+            TemplateKind::Lined => Some(Vec2::new(point.x, (point.y / spacing).round() * spacing)),
+            TemplateKind::Grid | TemplateKind::DotGrid => Some((point / spacing).round() * spacing),
+            TemplateKind::Isometric => {
+                // the lines cross at (column, row) * (spacing, spacing / sqrt(3))
+                // where the row and column are both even or both odd
+                let row_height = spacing / f32::sqrt(3.0);
+                let column = (point.x / spacing).floor();
+                [column, column + 1.0]
+                    .into_iter()
+                    .map(|column| {
+                        let parity = column.rem_euclid(2.0);
+                        let row = ((point.y / row_height - parity) / 2.0).round() * 2.0 + parity;
+                        Vec2::new(column * spacing, row * row_height)
+                    })
+                    .min_by(|a, b| {
+                        a.distance_squared(point)
+                            .total_cmp(&b.distance_squared(point))
+                    })
+            }
+        }
+    }
+
+    fn uniform(&self, plane_scale: Vec2) -> TemplateUniform {
+        let aspect = if plane_scale.x > 0.0 {
+            plane_scale.y / plane_scale.x
+        } else {
+            1.0
+        };
+        TemplateUniform {
+            colour: self.colour.to_linear().to_vec4(),
+            kind: self.kind.shader_index(),
+            spacing: self.spacing,
+            line_width: self.line_width,
+            aspect,
+        }
+    }
+}
+
+/// the part of the line `dot(point, normal) == offset` inside a rectangle from
+/// the origin to `size`
+fn clip_line(normal: Vec2, offset: f32, size: Vec2) -> Option<(Vec2, Vec2)> {
+    let origin = normal * offset;
+    let direction = normal.perp();
+    let (mut start, mut end) = (f32::NEG_INFINITY, f32::INFINITY);
+    for axis in 0..2 {
+        if direction[axis].abs() < 1e-6 {
+            if origin[axis] < 0.0 || origin[axis] > size[axis] {
+                return None;
+            }
+            continue;
+        }
+        let a = -origin[axis] / direction[axis];
+        let b = (size[axis] - origin[axis]) / direction[axis];
+        start = start.max(a.min(b));
+        end = end.min(a.max(b));
+    }
+    (start < end).then(|| (origin + direction * start, origin + direction * end))
+}
+
+/// [`PageTemplate`] as the drawable shader reads it
+#[derive(Debug, Clone, Copy, Default, ShaderType)]
+pub struct TemplateUniform {
+    colour: Vec4,
+    kind: u32,
+    spacing: f32,
+    line_width: f32,
+    /// height of the page over its width
+    aspect: f32,
+}
+
+/// Message for changing the template of a page
+#[derive(Debug, Message)]
+pub struct SetPageTemplate {
+    pub drawable: Entity,
+    pub template: PageTemplate,
+}
+
+pub(super) fn apply_page_templates(
+    mut reader: MessageReader<SetPageTemplate>,
+    mut content_query: Query<(&mut DrawableContent, &MeshMaterial3d<DrawableMaterial>)>,
+    mut drawable_materials: ResMut<Assets<DrawableMaterial>>,
+) {
+    for message in reader.read() {
+        let Ok((mut content, mesh_material)) = content_query.get_mut(message.drawable) else {
+            continue;
+        };
+        content.template = message.template;
+        if let Some(material) = drawable_materials.get_mut(&mesh_material.0) {
+            material.template = message.template.uniform(content.plane_scale);
+        }
+    }
+}
+
+/// G cycles the template of the page under the cursor, Ctrl+G the other way
+pub(super) fn cycle_page_template_system(
+    mut cursor: DrawableCursor,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    content_query: Query<&DrawableContent>,
+    mut writer: MessageWriter<SetPageTemplate>,
+) {
+    if !keyboard_input.just_pressed(settings.key_bindings.page_template) {
+        return;
+    }
+    let Some(hit) = cursor.hit() else {
+        return;
+    };
+    let Ok(content) = content_query.get(hit.drawable_object) else {
+        return;
+    };
+
+    let kinds = TemplateKind::ALL;
+    let current = kinds
+        .iter()
+        .position(|kind| *kind == content.template.kind)
+        .unwrap_or(0);
+    let next = if ctrl_pressed(&keyboard_input) {
+        current + kinds.len() - 1
+    } else {
+        current + 1
+    };
+    writer.write(SetPageTemplate {
+        drawable: hit.drawable_object,
+        template: PageTemplate {
+            kind: kinds[next % kinds.len()],
+            ..content.template
+        },
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::Vec2;
+
+    use super::{PageTemplate, TemplateKind, TemplateMark};
+
+    fn marks(kind: TemplateKind, aspect: f32) -> Vec<TemplateMark> {
+        PageTemplate {
+            kind,
+            spacing: 0.25,
+            ..Default::default()
+        }
+        .marks(aspect)
+    }
+
+    #[test]
+    fn lined_and_grid_pages() {
+        // lines at 0, 0.25, .. 1.5
+        assert_eq!(marks(TemplateKind::Lined, 1.5).len(), 7);
+        // and 5 vertical lines
+        assert_eq!(marks(TemplateKind::Grid, 1.5).len(), 12);
+        assert_eq!(marks(TemplateKind::DotGrid, 1.0).len(), 25);
+        assert!(marks(TemplateKind::Blank, 1.0).is_empty());
+    }
+
+    #[test]
+    fn marks_stay_on_the_page() {
+        let inside = |point: Vec2| {
+            (-1e-4..=1.0 + 1e-4).contains(&point.x) && (-1e-4..=1.5 + 1e-4).contains(&point.y)
+        };
+        for kind in TemplateKind::ALL {
+            for mark in marks(kind, 1.5) {
+                match mark {
+                    TemplateMark::Line(start, end) => {
+                        assert!(inside(start) && inside(end), "{kind:?} {mark:?}")
+                    }
+                    TemplateMark::Dot(centre) => assert!(inside(centre), "{kind:?} {mark:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn points_snap_to_intersections() {
+        let template = |kind| PageTemplate {
+            kind,
+            spacing: 0.25,
+            ..Default::default()
+        };
+        let point = Vec2::new(0.3, 0.45);
+        assert_eq!(
+            template(TemplateKind::Grid).snap(point),
+            Some(Vec2::new(0.25, 0.5))
+        );
+        assert_eq!(
+            template(TemplateKind::Lined).snap(point),
+            Some(Vec2::new(0.3, 0.5))
+        );
+        assert_eq!(template(TemplateKind::Blank).snap(point), None);
+
+        // every snapped point is on a line of all three directions
+        let isometric = template(TemplateKind::Isometric);
+        let snapped = isometric.snap(point).unwrap();
+        let slope = f32::sqrt(3.0) / 2.0;
+        for normal in [Vec2::X, Vec2::new(0.5, slope), Vec2::new(-0.5, slope)] {
+            let lines = snapped.dot(normal) / 0.25;
+            assert!((lines - lines.round()).abs() < 1e-4, "{normal}");
+        }
+        assert!(snapped.distance(point) < 0.25);
+    }
+
+    #[test]
+    fn music_staffs_have_five_lines() {
+        // staffs start at 0.5 and 2.75, the second doesn't fit
+        assert_eq!(marks(TemplateKind::MusicStaff, 2.0).len(), 5);
+    }
+}
@@ -0,0 +1,341 @@
+//! Text tool, types text onto pages
+//!
+//! Clicking on a page with the text tool places a caret there. Typed text
+//! stays editable in a [`TextBox`] until it's committed by clicking somewhere
+//! else, pressing Escape or switching tools, then it's rasterised into the
+//! page's raster layer. Text uses the brush colour and one of Bevy's loaded
+//! [`Font`] assets, Ctrl+F cycles the font and Ctrl+Plus/Minus changes the
+//! size.
+
+use ab_glyph::{point, Font as _, FontRef, GlyphId, PxScale, ScaleFont};
+use bevy::{
+    input::{keyboard::KeyboardInput, ButtonState},
+    prelude::*,
+};
+use image::RgbaImage;
+
+use super::{
+    clipboard::ctrl_pressed,
+    drawable::DrawableCursor,
+    drawable_material::DrawableMaterial,
+    paint::{brush::blend_over, PaintSettings},
+    stroke::DrawableContent,
+    tool::Tool,
+};
+
+/// Width of the caret in pixels
+const CARET_WIDTH: f32 = 2.0;
+/// How much Ctrl+Plus/Minus changes the text size
+const SIZE_STEP: f32 = 1.25;
+
+/// Font and size for new text
+#[derive(Resource, Debug, Clone)]
+pub struct TextSettings {
+    pub font: Handle<Font>,
+    /// height of the text in world units, like the brush radius
+    pub size: f32,
+}
+
+impl Default for TextSettings {
+    fn default() -> Self {
+        Self {
+            // Bevy's default font
+            font: Handle::default(),
+            size: 0.06,
+        }
+    }
+}
+
+/// Text that is still being typed
+#[derive(Debug, Clone)]
+pub struct TextBox {
+    pub drawable: Entity,
+    /// top left of the first line, in page units
+    pub position: Vec2,
+    pub text: String,
+    pub font: Handle<Font>,
+    pub size: f32,
+    pub colour: Color,
+    plane_scale: Vec2,
+}
+
+impl TextBox {
+    /// draws the text onto an rgba8 image, returns the caret position in pixels
+    fn draw(&self, data: &mut [u8], image_size: UVec2, fonts: &Assets<Font>) -> Option<Vec2> {
+        let font = fonts.get(&self.font)?;
+        let font = FontRef::try_from_slice(&font.data).ok()?;
+        let size_px = self.size / self.plane_scale.x * image_size.x as f32;
+        let origin = self.position * image_size.as_vec2();
+        let colour = self.colour.to_srgba().to_f32_array();
+        Some(draw_text(
+            data, image_size, &font, &self.text, origin, size_px, colour,
+        ))
+    }
+
+    fn draw_caret(&self, data: &mut [u8], image_size: UVec2, caret: Vec2) {
+        let size_px = self.size / self.plane_scale.x * image_size.x as f32;
+        let colour = self.colour.to_srgba().to_f32_array();
+        let low = caret.max(Vec2::ZERO).as_uvec2();
+        let high = (caret + Vec2::new(CARET_WIDTH, size_px))
+            .as_uvec2()
+            .min(image_size);
+        for y in low.y..high.y {
+            for x in low.x..high.x {
+                let index = ((y * image_size.x + x) * 4) as usize;
+                blend_over(&mut data[index..index + 4], colour, 1.0);
+            }
+        }
+    }
+
+    /// rasterises the text into the raster layer of the page
+    fn commit(&self, content: &mut DrawableContent, image_size: UVec2, fonts: &Assets<Font>) {
+        content.request_render();
+        if self.text.trim().is_empty() {
+            return;
+        }
+        let raster = content
+            .raster
+            .get_or_insert_with(|| RgbaImage::new(image_size.x, image_size.y));
+        let size = UVec2::new(raster.width(), raster.height());
+        if self.draw(raster, size, fonts).is_none() {
+            warn!("Couldn't draw text, the font isn't loaded");
+        }
+    }
+}
+
+/// Lays out `text` from `origin`, the top left in pixels, and draws it onto
+/// `data`, an rgba8 image. Returns where the caret goes after the text.
+pub fn draw_text(
+    data: &mut [u8],
+    image_size: UVec2,
+    font: &FontRef,
+    text: &str,
+    origin: Vec2,
+    size_px: f32,
+    colour: [f32; 4],
+) -> Vec2 {
+    let scale = PxScale::from(size_px);
+    let scaled = font.as_scaled(scale);
+    let line_height = scaled.height() + scaled.line_gap();
+
+    let mut caret = origin;
+    let mut previous: Option<GlyphId> = None;
+    for character in text.chars() {
+        if character == '\n' {
+            caret = Vec2::new(origin.x, caret.y + line_height);
+            previous = None;
+            continue;
+        }
+        let id = scaled.glyph_id(character);
+        if let Some(previous) = previous {
+            caret.x += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(caret.x, caret.y + scaled.ascent()));
+        caret.x += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|x, y, coverage| {
+            let x = bounds.min.x as i64 + x as i64;
+            let y = bounds.min.y as i64 + y as i64;
+            if x < 0 || y < 0 || x >= image_size.x as i64 || y >= image_size.y as i64 {
+                return;
+            }
+            let index = ((y as u32 * image_size.x + x as u32) * 4) as usize;
+            blend_over(&mut data[index..index + 4], colour, coverage);
+        });
+    }
+    caret
+}
+
+/// State of the text tool
+#[derive(Resource, Debug, Default)]
+pub struct TextEditor {
+    pub active: Option<TextBox>,
+    /// a text field of the GUI is being typed in
+    pub field_focused: bool,
+}
+
+impl TextEditor {
+    /// draws the text being typed and its caret if it's on `drawable`
+    pub(super) fn render_preview(&self, drawable: Entity, image: &mut Image, fonts: &Assets<Font>) {
+        let Some(text_box) = self.active.as_ref().filter(|t| t.drawable == drawable) else {
+            return;
+        };
+        let size = UVec2::new(image.width(), image.height());
+        let Some(data) = image.data.as_mut() else {
+            return;
+        };
+        if let Some(caret) = text_box.draw(data, size, fonts) {
+            text_box.draw_caret(data, size, caret);
+        }
+    }
+}
+
+/// run condition for keyboard shortcuts, which shouldn't fire while typing
+pub fn not_typing(text_editor: Res<TextEditor>) -> bool {
+    text_editor.active.is_none() && !text_editor.field_focused
+}
+
+/// size of the image shown on a drawable
+fn image_size(
+    material: &MeshMaterial3d<DrawableMaterial>,
+    materials: &Assets<DrawableMaterial>,
+    images: &Assets<Image>,
+) -> Option<UVec2> {
+    let material = materials.get(&material.0)?;
+    let image = images.get(&material.draw_texture)?;
+    Some(UVec2::new(image.width(), image.height()))
+}
+
+/// Places text boxes and types into them, see the [module docs](self)
+#[allow(clippy::too_many_arguments)]
+pub(super) fn text_tool_system(
+    mut cursor: DrawableCursor,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut key_reader: MessageReader<KeyboardInput>,
+    tool: Res<Tool>,
+    mut text_editor: ResMut<TextEditor>,
+    mut text_settings: ResMut<TextSettings>,
+    paint_settings: Res<PaintSettings>,
+    mut content_query: Query<(&mut DrawableContent, &MeshMaterial3d<DrawableMaterial>)>,
+    drawable_materials: Res<Assets<DrawableMaterial>>,
+    images: Res<Assets<Image>>,
+    // mutable to get handles when changing the font
+    mut fonts: ResMut<Assets<Font>>,
+) {
+    let mut commit = |text_box: TextBox, fonts: &Assets<Font>| {
+        if let Ok((mut content, material)) = content_query.get_mut(text_box.drawable) {
+            if let Some(size) = image_size(material, &drawable_materials, &images) {
+                text_box.commit(&mut content, size, fonts);
+            }
+        }
+    };
+
+    if *tool != Tool::Text {
+        if let Some(text_box) = text_editor.active.take() {
+            commit(text_box, &fonts);
+        }
+        key_reader.clear();
+        return;
+    }
+
+    let placed = buttons.just_pressed(MouseButton::Left);
+    if placed {
+        if let Some(text_box) = text_editor.active.take() {
+            commit(text_box, &fonts);
+        }
+        if let Some(hit) = cursor.hit() {
+            text_editor.active = Some(TextBox {
+                drawable: hit.drawable_object,
+                position: hit.position,
+                text: String::new(),
+                font: text_settings.font.clone(),
+                size: text_settings.size,
+                colour: paint_settings.colour,
+                plane_scale: hit.plane_scale,
+            });
+        }
+    }
+
+    let Some(text_box) = text_editor.active.as_mut() else {
+        key_reader.clear();
+        return;
+    };
+    let mut changed = placed;
+    let ctrl = ctrl_pressed(&keyboard_input);
+    let mut committed = false;
+    for input in key_reader.read() {
+        if input.state != ButtonState::Pressed {
+            continue;
+        }
+        changed = true;
+        match input.key_code {
+            KeyCode::KeyF if ctrl => {
+                let mut font_ids: Vec<_> = fonts.ids().collect();
+                font_ids.sort();
+                let current = font_ids.iter().position(|id| *id == text_box.font.id());
+                if let Some(id) = current
+                    .map(|index| font_ids[(index + 1) % font_ids.len()])
+                    .or(font_ids.first().copied())
+                {
+                    if let Some(handle) = fonts.get_strong_handle(id) {
+                        text_box.font = handle.clone();
+                        text_settings.font = handle;
+                    }
+                }
+            }
+            KeyCode::Equal | KeyCode::NumpadAdd if ctrl => {
+                text_box.size *= SIZE_STEP;
+                text_settings.size = text_box.size;
+            }
+            KeyCode::Minus | KeyCode::NumpadSubtract if ctrl => {
+                text_box.size /= SIZE_STEP;
+                text_settings.size = text_box.size;
+            }
+            _ if ctrl => {}
+            KeyCode::Escape => committed = true,
+            KeyCode::Backspace => {
+                text_box.text.pop();
+            }
+            KeyCode::Enter | KeyCode::NumpadEnter => text_box.text.push('\n'),
+            _ => {
+                if let Some(text) = &input.text {
+                    text_box
+                        .text
+                        .extend(text.chars().filter(|character| !character.is_control()));
+                }
+            }
+        }
+    }
+
+    let drawable = text_box.drawable;
+    if committed {
+        if let Some(text_box) = text_editor.active.take() {
+            commit(text_box, &fonts);
+        }
+    } else if changed {
+        if let Ok((mut content, _)) = content_query.get_mut(drawable) {
+            content.request_render();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ab_glyph::FontRef;
+    use bevy::{prelude::*, text::DEFAULT_FONT_DATA};
+
+    use super::draw_text;
+
+    #[test]
+    fn draws_lines_of_text() {
+        let font = FontRef::try_from_slice(DEFAULT_FONT_DATA).unwrap();
+        let size = UVec2::new(64, 64);
+        let mut data = vec![0; 64 * 64 * 4];
+
+        let caret = draw_text(
+            &mut data,
+            size,
+            &font,
+            "Hi\nA",
+            Vec2::new(2.0, 2.0),
+            16.0,
+            [0.0, 0.0, 0.0, 1.0],
+        );
+
+        let alpha = |x: usize, y: usize| data[(y * 64 + x) * 4 + 3];
+        let painted = |rows: std::ops::Range<usize>| {
+            rows.flat_map(|y| (0..64).map(move |x| (x, y)))
+                .any(|(x, y)| alpha(x, y) > 0)
+        };
+        assert!(painted(0..18), "first line is drawn");
+        assert!(painted(20..36), "second line is drawn");
+        assert!(caret.y > 16.0, "caret is on the second line");
+        assert!(caret.x > 2.0);
+    }
+}
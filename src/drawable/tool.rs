@@ -0,0 +1,42 @@
+//! The tool used with the left mouse button
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::selection::SelectionShape;
+use crate::config::Settings;
+
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tool {
+    /// paints strokes with the [`PaintSettings`](super::paint::PaintSettings)
+    #[default]
+    Pen,
+    /// selects part of a page to move, scale or rotate it
+    Select(SelectionShape),
+    /// types text onto a page
+    Text,
+}
+
+/// S switches between the pen, rectangle selection and lasso selection, T
+/// switches between the pen and the text tool, unless they're rebound
+pub(super) fn select_tool_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut tool: ResMut<Tool>,
+) {
+    let keys = &settings.key_bindings;
+    if keyboard_input.just_pressed(keys.select_tool) {
+        *tool = match *tool {
+            Tool::Pen | Tool::Text => Tool::Select(SelectionShape::Rectangle),
+            Tool::Select(SelectionShape::Rectangle) => Tool::Select(SelectionShape::Lasso),
+            Tool::Select(SelectionShape::Lasso) => Tool::Pen,
+        };
+    }
+    if keyboard_input.just_pressed(keys.text_tool) {
+        *tool = if *tool == Tool::Text {
+            Tool::Pen
+        } else {
+            Tool::Text
+        };
+    }
+}
@@ -4,16 +4,13 @@
 
 use bevy::{input_focus::InputFocus, prelude::*};
 
-use crate::gui::ButtonMenuComponent;
-
-const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
-const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
-const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.25, 0.3);
+use crate::{config::Settings, gui::ButtonMenuComponent};
 
 const BORDER_BUTTON: Color = Color::BLACK;
 
 pub(super) fn button_system(
     mut input_focus: ResMut<InputFocus>,
+    settings: Res<Settings>,
     mut interaction_query: Query<
         (
             Entity,
@@ -25,25 +22,26 @@ pub(super) fn button_system(
         Changed<Interaction>,
     >,
 ) {
+    let [normal, hovered, pressed] = settings.theme.button_colours();
     for (entity, interaction, mut bg_color, mut border_color, mut button) in &mut interaction_query
     {
         match *interaction {
             Interaction::Pressed => {
                 input_focus.set(entity);
-                *bg_color = PRESSED_BUTTON.into();
+                *bg_color = pressed.into();
                 *border_color = BorderColor::all(Color::srgb(0.05, 0.0, 0.2));
 
                 button.set_changed();
             }
             Interaction::Hovered => {
                 input_focus.set(entity);
-                *bg_color = HOVERED_BUTTON.into();
+                *bg_color = hovered.into();
                 *border_color = BorderColor::all(Color::srgb(0.25, 0.2, 0.4));
                 button.set_changed();
             }
             Interaction::None => {
                 input_focus.clear();
-                *bg_color = NORMAL_BUTTON.into();
+                *bg_color = normal.into();
                 *border_color = BorderColor::all(BORDER_BUTTON);
             }
         }
@@ -51,11 +49,29 @@ pub(super) fn button_system(
 }
 
 pub(super) fn create_button(menu: impl ButtonMenuComponent) -> impl Bundle {
+    let label = menu.to_str().to_string();
+    create_labelled_button(menu, label)
+}
+
+/// a button like [`create_button`] for components whose label isn't fixed,
+/// the label is the text of its first child
+pub(super) fn create_labelled_button(menu: impl Component, label: String) -> impl Bundle {
+    create_sized_button(menu, label, px(150), px(65))
+}
+
+/// a [labelled button](create_labelled_button) of another size, for screens
+/// with a lot of them
+pub(super) fn create_sized_button(
+    menu: impl Component,
+    label: String,
+    width: Val,
+    height: Val,
+) -> impl Bundle {
     (
         Button,
         Node {
-            width: px(150),
-            height: px(65),
+            width,
+            height,
             border: UiRect::all(px(5)),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
@@ -67,10 +83,24 @@ pub(super) fn create_button(menu: impl ButtonMenuComponent) -> impl Bundle {
         BackgroundColor(Color::BLACK),
         menu,
         children![(
-            Text::new(menu.to_str()),
+            Text::new(label),
             TextFont::default(),
             TextColor(Color::srgb(0.9, 0.9, 0.9)),
             TextShadow::default(),
         )],
     )
 }
+
+/// changes the label of a button made with [`create_labelled_button`]
+pub(super) fn set_button_label(
+    children: &Children,
+    text_query: &mut Query<&mut Text>,
+    label: impl Into<String>,
+) {
+    if let Some(mut text) = children
+        .first()
+        .and_then(|child| text_query.get_mut(*child).ok())
+    {
+        text.0 = label.into();
+    }
+}
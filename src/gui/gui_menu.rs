@@ -3,8 +3,14 @@
 use bevy::prelude::*;
 
 use crate::{
-    drawable::{ClearDrawableImage, SaveDrawableImage},
-    gui::{create_button, ButtonMenuComponent, GuiMenuData},
+    drawable::{
+        document::{OpenNotebook, SaveNotebook, NOTEBOOK_DOCUMENT_PATH},
+        export::{ExportFormat, ExportNotebook},
+        recording::RecordingControl,
+        ClearDrawableImage, SaveDrawableImage,
+    },
+    gui::{button::set_button_label, create_button, ButtonMenuComponent, GuiMenuData},
+    notebook::animation::{NotebookInput, NotebookState},
     AppState,
 };
 
@@ -23,8 +29,45 @@ pub(super) fn gui_menu_system(
 ) {
     for interaction in interaction_query {
         if *interaction == Interaction::Pressed {
-            // open menu
-            next_state.set(AppState::Menu);
+            // pause, the notebook stays behind the pause menu
+            next_state.set(AppState::Paused);
+        }
+    }
+}
+
+/// Opens or closes the notebook, labelled with what it will do
+#[derive(Component, Clone, Copy)]
+pub(super) struct NotebookCoverButton;
+
+pub(super) fn cover_button_label(state: &NotebookState) -> &'static str {
+    match state {
+        NotebookState::Open | NotebookState::Opening => "Close book",
+        NotebookState::Closed | NotebookState::Closing => "Open book",
+    }
+}
+
+pub(super) fn notebook_cover_button_system(
+    interaction_query: Query<&Interaction, (With<NotebookCoverButton>, Changed<Interaction>)>,
+    label_query: Query<&Children, With<NotebookCoverButton>>,
+    mut text_query: Query<&mut Text>,
+    notebook_state: Res<State<NotebookState>>,
+    mut input_writer: MessageWriter<NotebookInput>,
+) {
+    for interaction in interaction_query {
+        if *interaction == Interaction::Pressed {
+            input_writer.write(match notebook_state.get() {
+                NotebookState::Open | NotebookState::Opening => NotebookInput::Close,
+                NotebookState::Closed | NotebookState::Closing => NotebookInput::Open,
+            });
+        }
+    }
+    if notebook_state.is_changed() {
+        for children in &label_query {
+            set_button_label(
+                children,
+                &mut text_query,
+                cover_button_label(notebook_state.get()),
+            );
         }
     }
 }
@@ -107,6 +150,106 @@ pub(super) fn clear_image_button_system(
     }
 }
 
+#[derive(Component, Clone, Copy)]
+pub(super) struct ExportButton(ExportFormat);
+
+impl ButtonMenuComponent for ExportButton {
+    fn to_str(&self) -> &str {
+        match self.0 {
+            ExportFormat::Svg => "Export SVG",
+            ExportFormat::Pdf => "Export PDF",
+        }
+    }
+}
+
+pub(super) fn export_button_system(
+    interaction_query: Query<(&Interaction, &ExportButton), Changed<Interaction>>,
+    mut export_writer: MessageWriter<ExportNotebook>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction == Interaction::Pressed {
+            export_writer.write(ExportNotebook { format: button.0 });
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+pub(super) enum DocumentButton {
+    Save,
+    Open,
+}
+
+impl ButtonMenuComponent for DocumentButton {
+    fn to_str(&self) -> &str {
+        match self {
+            DocumentButton::Save => "Save Notebook",
+            DocumentButton::Open => "Open Notebook",
+        }
+    }
+}
+
+pub(super) fn document_button_system(
+    interaction_query: Query<(&Interaction, &DocumentButton), Changed<Interaction>>,
+    mut save_writer: MessageWriter<SaveNotebook>,
+    mut open_writer: MessageWriter<OpenNotebook>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction == Interaction::Pressed {
+            let path = NOTEBOOK_DOCUMENT_PATH.into();
+            match button {
+                DocumentButton::Save => {
+                    save_writer.write(SaveNotebook { path });
+                }
+                DocumentButton::Open => {
+                    open_writer.write(OpenNotebook { path });
+                }
+            }
+        }
+    }
+}
+
+/// Speed of the fast replay
+const FAST_REPLAY_SPEED: f64 = 4.0;
+
+#[derive(Component, Clone, Copy)]
+pub(super) enum RecordingButton {
+    Record,
+    Replay,
+    FastReplay,
+    ExportFrames,
+}
+
+impl ButtonMenuComponent for RecordingButton {
+    fn to_str(&self) -> &str {
+        match self {
+            RecordingButton::Record => "Record / Stop",
+            RecordingButton::Replay => "Replay",
+            RecordingButton::FastReplay => "Replay 4x",
+            RecordingButton::ExportFrames => "Export Frames",
+        }
+    }
+}
+
+pub(super) fn recording_button_system(
+    interaction_query: Query<(&Interaction, &RecordingButton), Changed<Interaction>>,
+    mut recording_writer: MessageWriter<RecordingControl>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction == Interaction::Pressed {
+            recording_writer.write(match button {
+                RecordingButton::Record => RecordingControl::ToggleRecording,
+                RecordingButton::Replay => RecordingControl::Replay { speed: 1.0 },
+                RecordingButton::FastReplay => RecordingControl::Replay {
+                    speed: FAST_REPLAY_SPEED,
+                },
+                RecordingButton::ExportFrames => RecordingControl::ExportFrames {
+                    speed: FAST_REPLAY_SPEED,
+                },
+            });
+        }
+    }
+}
+
 #[derive(Resource)]
 pub(super) struct DebugMenuData {
     debug_menu_entity: Entity,
@@ -122,7 +265,15 @@ pub(super) fn setup_debug_menu(mut commands: Commands, gui_menu_data: Res<GuiMen
             },
             children![
                 create_button(SaveImageButton),
-                create_button(ClearImageButton)
+                create_button(ClearImageButton),
+                create_button(DocumentButton::Save),
+                create_button(DocumentButton::Open),
+                create_button(ExportButton(ExportFormat::Svg)),
+                create_button(ExportButton(ExportFormat::Pdf)),
+                create_button(RecordingButton::Record),
+                create_button(RecordingButton::Replay),
+                create_button(RecordingButton::FastReplay),
+                create_button(RecordingButton::ExportFrames)
             ],
         ))
         .id();
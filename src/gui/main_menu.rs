@@ -1,10 +1,40 @@
+//! The start screen, with screens for creating and opening notebooks and the
+//! settings
+
+use std::path::{Path, PathBuf};
+
 use bevy::prelude::*;
 
 use crate::{
-    gui::{create_button, ButtonMenuComponent},
+    config::{RecentNotebooks, Settings},
+    drawable::{
+        autosave::RecoveredSession,
+        document::{notebook_files, NewNotebook, StartupNotebook, NOTEBOOKS_FOLDER},
+        guide::Snapping,
+        template::TemplateKind,
+    },
+    gui::{
+        button::{create_labelled_button, set_button_label},
+        create_button,
+        settings::spawn_settings,
+        ButtonMenuComponent,
+    },
     AppState,
 };
 
+const PAGE_COUNTS: [usize; 6] = [1, 2, 4, 8, 16, 32];
+pub(super) const RESOLUTIONS: [usize; 3] = [1024, 2048, 4096];
+
+#[derive(SubStates, Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+#[source(AppState = AppState::Menu)]
+pub(super) enum MenuScreen {
+    #[default]
+    Main,
+    New,
+    Open,
+    Settings,
+}
+
 pub(super) fn start_button_menu_system(
     interaction_query: Query<&Interaction, (With<StartButton>, Changed<Interaction>)>,
     mut next_state: ResMut<NextState<AppState>>,
@@ -26,27 +56,355 @@ impl ButtonMenuComponent for StartButton {
     }
 }
 
-#[derive(Resource)]
-pub(super) struct MainMenuData {
-    menu_entity: Entity,
+/// Goes to another screen of the menu
+#[derive(Component, Clone, Copy)]
+pub(super) struct ScreenButton(MenuScreen);
+
+impl ButtonMenuComponent for ScreenButton {
+    fn to_str(&self) -> &str {
+        match self.0 {
+            MenuScreen::Main => "Back",
+            MenuScreen::New => "New",
+            MenuScreen::Open => "Open",
+            MenuScreen::Settings => "Settings",
+        }
+    }
+}
+
+pub(super) fn screen_button_system(
+    interaction_query: Query<(&Interaction, &ScreenButton), Changed<Interaction>>,
+    mut next_screen: ResMut<NextState<MenuScreen>>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction == Interaction::Pressed {
+            next_screen.set(button.0);
+        }
+    }
+}
+
+/// Opens a notebook document
+#[derive(Component, Clone)]
+pub(super) struct NotebookButton(PathBuf);
+
+fn notebook_button(path: &Path) -> impl Bundle {
+    let name = path.file_stem().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    create_labelled_button(NotebookButton(path.to_path_buf()), name)
+}
+
+pub(super) fn notebook_button_system(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &NotebookButton), Changed<Interaction>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction == Interaction::Pressed {
+            commands.insert_resource(StartupNotebook(button.0.clone()));
+            next_state.set(AppState::Playing);
+        }
+    }
+}
+
+/// Restores a notebook of the session that closed with unsaved changes, or
+/// discards the ones left
+#[derive(Component, Clone, Copy)]
+pub(super) enum RecoveryButton {
+    Restore,
+    Discard,
+}
+
+impl ButtonMenuComponent for RecoveryButton {
+    fn to_str(&self) -> &str {
+        match self {
+            RecoveryButton::Restore => "Restore",
+            RecoveryButton::Discard => "Discard",
+        }
+    }
+}
+
+/// The question about the unsaved session and its buttons
+#[derive(Component)]
+pub(super) struct RecoveryPrompt;
+
+pub(super) fn recovery_button_system(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &RecoveryButton), Changed<Interaction>>,
+    prompt_query: Query<Entity, With<RecoveryPrompt>>,
+    recovered: Option<ResMut<RecoveredSession>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let Some(mut recovered) = recovered else {
+        return;
+    };
+    for (interaction, button) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            RecoveryButton::Restore => {
+                // the other notebooks are offered when the menu is back
+                if !recovered.0.is_empty() {
+                    let path = recovered.0.remove(0);
+                    commands.insert_resource(StartupNotebook(path));
+                    next_state.set(AppState::Playing);
+                }
+            }
+            RecoveryButton::Discard => {
+                recovered.discard();
+                recovered.0.clear();
+            }
+        }
+        if recovered.0.is_empty() {
+            commands.remove_resource::<RecoveredSession>();
+        }
+        for prompt in &prompt_query {
+            commands.entity(prompt).despawn();
+        }
+        return;
+    }
+}
+
+/// the full screen node the buttons of a screen go in
+fn screen_root(screen: MenuScreen) -> impl Bundle {
+    (
+        DespawnOnExit(screen),
+        Node {
+            width: percent(100),
+            height: percent(100),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+    )
+}
+
+pub(super) fn column() -> Node {
+    Node {
+        flex_direction: FlexDirection::Column,
+        align_items: AlignItems::Center,
+        margin: UiRect::horizontal(px(20)),
+        ..default()
+    }
+}
+
+pub(super) fn heading(text: impl Into<String>) -> impl Bundle {
+    (
+        Text::new(text),
+        Node {
+            margin: UiRect::bottom(px(10)),
+            ..default()
+        },
+    )
 }
 
-pub(super) fn setup_main_menu(mut commands: Commands) {
-    let menu_entity = commands
+pub(super) fn setup_main_menu(
+    mut commands: Commands,
+    recovered: Option<Res<RecoveredSession>>,
+    recent: Res<RecentNotebooks>,
+) {
+    let root = commands.spawn(screen_root(MenuScreen::Main)).id();
+    let row = commands
         .spawn((
             Node {
-                width: percent(100),
-                height: percent(100),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexStart,
                 ..default()
             },
-            children![create_button(StartButton)],
+            ChildOf(root),
+            children![(
+                column(),
+                children![
+                    create_button(StartButton),
+                    create_button(ScreenButton(MenuScreen::New)),
+                    create_button(ScreenButton(MenuScreen::Open)),
+                    create_button(ScreenButton(MenuScreen::Settings)),
+                ]
+            )],
         ))
         .id();
-    commands.insert_resource(MainMenuData { menu_entity });
+
+    let recent: Vec<_> = recent.paths.iter().filter(|path| path.exists()).collect();
+    if !recent.is_empty() {
+        commands
+            .spawn((column(), ChildOf(row)))
+            .with_children(|column| {
+                column.spawn(heading("Recent"));
+                for path in recent {
+                    column.spawn(notebook_button(path));
+                }
+            });
+    }
+
+    if let Some(recovered) = recovered {
+        let heading_text = match recovered.0.len() {
+            1 => "The last session closed with unsaved changes".to_string(),
+            count => format!("The last session closed with unsaved changes in {count} notebooks"),
+        };
+        commands.spawn((
+            RecoveryPrompt,
+            ChildOf(root),
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                margin: UiRect::top(px(20)),
+                ..default()
+            },
+            children![
+                heading(heading_text),
+                create_button(RecoveryButton::Restore),
+                create_button(RecoveryButton::Discard),
+            ],
+        ));
+    }
+}
+
+/// The choices for a new notebook
+#[derive(Resource, Debug)]
+pub(super) struct NewNotebookSettings {
+    pages: usize,
+    resolution: usize,
+    template: TemplateKind,
+}
+
+impl Default for NewNotebookSettings {
+    fn default() -> Self {
+        Self {
+            pages: 4,
+            resolution: 1024,
+            template: TemplateKind::Lined,
+        }
+    }
+}
+
+/// Changes one of the [`NewNotebookSettings`] to the next choice
+#[derive(Component, Clone, Copy)]
+pub(super) enum NewNotebookOption {
+    Pages,
+    Resolution,
+    Template,
+}
+
+impl NewNotebookOption {
+    fn label(&self, settings: &NewNotebookSettings) -> String {
+        match self {
+            NewNotebookOption::Pages => format!("Pages: {}", settings.pages),
+            NewNotebookOption::Resolution => format!("Size: {} px", settings.resolution),
+            NewNotebookOption::Template => format!("{:?}", settings.template),
+        }
+    }
+}
+
+/// the choice after `current` in `choices`
+fn next_choice<T: Copy + PartialEq>(choices: &[T], current: T) -> T {
+    let index = choices.iter().position(|choice| *choice == current);
+    choices[index.map_or(0, |index| (index + 1) % choices.len())]
+}
+
+#[derive(Component, Clone, Copy)]
+pub(super) struct CreateButton;
+
+impl ButtonMenuComponent for CreateButton {
+    fn to_str(&self) -> &str {
+        "Create"
+    }
+}
+
+pub(super) fn setup_new_notebook_menu(mut commands: Commands, settings: Res<NewNotebookSettings>) {
+    let options = [
+        NewNotebookOption::Pages,
+        NewNotebookOption::Resolution,
+        NewNotebookOption::Template,
+    ];
+    commands
+        .spawn(screen_root(MenuScreen::New))
+        .with_children(|root| {
+            root.spawn(heading("New notebook"));
+            for option in options {
+                root.spawn(create_labelled_button(option, option.label(&settings)));
+            }
+            root.spawn(create_button(CreateButton));
+            root.spawn(create_button(ScreenButton(MenuScreen::Main)));
+        });
+}
+
+pub(super) fn new_notebook_button_system(
+    option_query: Query<(&Interaction, &NewNotebookOption, &Children), Changed<Interaction>>,
+    create_query: Query<&Interaction, (With<CreateButton>, Changed<Interaction>)>,
+    mut text_query: Query<&mut Text>,
+    mut settings: ResMut<NewNotebookSettings>,
+    mut new_writer: MessageWriter<NewNotebook>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for (interaction, option, children) in option_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match option {
+            NewNotebookOption::Pages => {
+                settings.pages = next_choice(&PAGE_COUNTS, settings.pages);
+            }
+            NewNotebookOption::Resolution => {
+                settings.resolution = next_choice(&RESOLUTIONS, settings.resolution);
+            }
+            NewNotebookOption::Template => {
+                settings.template = next_choice(&TemplateKind::ALL, settings.template);
+            }
+        }
+        set_button_label(children, &mut text_query, option.label(&settings));
+    }
+
+    for interaction in create_query {
+        if *interaction == Interaction::Pressed {
+            new_writer.write(NewNotebook {
+                pages: settings.pages,
+                template: settings.template,
+                resolution: settings.resolution,
+            });
+            next_state.set(AppState::Playing);
+        }
+    }
 }
 
-pub(super) fn close_main_menu(mut commands: Commands, main_menu_data: Res<MainMenuData>) {
-    commands.entity(main_menu_data.menu_entity).despawn();
+pub(super) fn setup_open_menu(mut commands: Commands) {
+    let files = notebook_files();
+    commands
+        .spawn(screen_root(MenuScreen::Open))
+        .with_children(|root| {
+            if files.is_empty() {
+                root.spawn(heading(format!(
+                    "There are no notebooks in {NOTEBOOKS_FOLDER}"
+                )));
+            } else {
+                root.spawn(heading(format!("Notebooks in {NOTEBOOKS_FOLDER}")));
+            }
+            for path in &files {
+                root.spawn(notebook_button(path));
+            }
+            root.spawn(create_button(ScreenButton(MenuScreen::Main)));
+        });
+}
+
+pub(super) fn setup_settings_menu(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    snapping: Res<Snapping>,
+) {
+    let root = commands.spawn(screen_root(MenuScreen::Settings)).id();
+    spawn_settings(&mut commands, root, &settings, &snapping);
+    commands.spawn((create_button(ScreenButton(MenuScreen::Main)), ChildOf(root)));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{next_choice, PAGE_COUNTS};
+
+    #[test]
+    fn choices_wrap_around() {
+        assert_eq!(next_choice(&PAGE_COUNTS, 4), 8);
+        assert_eq!(next_choice(&PAGE_COUNTS, 32), 1);
+        // a value that isn't a choice starts from the first one
+        assert_eq!(next_choice(&PAGE_COUNTS, 3), 1);
+    }
 }
@@ -2,19 +2,44 @@ use bevy::{input_focus::InputFocus, prelude::*};
 
 use crate::{
     gui::{
-        button::{button_system, create_button},
+        button::{button_system, create_button, create_labelled_button},
         gui_menu::{
-            clear_image_button_system, close_debug_menu, debug_menu_system, gui_menu_system,
-            save_image_button_system, setup_debug_menu, DebugMenu, GuiMenu, GuiMenuState,
+            clear_image_button_system, close_debug_menu, cover_button_label, debug_menu_system,
+            document_button_system, export_button_system, gui_menu_system,
+            notebook_cover_button_system, recording_button_system, save_image_button_system,
+            setup_debug_menu, DebugMenu, GuiMenu, GuiMenuState, NotebookCoverButton,
         },
-        main_menu::{close_main_menu, setup_main_menu, start_button_menu_system},
+        main_menu::{
+            new_notebook_button_system, notebook_button_system, recovery_button_system,
+            screen_button_system, setup_main_menu, setup_new_notebook_menu, setup_open_menu,
+            setup_settings_menu, start_button_menu_system, MenuScreen, NewNotebookSettings,
+        },
+        page_panel::{
+            bookmark_list_system, page_panel_button_system, page_panel_system, setup_page_panel,
+        },
+        page_strip::{
+            page_strip_input_system, page_strip_scroll_system, page_strip_system, setup_page_strip,
+            update_page_thumbnails, PageThumbnails,
+        },
+        pause_menu::{
+            pause_button_system, pause_escape_system, setup_confirm_quit, setup_pause_menu,
+            setup_pause_settings, PauseScreen,
+        },
+        settings::{key_binding_system, settings_widget_system, KeyBindingButton},
+        widgets::{dropdown_system, slider_system, text_field_system, toggle_system},
     },
+    notebook::animation::NotebookState,
     AppState,
 };
 
 mod button;
 mod gui_menu;
 mod main_menu;
+mod page_panel;
+mod page_strip;
+mod pause_menu;
+mod settings;
+mod widgets;
 
 pub struct GuiPlugin;
 
@@ -23,13 +48,70 @@ impl Plugin for GuiPlugin {
         // general stuff
         app.init_resource::<InputFocus>();
         app.add_systems(Update, button_system);
+        app.add_systems(
+            Update,
+            (
+                toggle_system,
+                slider_system,
+                dropdown_system,
+                text_field_system,
+            )
+                .chain(),
+        );
+        // the settings screens of the main menu and the pause menu
+        app.add_systems(
+            Update,
+            (
+                settings_widget_system.after(dropdown_system),
+                key_binding_system.run_if(any_with_component::<KeyBindingButton>),
+            ),
+        );
         // main menu stuff
-        app.add_systems(OnEnter(AppState::Menu), setup_main_menu);
+        app.add_sub_state::<MenuScreen>();
+        app.init_resource::<NewNotebookSettings>();
+        app.add_systems(OnEnter(MenuScreen::Main), setup_main_menu);
+        app.add_systems(OnEnter(MenuScreen::New), setup_new_notebook_menu);
+        app.add_systems(OnEnter(MenuScreen::Open), setup_open_menu);
+        app.add_systems(OnEnter(MenuScreen::Settings), setup_settings_menu);
+        app.add_systems(
+            Update,
+            (
+                start_button_menu_system,
+                screen_button_system,
+                notebook_button_system,
+                recovery_button_system,
+                new_notebook_button_system,
+            )
+                .run_if(in_state(AppState::Menu)),
+        );
+        // pause menu stuff
+        app.add_sub_state::<PauseScreen>();
+        app.add_systems(OnEnter(PauseScreen::Main), setup_pause_menu);
+        app.add_systems(OnEnter(PauseScreen::Settings), setup_pause_settings);
+        app.add_systems(OnEnter(PauseScreen::ConfirmQuit), setup_confirm_quit);
+        app.add_systems(
+            Update,
+            (pause_button_system, pause_escape_system).run_if(in_state(AppState::Paused)),
+        );
+        // page strip stuff
+        app.init_resource::<PageThumbnails>();
+        app.add_systems(OnEnter(AppState::Playing), setup_page_strip);
+        app.add_systems(Update, (update_page_thumbnails, page_strip_system).chain());
+        app.add_systems(
+            Update,
+            (page_strip_input_system, page_strip_scroll_system).run_if(in_state(AppState::Playing)),
+        );
+        // page panel stuff
+        app.add_systems(OnEnter(AppState::Playing), setup_page_panel);
         app.add_systems(
             Update,
-            start_button_menu_system.run_if(in_state(AppState::Menu)),
+            (
+                page_panel_system.after(text_field_system),
+                bookmark_list_system,
+                page_panel_button_system,
+            )
+                .run_if(in_state(AppState::Playing)),
         );
-        app.add_systems(OnExit(AppState::Menu), close_main_menu);
         // gui menu stuff
         app.init_state::<GuiMenuState>();
         app.add_systems(OnEnter(GuiMenuState::Debug), setup_debug_menu);
@@ -39,6 +121,10 @@ impl Plugin for GuiPlugin {
         app.add_systems(Update, debug_menu_system);
         app.add_systems(Update, save_image_button_system);
         app.add_systems(Update, clear_image_button_system);
+        app.add_systems(Update, document_button_system);
+        app.add_systems(Update, export_button_system);
+        app.add_systems(Update, recording_button_system);
+        app.add_systems(Update, notebook_cover_button_system);
     }
 }
 
@@ -68,7 +154,14 @@ pub(super) fn setup_gui(mut commands: Commands) {
                     flex_direction: FlexDirection::Column,
                     ..default()
                 },
-                children![create_button(GuiMenu), create_button(DebugMenu)]
+                children![
+                    create_button(GuiMenu),
+                    create_labelled_button(
+                        NotebookCoverButton,
+                        cover_button_label(&NotebookState::default()).to_string()
+                    ),
+                    create_button(DebugMenu)
+                ]
             )],
         ))
         .id();
@@ -0,0 +1,204 @@
+//! Panel with the title, tags and bookmark of the shown page, and a list of
+//! the bookmarked pages to go to
+
+use bevy::prelude::*;
+
+use crate::{
+    drawable::{
+        document::NotebookPages, metadata::UpdateContents, pages::PageControl,
+        stroke::DrawableContent, PageFilter,
+    },
+    gui::{
+        button::create_sized_button,
+        main_menu::heading,
+        widgets::{text_field, toggle, TextField, Toggle},
+        ButtonMenuComponent,
+    },
+    AppState,
+};
+
+/// What a widget on the panel changes about the shown page
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub(super) enum PageField {
+    Title,
+    Tags,
+    Bookmarked,
+}
+
+#[derive(Component, Clone, Copy)]
+pub(super) struct ContentsButton;
+
+impl ButtonMenuComponent for ContentsButton {
+    fn to_str(&self) -> &str {
+        "Table of contents"
+    }
+}
+
+/// The list of bookmarked pages
+#[derive(Component)]
+pub(super) struct BookmarkList;
+
+/// Goes to a bookmarked page
+#[derive(Component, Clone, Copy)]
+pub(super) struct BookmarkButton(usize);
+
+/// tags are typed separated by commas
+fn parse_tags(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub(super) fn setup_page_panel(mut commands: Commands) {
+    commands.spawn((
+        DespawnOnExit(AppState::Playing),
+        Node {
+            position_type: PositionType::Absolute,
+            right: px(0),
+            bottom: px(0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            padding: UiRect::all(px(10)),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.4)),
+        children![
+            heading("Page"),
+            text_field(TextField::new("Title", ""), PageField::Title),
+            text_field(TextField::new("Tags", ""), PageField::Tags),
+            toggle(
+                Toggle {
+                    on: false,
+                    label: "Bookmarked".to_string(),
+                },
+                PageField::Bookmarked,
+            ),
+            create_sized_button(
+                ContentsButton,
+                ContentsButton.to_str().to_string(),
+                px(260),
+                px(36),
+            ),
+            heading("Bookmarks"),
+            (
+                BookmarkList,
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+            ),
+        ],
+    ));
+}
+
+/// writes edits of the widgets to the metadata of the shown page, and shows
+/// the metadata of the page in the widgets otherwise
+pub(super) fn page_panel_system(
+    mut drawable_query: Query<(Entity, &mut DrawableContent), PageFilter>,
+    mut field_query: Query<(&mut TextField, &PageField)>,
+    mut toggle_query: Query<(&mut Toggle, &PageField)>,
+) {
+    let Some((_, mut content)) = drawable_query.iter_mut().min_by_key(|(entity, _)| *entity) else {
+        return;
+    };
+    for (mut field, page_field) in &mut field_query {
+        if field.editing() {
+            continue;
+        }
+        let metadata = &content.metadata;
+        let (current, edited) = match page_field {
+            PageField::Title => (metadata.title.clone(), field.text.trim().to_string()),
+            PageField::Tags => (metadata.tags.join(", "), parse_tags(&field.text).join(", ")),
+            PageField::Bookmarked => continue,
+        };
+        if current == edited {
+            continue;
+        }
+        if field.is_changed() {
+            match page_field {
+                PageField::Title => content.metadata.title = edited,
+                _ => content.metadata.tags = parse_tags(&field.text),
+            }
+        } else {
+            field.text = current;
+        }
+    }
+    for (mut toggle, page_field) in &mut toggle_query {
+        if *page_field != PageField::Bookmarked || toggle.on == content.metadata.bookmarked {
+            continue;
+        }
+        if toggle.is_changed() {
+            content.metadata.bookmarked = toggle.on;
+        } else {
+            toggle.on = content.metadata.bookmarked;
+        }
+    }
+}
+
+/// keeps a button in the list for each bookmarked page
+pub(super) fn bookmark_list_system(
+    mut commands: Commands,
+    list: Single<Entity, With<BookmarkList>>,
+    drawable_query: Query<(Entity, &DrawableContent), PageFilter>,
+    notebook_pages: Res<NotebookPages>,
+    // the bookmarks the list was made for
+    mut listed: Local<Option<Vec<(usize, String)>>>,
+) {
+    let mut drawables: Vec<_> = drawable_query.iter().collect();
+    drawables.sort_by_key(|(entity, _)| *entity);
+    let first_shown = notebook_pages.first_shown;
+    let bookmarks: Vec<_> = (0..notebook_pages.count(drawables.len()))
+        .filter_map(|index| {
+            let content = match index.checked_sub(first_shown) {
+                Some(shown) if shown < drawables.len() => Some(drawables[shown].1),
+                _ => notebook_pages.get(index),
+            }?;
+            let metadata = &content.metadata;
+            metadata
+                .bookmarked
+                .then(|| (index, metadata.display_title(index)))
+        })
+        .collect();
+    if listed.as_ref() == Some(&bookmarks) {
+        return;
+    }
+    commands.entity(*list).despawn_children();
+    for (index, title) in &bookmarks {
+        commands.spawn((
+            create_sized_button(BookmarkButton(*index), title.clone(), px(260), px(36)),
+            ChildOf(*list),
+        ));
+    }
+    *listed = Some(bookmarks);
+}
+
+pub(super) fn page_panel_button_system(
+    bookmark_query: Query<(&Interaction, &BookmarkButton), Changed<Interaction>>,
+    contents_query: Query<&Interaction, (With<ContentsButton>, Changed<Interaction>)>,
+    mut control_writer: MessageWriter<PageControl>,
+    mut contents_writer: MessageWriter<UpdateContents>,
+) {
+    for (interaction, bookmark) in bookmark_query {
+        if *interaction == Interaction::Pressed {
+            control_writer.write(PageControl::GoTo(bookmark.0));
+        }
+    }
+    for interaction in contents_query {
+        if *interaction == Interaction::Pressed {
+            contents_writer.write(UpdateContents);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_tags;
+
+    #[test]
+    fn tags_are_separated_by_commas() {
+        assert_eq!(parse_tags(" maths, notes,, "), ["maths", "notes"]);
+        assert!(parse_tags("").is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+//! Side panel with a thumbnail of every page
+//!
+//! Clicking a thumbnail goes to its page and dragging it onto another moves
+//! the page there. Thumbnails are rendered when their page changes, a few per
+//! frame.
+
+use bevy::{
+    asset::RenderAssetUsages,
+    input::mouse::{AccumulatedMouseScroll, MouseScrollUnit},
+    prelude::*,
+    ui::RelativeCursorPosition,
+};
+use image::DynamicImage;
+
+use crate::{
+    drawable::{
+        document::{render_content, NotebookOpened, NotebookPages},
+        metadata::UpdateContents,
+        pages::PageControl,
+        stroke::DrawableContent,
+        BrushTips, PageFilter,
+    },
+    gui::{button::create_sized_button, ButtonMenuComponent},
+    notebook::desk::NotebookSelected,
+    AppState,
+};
+
+/// Width and height in pixels thumbnails are rendered at
+const THUMBNAIL_RESOLUTION: usize = 128;
+/// Width of thumbnails on screen
+const THUMBNAIL_WIDTH: f32 = 110.0;
+const THUMBNAILS_PER_FRAME: usize = 4;
+/// Seconds between rendering the thumbnail of a page that's being drawn on
+const EDITED_INTERVAL: f64 = 0.5;
+
+const SHOWN_BORDER: Color = Color::srgb(0.3, 0.5, 0.9);
+const DRAGGED_BORDER: Color = Color::srgb(0.9, 0.6, 0.2);
+
+struct Thumbnail {
+    image: Handle<Image>,
+    /// the page changed since the thumbnail was rendered
+    stale: bool,
+    rendered_at: f64,
+}
+
+/// The thumbnail of each page, in page order
+#[derive(Resource, Default)]
+pub(super) struct PageThumbnails {
+    thumbnails: Vec<Thumbnail>,
+}
+
+impl PageThumbnails {
+    /// marks every thumbnail to be rendered as soon as possible
+    fn invalidate(&mut self) {
+        for thumbnail in &mut self.thumbnails {
+            thumbnail.stale = true;
+            thumbnail.rendered_at = f64::NEG_INFINITY;
+        }
+    }
+}
+
+/// The scrolling list of thumbnails
+#[derive(Component)]
+pub(super) struct PageStrip;
+
+#[derive(Component, Clone, Copy)]
+pub(super) struct PageThumbnail(usize);
+
+#[derive(Component, Clone, Copy)]
+pub(super) enum PageStripButton {
+    Insert,
+    Delete,
+}
+
+impl ButtonMenuComponent for PageStripButton {
+    fn to_str(&self) -> &str {
+        match self {
+            PageStripButton::Insert => "Insert",
+            PageStripButton::Delete => "Delete",
+        }
+    }
+}
+
+pub(super) fn setup_page_strip(mut commands: Commands) {
+    let button = |button: PageStripButton| {
+        create_sized_button(button, button.to_str().to_string(), px(65), px(36))
+    };
+    commands.spawn((
+        DespawnOnExit(AppState::Playing),
+        Node {
+            position_type: PositionType::Absolute,
+            left: px(0),
+            height: percent(100),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(px(10)),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.4)),
+        children![
+            (
+                Node {
+                    column_gap: px(5),
+                    ..default()
+                },
+                children![
+                    button(PageStripButton::Insert),
+                    button(PageStripButton::Delete)
+                ],
+            ),
+            (
+                PageStrip,
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    flex_grow: 1.0,
+                    margin: UiRect::top(px(10)),
+                    overflow: Overflow::scroll_y(),
+                    ..default()
+                },
+                RelativeCursorPosition::default(),
+            ),
+        ],
+    ));
+}
+
+/// renders the thumbnails of pages that changed
+#[allow(clippy::too_many_arguments)]
+pub(super) fn update_page_thumbnails(
+    mut thumbnails: ResMut<PageThumbnails>,
+    drawable_query: Query<(Entity, Ref<DrawableContent>), PageFilter>,
+    notebook_pages: Res<NotebookPages>,
+    mut control_reader: MessageReader<PageControl>,
+    mut opened_reader: MessageReader<NotebookOpened>,
+    mut contents_reader: MessageReader<UpdateContents>,
+    mut selected_reader: MessageReader<NotebookSelected>,
+    brush_tips: Res<BrushTips>,
+    mut images: ResMut<Assets<Image>>,
+    time: Res<Time>,
+) {
+    let mut drawables: Vec<_> = drawable_query.iter().collect();
+    drawables.sort_by_key(|(entity, _)| *entity);
+    let count = notebook_pages.count(drawables.len());
+
+    if thumbnails.thumbnails.len() != count {
+        thumbnails.thumbnails.truncate(count);
+        while thumbnails.thumbnails.len() < count {
+            let image = images.add(Image::default());
+            thumbnails.thumbnails.push(Thumbnail {
+                image,
+                stale: true,
+                rendered_at: f64::NEG_INFINITY,
+            });
+        }
+    }
+    // the pages moved around
+    let edited =
+        control_reader.read().any(PageControl::edits) || contents_reader.read().count() > 0;
+    let other_notebook = opened_reader.read().count() + selected_reader.read().count() > 0;
+    if edited || other_notebook {
+        thumbnails.invalidate();
+    }
+    let shown = notebook_pages.first_shown..notebook_pages.first_shown + drawables.len();
+    for (index, (_, content)) in shown.clone().zip(&drawables) {
+        if content.is_changed() {
+            if let Some(thumbnail) = thumbnails.thumbnails.get_mut(index) {
+                thumbnail.stale = true;
+            }
+        }
+    }
+
+    let now = time.elapsed_secs_f64();
+    let stale = thumbnails
+        .thumbnails
+        .iter()
+        .enumerate()
+        .filter(|(_, thumbnail)| thumbnail.stale && now - thumbnail.rendered_at > EDITED_INTERVAL)
+        .map(|(index, _)| index)
+        .take(THUMBNAILS_PER_FRAME)
+        .collect::<Vec<_>>();
+    for index in stale {
+        let content = if shown.contains(&index) {
+            Some(&*drawables[index - shown.start].1)
+        } else {
+            notebook_pages.get(index)
+        };
+        let thumbnail = &mut thumbnails.thumbnails[index];
+        thumbnail.stale = false;
+        thumbnail.rendered_at = now;
+        let Some(content) = content else {
+            continue;
+        };
+        match render_content(content, THUMBNAIL_RESOLUTION, &brush_tips) {
+            Ok(rendered) => {
+                let image = Image::from_dynamic(
+                    DynamicImage::ImageRgba8(rendered),
+                    true,
+                    RenderAssetUsages::RENDER_WORLD,
+                );
+                if let Err(error) = images.insert(&thumbnail.image, image) {
+                    error!("Failed to update page thumbnail: {error}");
+                }
+            }
+            Err(error) => error!("Failed to render page thumbnail: {error}"),
+        }
+    }
+}
+
+/// keeps a thumbnail in the strip for each page and highlights the shown ones
+pub(super) fn page_strip_system(
+    mut commands: Commands,
+    strip: Single<(Entity, Option<&Children>), With<PageStrip>>,
+    mut thumbnail_query: Query<(&PageThumbnail, &Interaction, &mut BorderColor)>,
+    thumbnails: Res<PageThumbnails>,
+    drawable_query: Query<&DrawableContent, PageFilter>,
+    notebook_pages: Res<NotebookPages>,
+) {
+    let (strip, children) = *strip;
+    let count = thumbnails.thumbnails.len();
+    if children.map_or(0, |children| children.len()) != count {
+        commands.entity(strip).despawn_children();
+        // pages are the size of the drawables they're shown on
+        let aspect = drawable_query
+            .iter()
+            .next()
+            .map(|content| content.plane_scale.y / content.plane_scale.x)
+            .filter(|aspect| aspect.is_finite() && *aspect > 0.0)
+            .unwrap_or(1.0);
+        for (index, thumbnail) in thumbnails.thumbnails.iter().enumerate() {
+            commands.spawn((
+                PageThumbnail(index),
+                Node {
+                    width: px(THUMBNAIL_WIDTH),
+                    height: px(THUMBNAIL_WIDTH * aspect),
+                    border: UiRect::all(px(3)),
+                    margin: UiRect::bottom(px(8)),
+                    flex_shrink: 0.0,
+                    ..default()
+                },
+                BorderColor::all(Color::BLACK),
+                BackgroundColor(Color::WHITE),
+                ImageNode::new(thumbnail.image.clone()),
+                Interaction::default(),
+                RelativeCursorPosition::default(),
+                ChildOf(strip),
+                children![(
+                    Text::new((index + 1).to_string()),
+                    TextFont::from_font_size(12.0),
+                    TextColor(Color::srgb(0.4, 0.4, 0.4)),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: px(4),
+                        top: px(2),
+                        ..default()
+                    },
+                )],
+            ));
+        }
+        return;
+    }
+
+    let shown =
+        notebook_pages.first_shown..notebook_pages.first_shown + drawable_query.iter().len();
+    for (thumbnail, interaction, mut border) in &mut thumbnail_query {
+        let colour = if *interaction == Interaction::Pressed {
+            DRAGGED_BORDER
+        } else if shown.contains(&thumbnail.0) {
+            SHOWN_BORDER
+        } else {
+            Color::BLACK
+        };
+        border.set_if_neq(BorderColor::all(colour));
+    }
+}
+
+pub(super) fn page_strip_input_system(
+    thumbnail_query: Query<(&PageThumbnail, &Interaction, &RelativeCursorPosition)>,
+    button_query: Query<(&Interaction, &PageStripButton), Changed<Interaction>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    notebook_pages: Res<NotebookPages>,
+    // the page of the thumbnail being dragged
+    mut dragging: Local<Option<usize>>,
+    mut control_writer: MessageWriter<PageControl>,
+) {
+    if mouse_input.just_pressed(MouseButton::Left) {
+        *dragging = thumbnail_query
+            .iter()
+            .find(|(_, interaction, _)| **interaction == Interaction::Pressed)
+            .map(|(thumbnail, _, _)| thumbnail.0);
+    }
+    if mouse_input.just_released(MouseButton::Left) {
+        if let Some(from) = dragging.take() {
+            // letting go outside of the thumbnails does nothing
+            let target = thumbnail_query
+                .iter()
+                .find(|(_, _, cursor)| cursor.cursor_over)
+                .map(|(thumbnail, _, _)| thumbnail.0);
+            match target {
+                Some(to) if to == from => {
+                    control_writer.write(PageControl::GoTo(to));
+                }
+                Some(to) => {
+                    control_writer.write(PageControl::Move { from, to });
+                }
+                None => {}
+            }
+        }
+    }
+
+    for (interaction, button) in button_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let first_shown = notebook_pages.first_shown;
+        control_writer.write(match button {
+            PageStripButton::Insert => PageControl::Insert(first_shown + 1),
+            PageStripButton::Delete => PageControl::Delete(first_shown),
+        });
+    }
+}
+
+/// scrolls the strip with the mouse wheel while the cursor is over it
+pub(super) fn page_strip_scroll_system(
+    scroll: Res<AccumulatedMouseScroll>,
+    strip: Single<(&RelativeCursorPosition, &mut ScrollPosition), With<PageStrip>>,
+) {
+    let (cursor, mut position) = strip.into_inner();
+    if !cursor.cursor_over || scroll.delta.y == 0.0 {
+        return;
+    }
+    let pixels = match scroll.unit {
+        MouseScrollUnit::Line => scroll.delta.y * 40.0,
+        MouseScrollUnit::Pixel => scroll.delta.y,
+    };
+    position.y = (position.y - pixels).max(0.0);
+}
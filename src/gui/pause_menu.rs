@@ -0,0 +1,201 @@
+//! The menu over the notebook while the app is paused
+
+use bevy::{prelude::*, ui::FocusPolicy};
+
+use crate::{
+    config::Settings,
+    drawable::{
+        document::{new_notebook_path, NotebookPages, SaveNotebook, UnsavedChanges},
+        export::{ExportFormat, ExportNotebook},
+        guide::Snapping,
+    },
+    gui::{
+        create_button,
+        main_menu::{column, heading},
+        settings::spawn_settings,
+        ButtonMenuComponent,
+    },
+    notebook::desk::{SaveShelvedNotebooks, ShelvedNotebook},
+    AppState,
+};
+
+#[derive(SubStates, Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+#[source(AppState = AppState::Paused)]
+pub(super) enum PauseScreen {
+    #[default]
+    Main,
+    Settings,
+    /// asks whether to save before quitting to the main menu
+    ConfirmQuit,
+}
+
+#[derive(Component, Clone, Copy)]
+pub(super) enum PauseButton {
+    Resume,
+    Save,
+    Export,
+    Settings,
+    QuitToMenu,
+    Back,
+    SaveAndQuit,
+    QuitWithoutSaving,
+}
+
+impl ButtonMenuComponent for PauseButton {
+    fn to_str(&self) -> &str {
+        match self {
+            PauseButton::Resume => "Resume",
+            PauseButton::Save => "Save",
+            PauseButton::Export => "Export PDF",
+            PauseButton::Settings => "Settings",
+            PauseButton::QuitToMenu => "Quit to menu",
+            PauseButton::Back => "Back",
+            PauseButton::SaveAndQuit => "Save and quit",
+            PauseButton::QuitWithoutSaving => "Don't save",
+        }
+    }
+}
+
+/// dims the notebook behind the screen and stops clicks reaching the buttons
+/// that are always shown
+fn overlay(screen: PauseScreen) -> impl Bundle {
+    (
+        DespawnOnExit(screen),
+        Node {
+            width: percent(100),
+            height: percent(100),
+            position_type: PositionType::Absolute,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        FocusPolicy::Block,
+        GlobalZIndex(1),
+    )
+}
+
+pub(super) fn setup_pause_menu(mut commands: Commands) {
+    commands.spawn((
+        overlay(PauseScreen::Main),
+        children![(
+            column(),
+            children![
+                heading("Paused"),
+                create_button(PauseButton::Resume),
+                create_button(PauseButton::Save),
+                create_button(PauseButton::Export),
+                create_button(PauseButton::Settings),
+                create_button(PauseButton::QuitToMenu),
+            ]
+        )],
+    ));
+}
+
+pub(super) fn setup_pause_settings(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    snapping: Res<Snapping>,
+) {
+    let root = commands.spawn(overlay(PauseScreen::Settings)).id();
+    spawn_settings(&mut commands, root, &settings, &snapping);
+    commands.spawn((create_button(PauseButton::Back), ChildOf(root)));
+}
+
+/// whether a notebook on the desk has unsaved changes
+fn any_unsaved(unsaved: &UnsavedChanges, shelved_query: &Query<&ShelvedNotebook>) -> bool {
+    unsaved.0 || shelved_query.iter().any(ShelvedNotebook::unsaved)
+}
+
+pub(super) fn setup_confirm_quit(
+    mut commands: Commands,
+    unsaved: Res<UnsavedChanges>,
+    shelved_query: Query<&ShelvedNotebook>,
+) {
+    let root = commands.spawn(overlay(PauseScreen::ConfirmQuit)).id();
+    let column = commands.spawn((column(), ChildOf(root))).id();
+    let text = if unsaved.0 {
+        "The notebook has unsaved changes"
+    } else {
+        "Another notebook on the desk has unsaved changes"
+    };
+    commands.spawn((heading(text), ChildOf(column)));
+    if any_unsaved(&unsaved, &shelved_query) {
+        commands.spawn((create_button(PauseButton::SaveAndQuit), ChildOf(column)));
+    }
+    commands.spawn((
+        create_button(PauseButton::QuitWithoutSaving),
+        ChildOf(column),
+    ));
+    commands.spawn((create_button(PauseButton::Back), ChildOf(column)));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn pause_button_system(
+    interaction_query: Query<(&Interaction, &PauseButton), Changed<Interaction>>,
+    unsaved: Res<UnsavedChanges>,
+    shelved_query: Query<&ShelvedNotebook>,
+    notebook_pages: Res<NotebookPages>,
+    mut save_writer: MessageWriter<SaveNotebook>,
+    mut save_shelved_writer: MessageWriter<SaveShelvedNotebooks>,
+    mut export_writer: MessageWriter<ExportNotebook>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut next_screen: ResMut<NextState<PauseScreen>>,
+) {
+    let mut save = || {
+        let path = notebook_pages
+            .path
+            .clone()
+            .unwrap_or_else(new_notebook_path);
+        save_writer.write(SaveNotebook { path });
+    };
+    for (interaction, button) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            PauseButton::Resume => next_state.set(AppState::Playing),
+            PauseButton::Save => save(),
+            PauseButton::Export => {
+                export_writer.write(ExportNotebook {
+                    format: ExportFormat::Pdf,
+                });
+            }
+            PauseButton::Settings => next_screen.set(PauseScreen::Settings),
+            PauseButton::Back => next_screen.set(PauseScreen::Main),
+            PauseButton::QuitToMenu if any_unsaved(&unsaved, &shelved_query) => {
+                next_screen.set(PauseScreen::ConfirmQuit);
+            }
+            PauseButton::QuitToMenu | PauseButton::QuitWithoutSaving => {
+                next_state.set(AppState::Menu);
+            }
+            // every notebook on the desk with unsaved changes
+            PauseButton::SaveAndQuit => {
+                if unsaved.0 {
+                    save();
+                }
+                save_shelved_writer.write(SaveShelvedNotebooks);
+                next_state.set(AppState::Menu);
+            }
+        }
+    }
+}
+
+/// Escape resumes or cancels quitting, on the settings screen it cancels
+/// rebinding a key instead
+pub(super) fn pause_escape_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    screen: Res<State<PauseScreen>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut next_screen: ResMut<NextState<PauseScreen>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    match screen.get() {
+        PauseScreen::Main => next_state.set(AppState::Playing),
+        PauseScreen::ConfirmQuit => next_screen.set(PauseScreen::Main),
+        PauseScreen::Settings => {}
+    }
+}
@@ -0,0 +1,327 @@
+//! The settings screen, shared by the main menu and the pause menu
+
+use bevy::prelude::*;
+
+use crate::{
+    config::{KeyAction, Settings, Theme, AUTOSAVE_INTERVALS, BRUSH_SIZES, CAMERA_SENSITIVITIES},
+    drawable::{guide::Snapping, Brush},
+    gui::{
+        button::{create_sized_button, set_button_label},
+        main_menu::{column, heading, RESOLUTIONS},
+        widgets::{dropdown, slider, toggle, Dropdown, Slider, Toggle},
+    },
+};
+
+/// What a widget on the settings screen changes
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum SettingWidget {
+    Brush,
+    BrushSize,
+    Resolution,
+    AutosaveInterval,
+    Theme,
+    CameraSensitivity,
+    Snapping,
+}
+
+/// Rebinds the key of an action, the next key pressed after clicking it
+#[derive(Component, Clone, Copy)]
+pub(super) struct KeyBindingButton(KeyAction);
+
+/// Marks the [`KeyBindingButton`] waiting for a key, so leaving the settings
+/// screen stops the waiting
+#[derive(Component)]
+pub(super) struct WaitingForKey;
+
+/// "Digit1" is just "1"
+fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    ["Key", "Digit"]
+        .into_iter()
+        .find_map(|prefix| name.strip_prefix(prefix).map(str::to_string))
+        .unwrap_or(name)
+}
+
+fn key_binding_label(action: KeyAction, settings: &Settings) -> String {
+    format!(
+        "{}: {}",
+        action.name(),
+        key_name(settings.key_bindings.key(action))
+    )
+}
+
+/// spawns the settings widgets in `parent`, for the settings screens of the
+/// main menu and the pause menu
+pub(super) fn spawn_settings(
+    commands: &mut Commands,
+    parent: Entity,
+    settings: &Settings,
+    snapping: &Snapping,
+) {
+    let brush = &settings.default_brush.brush;
+    let brush_index = Brush::PRESETS
+        .iter()
+        .position(|preset| preset.name() == brush.name())
+        .unwrap_or(0);
+    let brushes = Brush::PRESETS.iter().map(|brush| brush.name().to_string());
+    let resolution_index = RESOLUTIONS
+        .iter()
+        .position(|resolution| *resolution == settings.resolution)
+        .unwrap_or(0);
+    let resolutions = RESOLUTIONS
+        .iter()
+        .map(|resolution| format!("{resolution} px"));
+    let theme_index = Theme::ALL
+        .iter()
+        .position(|theme| *theme == settings.theme)
+        .unwrap_or(0);
+    let themes = Theme::ALL.iter().map(|theme| format!("{theme:?}"));
+
+    commands.spawn((heading("Settings"), ChildOf(parent)));
+    let row = commands
+        .spawn((
+            Node {
+                align_items: AlignItems::FlexStart,
+                ..default()
+            },
+            ChildOf(parent),
+        ))
+        .id();
+    commands.spawn((
+        column(),
+        ChildOf(row),
+        children![
+            dropdown(
+                Dropdown::new("Brush", brushes.collect(), brush_index),
+                SettingWidget::Brush,
+            ),
+            slider(
+                Slider {
+                    value: settings.default_brush.radius,
+                    min: *BRUSH_SIZES.start(),
+                    max: *BRUSH_SIZES.end(),
+                    step: 0.005,
+                    label: "Brush size".to_string(),
+                },
+                SettingWidget::BrushSize,
+            ),
+            dropdown(
+                Dropdown::new("Page size", resolutions.collect(), resolution_index),
+                SettingWidget::Resolution,
+            ),
+            slider(
+                Slider {
+                    value: settings.autosave_interval as f32,
+                    min: *AUTOSAVE_INTERVALS.start() as f32,
+                    max: *AUTOSAVE_INTERVALS.end() as f32,
+                    step: 5.0,
+                    label: "Autosave seconds".to_string(),
+                },
+                SettingWidget::AutosaveInterval,
+            ),
+            dropdown(
+                Dropdown::new("Theme", themes.collect(), theme_index),
+                SettingWidget::Theme,
+            ),
+            slider(
+                Slider {
+                    value: settings.camera_sensitivity,
+                    min: *CAMERA_SENSITIVITIES.start(),
+                    max: *CAMERA_SENSITIVITIES.end(),
+                    step: 0.1,
+                    label: "Camera sensitivity".to_string(),
+                },
+                SettingWidget::CameraSensitivity,
+            ),
+            toggle(
+                Toggle {
+                    on: snapping.to_grid,
+                    label: "Snapping".to_string(),
+                },
+                SettingWidget::Snapping,
+            ),
+        ],
+    ));
+    commands
+        .spawn((column(), ChildOf(row)))
+        .with_children(|column| {
+            for action in KeyAction::ALL {
+                column.spawn(create_sized_button(
+                    KeyBindingButton(action),
+                    key_binding_label(action, settings),
+                    px(260),
+                    px(36),
+                ));
+            }
+        });
+}
+
+/// writes the values of the settings widgets that changed to the [`Settings`]
+///
+/// Widgets that were just spawned show the settings rather than change them,
+/// a resolution or brush that isn't one of the choices is kept until another
+/// is picked.
+pub(super) fn settings_widget_system(
+    toggle_query: Query<(Ref<Toggle>, &SettingWidget), Changed<Toggle>>,
+    slider_query: Query<(Ref<Slider>, &SettingWidget), Changed<Slider>>,
+    dropdown_query: Query<(Ref<Dropdown>, &SettingWidget), Changed<Dropdown>>,
+    mut settings: ResMut<Settings>,
+    mut snapping: ResMut<Snapping>,
+) {
+    let toggle_query = toggle_query.iter().filter(|(toggle, _)| !toggle.is_added());
+    let slider_query = slider_query.iter().filter(|(slider, _)| !slider.is_added());
+    let dropdown_query = dropdown_query
+        .iter()
+        .filter(|(dropdown, _)| !dropdown.is_added());
+    for (toggle, widget) in toggle_query {
+        if *widget == SettingWidget::Snapping && snapping.to_grid != toggle.on {
+            snapping.to_grid = toggle.on;
+        }
+    }
+    // only write settings that differ so they're not saved for nothing
+    let mut changed = settings.clone();
+    for (slider, widget) in slider_query {
+        match widget {
+            SettingWidget::BrushSize => changed.default_brush.radius = slider.value,
+            SettingWidget::AutosaveInterval => changed.autosave_interval = slider.value.into(),
+            SettingWidget::CameraSensitivity => changed.camera_sensitivity = slider.value,
+            _ => {}
+        }
+    }
+    for (dropdown, widget) in dropdown_query {
+        match widget {
+            SettingWidget::Brush => {
+                changed.default_brush.brush = Brush::PRESETS[dropdown.selected];
+            }
+            SettingWidget::Resolution => changed.resolution = RESOLUTIONS[dropdown.selected],
+            SettingWidget::Theme => changed.theme = Theme::ALL[dropdown.selected],
+            _ => {}
+        }
+    }
+    settings.set_if_neq(changed);
+}
+
+pub(super) fn key_binding_system(
+    mut commands: Commands,
+    button_query: Query<(Entity, &Interaction, &KeyBindingButton, &Children), Changed<Interaction>>,
+    all_buttons: Query<(Entity, &KeyBindingButton, &Children, Has<WaitingForKey>)>,
+    mut text_query: Query<&mut Text>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<Settings>,
+) {
+    for (entity, interaction, button, children) in button_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // only one action waits for a key at a time
+        for (other, other_button, children, waiting) in &all_buttons {
+            if waiting {
+                commands.entity(other).remove::<WaitingForKey>();
+                let label = key_binding_label(other_button.0, &settings);
+                set_button_label(children, &mut text_query, label);
+            }
+        }
+        commands.entity(entity).insert(WaitingForKey);
+        let label = format!("{}: press a key", button.0.name());
+        set_button_label(children, &mut text_query, label);
+        return;
+    }
+
+    let Some((entity, button, ..)) = all_buttons.iter().find(|(.., waiting)| *waiting) else {
+        return;
+    };
+    let Some(key) = keyboard_input.get_just_pressed().next().copied() else {
+        return;
+    };
+    commands.entity(entity).remove::<WaitingForKey>();
+    if key != KeyCode::Escape {
+        settings.key_bindings.rebind(button.0, key);
+    }
+    // rebinding can swap keys with another action
+    for (_, button, children, _) in &all_buttons {
+        set_button_label(
+            children,
+            &mut text_query,
+            key_binding_label(button.0, &settings),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use super::{
+        key_binding_system, key_name, settings_widget_system, KeyBindingButton, SettingWidget,
+        WaitingForKey,
+    };
+    use crate::{
+        config::{KeyAction, Settings},
+        drawable::guide::Snapping,
+        gui::{main_menu::RESOLUTIONS, widgets::Dropdown},
+    };
+
+    #[test]
+    fn key_names_are_short() {
+        assert_eq!(key_name(KeyCode::KeyS), "S");
+        assert_eq!(key_name(KeyCode::Digit1), "1");
+        assert_eq!(key_name(KeyCode::Space), "Space");
+    }
+
+    #[test]
+    fn opening_the_settings_changes_nothing() {
+        let mut app = App::new();
+        app.insert_resource(Settings {
+            resolution: 1500,
+            ..default()
+        })
+        .init_resource::<Snapping>()
+        .add_systems(Update, settings_widget_system);
+        let choices = RESOLUTIONS.iter().map(ToString::to_string).collect();
+        let widget = app
+            .world_mut()
+            .spawn((
+                Dropdown::new("Page size", choices, 0),
+                SettingWidget::Resolution,
+            ))
+            .id();
+        app.update();
+        assert_eq!(app.world().resource::<Settings>().resolution, 1500);
+
+        app.world_mut()
+            .get_mut::<Dropdown>(widget)
+            .unwrap()
+            .selected = 1;
+        app.update();
+        assert_eq!(
+            app.world().resource::<Settings>().resolution,
+            RESOLUTIONS[1]
+        );
+    }
+
+    #[test]
+    fn leaving_the_screen_stops_waiting_for_a_key() {
+        let mut app = App::new();
+        app.init_resource::<Settings>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_systems(Update, key_binding_system);
+        let button = app
+            .world_mut()
+            .spawn((
+                KeyBindingButton(KeyAction::Ruler),
+                Interaction::Pressed,
+                children![Text::default()],
+            ))
+            .id();
+        app.update();
+        assert!(app.world().entity(button).contains::<WaitingForKey>());
+
+        app.world_mut().despawn(button);
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyQ);
+        app.update();
+        let settings = app.world().resource::<Settings>();
+        assert_ne!(settings.key_bindings.key(KeyAction::Ruler), KeyCode::KeyQ);
+    }
+}
@@ -0,0 +1,383 @@
+//! Toggle, slider and dropdown widgets
+//!
+//! Each widget keeps its value in its component, systems that use the value
+//! look for the component changing. Toggles and text fields show values set
+//! from elsewhere too.
+
+use bevy::{
+    input::{keyboard::KeyboardInput, ButtonState},
+    prelude::*,
+    ui::RelativeCursorPosition,
+};
+
+use crate::{
+    drawable::text::TextEditor,
+    gui::button::{create_sized_button, set_button_label},
+};
+
+const WIDGET_WIDTH: Val = Val::Px(260.0);
+const WIDGET_HEIGHT: Val = Val::Px(36.0);
+const TEXT_COLOUR: Color = Color::srgb(0.9, 0.9, 0.9);
+
+/// A button that switches between on and off
+#[derive(Component, Debug, Clone)]
+pub(super) struct Toggle {
+    pub on: bool,
+    pub label: String,
+}
+
+impl Toggle {
+    fn text(&self) -> String {
+        let state = if self.on { "On" } else { "Off" };
+        format!("{}: {state}", self.label)
+    }
+}
+
+pub(super) fn toggle(toggle: Toggle, marker: impl Component) -> impl Bundle {
+    let text = toggle.text();
+    (
+        create_sized_button(toggle, text, WIDGET_WIDTH, WIDGET_HEIGHT),
+        marker,
+    )
+}
+
+pub(super) fn toggle_system(
+    mut toggle_query: Query<(Ref<Interaction>, &mut Toggle, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, mut toggle, children) in &mut toggle_query {
+        if interaction.is_changed() && *interaction == Interaction::Pressed {
+            toggle.on = !toggle.on;
+        }
+        if toggle.is_changed() {
+            set_button_label(children, &mut text_query, toggle.text());
+        }
+    }
+}
+
+/// A line of text typed in after clicking it, Enter or clicking somewhere
+/// else finishes and Escape puts the text back
+#[derive(Component, Debug, Clone)]
+pub(super) struct TextField {
+    pub text: String,
+    pub label: String,
+    /// the text before editing, while it's being edited
+    editing: Option<String>,
+}
+
+impl TextField {
+    pub fn new(label: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            label: label.into(),
+            editing: None,
+        }
+    }
+
+    pub fn editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    fn display(&self) -> String {
+        let caret = if self.editing() { "|" } else { "" };
+        format!("{}: {}{caret}", self.label, self.text)
+    }
+}
+
+pub(super) fn text_field(field: TextField, marker: impl Component) -> impl Bundle {
+    let text = field.display();
+    (
+        create_sized_button(field, text, WIDGET_WIDTH, WIDGET_HEIGHT),
+        marker,
+    )
+}
+
+pub(super) fn text_field_system(
+    mut field_query: Query<(&Interaction, &mut TextField, &Children)>,
+    mut key_reader: MessageReader<KeyboardInput>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut text_query: Query<&mut Text>,
+    mut text_editor: ResMut<TextEditor>,
+) {
+    if mouse_input.just_pressed(MouseButton::Left) {
+        for (interaction, mut field, _) in &mut field_query {
+            let clicked = *interaction == Interaction::Pressed;
+            if clicked && !field.editing() {
+                field.editing = Some(field.text.clone());
+            } else if !clicked && field.editing() {
+                field.editing = None;
+            }
+        }
+    }
+
+    let inputs: Vec<_> = key_reader
+        .read()
+        .filter(|input| input.state == ButtonState::Pressed)
+        .collect();
+    for (_, mut field, children) in &mut field_query {
+        if field.editing() {
+            for input in &inputs {
+                match input.key_code {
+                    KeyCode::Enter | KeyCode::NumpadEnter => field.editing = None,
+                    KeyCode::Escape => field.text = field.editing.take().unwrap_or_default(),
+                    KeyCode::Backspace => {
+                        field.text.pop();
+                    }
+                    _ => {
+                        if let Some(text) = &input.text {
+                            let typed = text.chars().filter(|character| !character.is_control());
+                            field.text.extend(typed);
+                        }
+                    }
+                }
+                if !field.editing() {
+                    break;
+                }
+            }
+        }
+        if field.is_changed() {
+            set_button_label(children, &mut text_query, field.display());
+        }
+    }
+    // keyboard shortcuts are off while typing
+    let typing = field_query.iter().any(|(_, field, _)| field.editing());
+    if text_editor.field_focused != typing {
+        text_editor.field_focused = typing;
+    }
+}
+
+/// A bar that's dragged to pick a value between `min` and `max`, the value
+/// is rounded to a multiple of `step`
+#[derive(Component, Debug, Clone)]
+pub(super) struct Slider {
+    pub value: f32,
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    pub label: String,
+}
+
+impl Slider {
+    /// how far along the bar the value is, from 0 to 1
+    fn fraction(&self) -> f32 {
+        ((self.value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+
+    /// the value at `fraction` along the bar
+    fn value_at(&self, fraction: f32) -> f32 {
+        let value = self.min + fraction.clamp(0.0, 1.0) * (self.max - self.min);
+        ((value / self.step).round() * self.step).clamp(self.min, self.max)
+    }
+
+    fn text(&self) -> String {
+        let decimals = (-self.step.log10()).ceil().max(0.0) as usize;
+        format!("{}: {:.*}", self.label, decimals, self.value)
+    }
+}
+
+/// The part of a [`Slider`] showing its value
+#[derive(Component)]
+pub(super) struct SliderFill;
+
+pub(super) fn slider(slider: Slider, marker: impl Component) -> impl Bundle {
+    let text = slider.text();
+    let fill = percent(slider.fraction() * 100.0);
+    (
+        Node {
+            width: WIDGET_WIDTH,
+            height: WIDGET_HEIGHT,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            margin: UiRect::bottom(px(10)),
+            ..default()
+        },
+        BorderRadius::all(px(5)),
+        BackgroundColor(Color::srgb(0.1, 0.1, 0.1)),
+        Interaction::default(),
+        RelativeCursorPosition::default(),
+        slider,
+        marker,
+        children![
+            (
+                SliderFill,
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: px(0),
+                    width: fill,
+                    height: percent(100),
+                    ..default()
+                },
+                BorderRadius::all(px(5)),
+                BackgroundColor(Color::srgb(0.3, 0.3, 0.45)),
+            ),
+            (Text::new(text), TextColor(TEXT_COLOUR)),
+        ],
+    )
+}
+
+pub(super) fn slider_system(
+    mut slider_query: Query<(
+        &Interaction,
+        &RelativeCursorPosition,
+        &mut Slider,
+        &Children,
+    )>,
+    mut fill_query: Query<&mut Node, With<SliderFill>>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, cursor, mut slider, children) in &mut slider_query {
+        // the slider stays pressed while dragging outside of it
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(normalized) = cursor.normalized else {
+            continue;
+        };
+        let value = slider.value_at(normalized.x + 0.5);
+        if value == slider.value {
+            continue;
+        }
+        slider.value = value;
+        for child in children {
+            if let Ok(mut fill) = fill_query.get_mut(*child) {
+                fill.width = percent(slider.fraction() * 100.0);
+            }
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.0 = slider.text();
+            }
+        }
+    }
+}
+
+/// A button that opens a list of choices
+#[derive(Component, Debug, Clone)]
+pub(super) struct Dropdown {
+    pub choices: Vec<String>,
+    pub selected: usize,
+    pub label: String,
+    /// the list of choices while it's open
+    list: Option<Entity>,
+}
+
+impl Dropdown {
+    pub fn new(label: impl Into<String>, choices: Vec<String>, selected: usize) -> Self {
+        Self {
+            choices,
+            selected,
+            label: label.into(),
+            list: None,
+        }
+    }
+
+    fn text(&self) -> String {
+        let choice = self.choices.get(self.selected).map_or("", String::as_str);
+        format!("{}: {choice}", self.label)
+    }
+}
+
+/// One of the choices of an open [`Dropdown`]
+#[derive(Component, Clone, Copy)]
+pub(super) struct DropdownChoice {
+    dropdown: Entity,
+    index: usize,
+}
+
+pub(super) fn dropdown(dropdown: Dropdown, marker: impl Component) -> impl Bundle {
+    let text = dropdown.text();
+    (
+        create_sized_button(dropdown, text, WIDGET_WIDTH, WIDGET_HEIGHT),
+        marker,
+    )
+}
+
+pub(super) fn dropdown_system(
+    mut commands: Commands,
+    interaction_query: Query<(Entity, &Interaction), Changed<Interaction>>,
+    choice_query: Query<(&Interaction, &DropdownChoice), Changed<Interaction>>,
+    mut dropdowns: Query<(&mut Dropdown, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (entity, interaction) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // not a dropdown
+        let Ok((mut dropdown, _)) = dropdowns.get_mut(entity) else {
+            continue;
+        };
+        // opening and closing the list doesn't change the choice
+        let dropdown = dropdown.bypass_change_detection();
+        if let Some(list) = dropdown.list.take() {
+            commands.entity(list).despawn();
+            continue;
+        }
+        let list = commands
+            .spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: percent(100),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                // above the pause menu's overlay
+                GlobalZIndex(2),
+                ChildOf(entity),
+            ))
+            .id();
+        for (index, choice) in dropdown.choices.iter().enumerate() {
+            commands.spawn((
+                create_sized_button(
+                    DropdownChoice {
+                        dropdown: entity,
+                        index,
+                    },
+                    choice.clone(),
+                    WIDGET_WIDTH,
+                    WIDGET_HEIGHT,
+                ),
+                ChildOf(list),
+            ));
+        }
+        dropdown.list = Some(list);
+    }
+
+    for (interaction, choice) in choice_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Ok((mut dropdown, children)) = dropdowns.get_mut(choice.dropdown) else {
+            continue;
+        };
+        dropdown.selected = choice.index;
+        if let Some(list) = dropdown.list.take() {
+            commands.entity(list).despawn();
+        }
+        set_button_label(children, &mut text_query, dropdown.text());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Slider;
+
+    #[test]
+    fn slider_values_snap_to_steps() {
+        let slider = Slider {
+            value: 30.0,
+            min: 5.0,
+            max: 300.0,
+            step: 5.0,
+            label: "Autosave".to_string(),
+        };
+        assert_eq!(slider.fraction(), 25.0 / 295.0);
+        assert_eq!(slider.value_at(0.5), 155.0);
+        assert_eq!(slider.value_at(1.5), 300.0);
+        assert_eq!(slider.text(), "Autosave: 30");
+
+        let slider = Slider {
+            step: 0.005,
+            ..slider
+        };
+        assert_eq!(slider.text(), "Autosave: 30.000");
+    }
+}
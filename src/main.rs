@@ -1,39 +1,136 @@
-use bevy::{
-    prelude::*, remote::http::RemoteHttpPlugin, remote::RemotePlugin, render::RenderPlugin,
+use bevy::{prelude::*, remote::http::RemoteHttpPlugin, render::RenderPlugin};
+use camera_controller::{camera_controller_system, camera_focus_system, CameraFocus};
+use clap::Parser;
+use cli::{Cli, Command};
+use config::{ConfigPlugin, Settings};
+use drawable::{
+    document::StartupNotebook, remote::remote_plugin, sync::SyncConnection, text::not_typing,
+    DefaultResolution, DrawablePlugin,
 };
-use drawable::DrawablePlugin;
 use notebook::{
-    add_notebook_load, keyboard_animation_control, setup_notebook_animations_once_loaded,
+    add_notebook_load,
+    animation::{
+        go_to_turned_page, notebook_animation_system, setup_notebook_animations_once_loaded,
+        NotebookInput, NotebookState, PageTurned,
+    },
+    desk::{
+        click_notebook_system, mark_selected_drawables, save_shelved_notebooks,
+        select_notebook_system, NotebookSelected, SaveShelvedNotebooks, SelectNotebook,
+        ShelvedNotebookSaved,
+    },
+    keyboard_animation_control,
 };
 use scene_hook::HookPlugin;
 
 use crate::gui::GuiPlugin;
 
 mod camera_controller;
+mod cli;
+mod config;
 mod drawable;
 mod gui;
 mod notebook;
 pub mod scene_hook;
 
-fn main() {
-    let plugin = DefaultPlugins.set(RenderPlugin::default());
+fn main() -> AppExit {
+    let cli = Cli::parse();
+    if let Some(Command::Render {
+        notebook,
+        out,
+        resolution,
+        brushes,
+    }) = cli.command
+    {
+        return cli::render(notebook, out, resolution, brushes);
+    }
 
-    App::new()
-        .add_plugins((plugin, DrawablePlugin::default(), HookPlugin))
-        // for debugging
-        .add_plugins((RemotePlugin::default(), RemoteHttpPlugin::default()))
-        .add_plugins(GuiPlugin)
-        .init_state::<AppState>()
+    let mut window = Window::default();
+    if let Some(size) = cli.window_size {
+        window.resolution = size.into();
+    }
+    let plugin = DefaultPlugins
+        .set(RenderPlugin::default())
+        .set(WindowPlugin {
+            primary_window: Some(window),
+            ..default()
+        });
+
+    let mut app = App::new();
+    app.add_plugins((plugin, DrawablePlugin::default(), HookPlugin, ConfigPlugin));
+    let resolution = cli
+        .resolution
+        .unwrap_or(app.world().resource::<Settings>().resolution);
+    app.insert_resource(DefaultResolution(resolution));
+    if !cli.no_remote {
+        // for debugging and scripting, see `drawable::remote`
+        app.add_plugins((remote_plugin(), RemoteHttpPlugin::default()));
+    }
+    let sync = match (&cli.host, &cli.join) {
+        (Some(address), _) => Some(SyncConnection::host(address)),
+        (_, Some(address)) => Some(SyncConnection::join(address)),
+        _ => None,
+    };
+    match sync {
+        Some(Ok(connection)) => {
+            app.insert_resource(connection);
+        }
+        Some(Err(error)) => {
+            eprintln!("Failed to start the drawing session: {error}");
+            return AppExit::error();
+        }
+        None => {}
+    }
+    if let Some(notebook) = cli.notebook {
+        app.insert_resource(StartupNotebook(notebook));
+    }
+    app.add_plugins(GuiPlugin)
+        .insert_state(if cli.skip_menu {
+            AppState::Playing
+        } else {
+            AppState::Menu
+        })
         .add_systems(Startup, add_notebook_load)
         .add_systems(Startup, setup)
-        .add_systems(Update, setup_notebook_animations_once_loaded)
-        .add_systems(Update, keyboard_animation_control)
-        .run();
+        .init_state::<NotebookState>()
+        .add_message::<NotebookInput>()
+        .add_message::<PageTurned>()
+        .add_systems(Update, keyboard_animation_control.run_if(not_typing))
+        .add_systems(
+            Update,
+            (
+                setup_notebook_animations_once_loaded,
+                notebook_animation_system,
+                go_to_turned_page,
+            )
+                .chain(),
+        )
+        .add_message::<SelectNotebook>()
+        .add_message::<NotebookSelected>()
+        .add_message::<SaveShelvedNotebooks>()
+        .add_message::<ShelvedNotebookSaved>()
+        .init_resource::<CameraFocus>()
+        .add_systems(Update, (mark_selected_drawables, save_shelved_notebooks))
+        .add_systems(
+            Update,
+            (
+                click_notebook_system.run_if(in_state(AppState::Playing)),
+                select_notebook_system,
+                camera_focus_system,
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            camera_controller_system.run_if(in_state(AppState::Playing)),
+        )
+        .run()
 }
 
 #[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash, States)]
 enum AppState {
     Playing,
+    /// the pause menu is shown over the notebook
+    Paused,
     #[default]
     Menu,
 }
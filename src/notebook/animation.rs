@@ -0,0 +1,437 @@
+//! Playing the notebook's animations
+//!
+//! Each notebook has a [`NotebookController`] that knows which page it's open
+//! at and plays the clips of its own animation graph for its own
+//! [`AnimationPlayer`]. Inputs go to the selected notebook, inputs given while
+//! a clip plays are queued and played in order once it finishes, and a
+//! [`PageTurned`] message is written after each page turn. The selected
+//! controller's [`NotebookState`] is mirrored in the app's state, so systems
+//! can run only while the notebook is open or closed.
+
+use std::collections::VecDeque;
+
+use bevy::{gltf::Gltf, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use super::desk::SelectedNotebook;
+use crate::drawable::{document::NotebookPages, pages::PageControl, PageFilter};
+
+/// Names of the clips in the notebook model. A model without one of a pair
+/// of clips plays the other one backwards instead, and one with none of
+/// these names uses its first clip to close.
+const TURN_FORWARD_CLIP: &str = "turn_forward";
+const TURN_BACK_CLIP: &str = "turn_back";
+const OPEN_CLIP: &str = "open";
+const CLOSE_CLIP: &str = "close";
+
+/// Inputs given after this many are queued are dropped
+const MAX_QUEUED: usize = 4;
+
+/// Message for animating the notebook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Message, Serialize, Deserialize)]
+pub enum NotebookInput {
+    Open,
+    Close,
+    TurnForward,
+    TurnBack,
+}
+
+/// Message written once a page turn finished playing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Message)]
+pub struct PageTurned {
+    pub notebook: Entity,
+    /// the page the notebook is open at now
+    pub page: usize,
+    pub forward: bool,
+}
+
+/// Whether the notebook is open, nothing can be drawn on while it's opening
+/// or closing
+#[derive(States, Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub enum NotebookState {
+    /// only the cover can be seen
+    Closed,
+    Opening,
+    #[default]
+    Open,
+    Closing,
+}
+
+/// The animation state of a notebook
+#[derive(Component, Debug, Default)]
+pub struct NotebookController {
+    /// the page the notebook is open at
+    page: usize,
+    state: NotebookState,
+    playing: Option<NotebookInput>,
+    /// the clip being played, `None` if the input is applied without one
+    playing_node: Option<AnimationNodeIndex>,
+    queued: VecDeque<NotebookInput>,
+    /// the player of the notebook model, once it's spawned
+    player: Option<Entity>,
+}
+
+impl NotebookController {
+    fn queue(&mut self, input: NotebookInput) {
+        if self.queued.len() < MAX_QUEUED {
+            self.queued.push_back(input);
+        }
+    }
+
+    /// starts the next queued input that can be played on a notebook with
+    /// `page_count` pages, inputs that can't are dropped
+    fn start_next(&mut self, page_count: usize) -> Option<NotebookInput> {
+        while let Some(input) = self.queued.pop_front() {
+            let open = self.state == NotebookState::Open;
+            let playable = match input {
+                NotebookInput::Open => self.state == NotebookState::Closed,
+                NotebookInput::Close => open,
+                NotebookInput::TurnForward => open && self.page + 1 < page_count,
+                NotebookInput::TurnBack => open && self.page > 0,
+            };
+            if !playable {
+                continue;
+            }
+            match input {
+                NotebookInput::Open => self.state = NotebookState::Opening,
+                NotebookInput::Close => self.state = NotebookState::Closing,
+                _ => {}
+            }
+            self.playing = Some(input);
+            return Some(input);
+        }
+        None
+    }
+
+    /// finishes the playing input, returns whether it turned the page forward
+    /// or back
+    fn finish(&mut self) -> Option<bool> {
+        self.playing_node = None;
+        match self.playing.take()? {
+            NotebookInput::Open => self.state = NotebookState::Open,
+            NotebookInput::Close => self.state = NotebookState::Closed,
+            NotebookInput::TurnForward => {
+                self.page += 1;
+                return Some(true);
+            }
+            NotebookInput::TurnBack => {
+                self.page -= 1;
+                return Some(false);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Clip {
+    node: AnimationNodeIndex,
+    handle: Handle<AnimationClip>,
+}
+
+/// The clips of the notebook model in its animation graph
+#[derive(Debug)]
+struct NotebookClips {
+    graph: Handle<AnimationGraph>,
+    turn_forward: Option<Clip>,
+    turn_back: Option<Clip>,
+    open: Option<Clip>,
+    close: Option<Clip>,
+}
+
+/// `clip`, or `reversed` played backwards if there isn't one
+fn clip_or_reversed<'a>(
+    clip: Option<&'a Clip>,
+    reversed: Option<&'a Clip>,
+) -> Option<(&'a Clip, bool)> {
+    clip.map(|clip| (clip, false))
+        .or_else(|| reversed.map(|clip| (clip, true)))
+}
+
+impl NotebookClips {
+    /// the clip played for `input` and whether it's played backwards
+    fn clip_for(&self, input: NotebookInput) -> Option<(&Clip, bool)> {
+        let (clip, reversed) = match input {
+            NotebookInput::TurnForward => (&self.turn_forward, &self.turn_back),
+            NotebookInput::TurnBack => (&self.turn_back, &self.turn_forward),
+            NotebookInput::Open => (&self.open, &self.close),
+            NotebookInput::Close => (&self.close, &self.open),
+        };
+        clip_or_reversed(clip.as_ref(), reversed.as_ref())
+    }
+
+    /// starts the clip for `input`, returns its node if there is one
+    fn play(
+        &self,
+        player: &mut AnimationPlayer,
+        input: NotebookInput,
+        animation_clips: &Assets<AnimationClip>,
+    ) -> Option<AnimationNodeIndex> {
+        let (clip, reversed) = self.clip_for(input)?;
+        player.stop_all();
+        let animation = player.start(clip.node);
+        if reversed {
+            let duration = animation_clips
+                .get(&clip.handle)
+                .map_or(0.0, AnimationClip::duration);
+            animation.set_speed(-1.0).seek_to(duration);
+        }
+        Some(clip.node)
+    }
+
+    /// holds the pose of the notebook while it's open or closed, which is
+    /// the first frame of the clip that closes or opens it
+    fn rest(
+        &self,
+        player: &mut AnimationPlayer,
+        state: NotebookState,
+        animation_clips: &Assets<AnimationClip>,
+    ) {
+        let leaving = match state {
+            NotebookState::Open => NotebookInput::Close,
+            NotebookState::Closed => NotebookInput::Open,
+            NotebookState::Opening | NotebookState::Closing => return,
+        };
+        if self.play(player, leaving, animation_clips).is_some() {
+            player.pause_all();
+        }
+    }
+}
+
+/// The animations of a notebook's model, the graph is made once the model
+/// loads
+#[derive(Component)]
+pub struct NotebookAnimations {
+    gltf: Handle<Gltf>,
+    clips: Option<NotebookClips>,
+}
+
+impl NotebookAnimations {
+    pub fn new(gltf: Handle<Gltf>) -> Self {
+        Self { gltf, clips: None }
+    }
+}
+
+/// makes the animation graph of each notebook once its model is loaded, then
+/// gives it to the animation player of the notebook as it's spawned
+pub fn setup_notebook_animations_once_loaded(
+    mut commands: Commands,
+    gltfs: Res<Assets<Gltf>>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    animation_clips: Res<Assets<AnimationClip>>,
+    mut player_query: Query<(Entity, &mut AnimationPlayer), Without<AnimationGraphHandle>>,
+    parent_query: Query<&ChildOf>,
+    mut notebook_query: Query<(&mut NotebookController, &mut NotebookAnimations)>,
+) {
+    for (_, mut animations) in &mut notebook_query {
+        if animations.clips.is_some() {
+            continue;
+        }
+        let Some(gltf) = gltfs.get(&animations.gltf) else {
+            continue;
+        };
+        let mut graph = AnimationGraph::new();
+        let mut add_clip = |handle: Option<&Handle<AnimationClip>>| {
+            let handle = handle?.clone();
+            let node = graph.add_clip(handle.clone(), 1.0, graph.root);
+            Some(Clip { node, handle })
+        };
+        let named = |name: &str| gltf.named_animations.get(name);
+        let unnamed = [TURN_FORWARD_CLIP, TURN_BACK_CLIP, OPEN_CLIP, CLOSE_CLIP]
+            .into_iter()
+            .all(|name| named(name).is_none());
+        let turn_forward = add_clip(named(TURN_FORWARD_CLIP));
+        let turn_back = add_clip(named(TURN_BACK_CLIP));
+        let open = add_clip(named(OPEN_CLIP));
+        let close = add_clip(named(CLOSE_CLIP).or(gltf.animations.first().filter(|_| unnamed)));
+        let clips = NotebookClips {
+            graph: graphs.add(graph),
+            turn_forward,
+            turn_back,
+            open,
+            close,
+        };
+        animations.clips = Some(clips);
+    }
+
+    for (entity, mut player) in &mut player_query {
+        // only players in a notebook's scene play its clips
+        let Some(notebook) = parent_query
+            .iter_ancestors(entity)
+            .find(|ancestor| notebook_query.contains(*ancestor))
+        else {
+            continue;
+        };
+        let Ok((mut controller, animations)) = notebook_query.get_mut(notebook) else {
+            continue;
+        };
+        let Some(clips) = &animations.clips else {
+            continue;
+        };
+        controller.player = Some(entity);
+        clips.rest(&mut player, controller.state, &animation_clips);
+        commands
+            .entity(entity)
+            .insert(AnimationGraphHandle(clips.graph.clone()));
+    }
+}
+
+/// plays the queued inputs of each notebook one after another
+#[allow(clippy::too_many_arguments)]
+pub fn notebook_animation_system(
+    mut input_reader: MessageReader<NotebookInput>,
+    mut controller_query: Query<(
+        Entity,
+        &mut NotebookController,
+        &NotebookAnimations,
+        Has<SelectedNotebook>,
+    )>,
+    mut player_query: Query<&mut AnimationPlayer>,
+    animation_clips: Res<Assets<AnimationClip>>,
+    notebook_pages: Res<NotebookPages>,
+    drawable_query: Query<(), PageFilter>,
+    mut turned_writer: MessageWriter<PageTurned>,
+    state: Res<State<NotebookState>>,
+    mut next_state: ResMut<NextState<NotebookState>>,
+) {
+    let inputs: Vec<_> = input_reader.read().copied().collect();
+    // only the selected notebook starts inputs, so only its pages count
+    let page_count = notebook_pages.count(drawable_query.iter().len());
+    for (notebook, mut controller, animations, selected) in &mut controller_query {
+        if selected {
+            for input in &inputs {
+                controller.queue(*input);
+            }
+            // the page was gone to some other way, e.g. from the page strip,
+            // or the notebook was just selected
+            if notebook_pages.is_changed() {
+                controller.page = notebook_pages.first_shown;
+            }
+        } else {
+            // the others only finish what they're playing
+            controller.queued.clear();
+        }
+        let mut player = controller
+            .player
+            .and_then(|player| player_query.get_mut(player).ok());
+        let clips = animations.clips.as_ref();
+
+        let finished = match (controller.playing_node, &player) {
+            (Some(node), Some(player)) => player
+                .animation(node)
+                .is_none_or(|animation| animation.is_finished()),
+            _ => true,
+        };
+        if finished {
+            // inputs without a clip are applied straight away
+            while controller.playing.is_some() || !controller.queued.is_empty() {
+                if let Some(forward) = controller.finish() {
+                    turned_writer.write(PageTurned {
+                        notebook,
+                        page: controller.page,
+                        forward,
+                    });
+                }
+                let Some(input) = controller.start_next(page_count) else {
+                    break;
+                };
+                if let (Some(clips), Some(player)) = (clips, &mut player) {
+                    controller.playing_node = clips.play(player, input, &animation_clips);
+                    if controller.playing_node.is_some() {
+                        break;
+                    }
+                }
+            }
+            if controller.playing.is_none() {
+                if let (Some(clips), Some(player)) = (clips, &mut player) {
+                    if !player.all_paused() {
+                        clips.rest(player, controller.state, &animation_clips);
+                    }
+                }
+            }
+        }
+
+        if selected && *state.get() != controller.state {
+            next_state.set(controller.state);
+        }
+    }
+}
+
+/// shows the page the selected notebook was turned to
+pub fn go_to_turned_page(
+    mut turned_reader: MessageReader<PageTurned>,
+    selected_query: Query<(), With<SelectedNotebook>>,
+    mut control_writer: MessageWriter<PageControl>,
+) {
+    for turned in turned_reader.read() {
+        if selected_query.contains(turned.notebook) {
+            control_writer.write(PageControl::GoTo(turned.page));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NotebookController, NotebookInput, NotebookState};
+
+    #[test]
+    fn turns_stay_within_the_pages() {
+        let mut controller = NotebookController::default();
+        controller.queue(NotebookInput::TurnBack);
+        controller.queue(NotebookInput::TurnForward);
+        // turning back from the first page is dropped
+        assert_eq!(controller.start_next(2), Some(NotebookInput::TurnForward));
+        assert_eq!(controller.finish(), Some(true));
+        assert_eq!(controller.page, 1);
+
+        controller.queue(NotebookInput::TurnForward);
+        assert_eq!(controller.start_next(2), None);
+        assert!(controller.queued.is_empty());
+    }
+
+    #[test]
+    fn inputs_are_queued_in_order() {
+        let mut controller = NotebookController::default();
+        for _ in 0..6 {
+            controller.queue(NotebookInput::TurnForward);
+        }
+        controller.queue(NotebookInput::Close);
+        let mut played = Vec::new();
+        while let Some(input) = controller.start_next(10) {
+            played.push(input);
+            controller.finish();
+        }
+        // the rest were dropped while the queue was full
+        assert_eq!(played, [NotebookInput::TurnForward; 4]);
+        assert_eq!(controller.page, 4);
+
+        controller.queue(NotebookInput::Close);
+        controller.queue(NotebookInput::TurnForward);
+        controller.queue(NotebookInput::Open);
+        assert_eq!(controller.start_next(10), Some(NotebookInput::Close));
+        assert_eq!(controller.finish(), None);
+        // a closed notebook can't be turned
+        assert_eq!(controller.start_next(10), Some(NotebookInput::Open));
+        controller.finish();
+        assert_eq!(controller.state, NotebookState::Open);
+    }
+
+    #[test]
+    fn opening_and_closing_go_through_transitions() {
+        let mut controller = NotebookController::default();
+        controller.queue(NotebookInput::Open);
+        // it's already open
+        assert_eq!(controller.start_next(1), None);
+
+        controller.queue(NotebookInput::Close);
+        controller.queue(NotebookInput::Open);
+        controller.start_next(1);
+        assert_eq!(controller.state, NotebookState::Closing);
+        controller.finish();
+        assert_eq!(controller.state, NotebookState::Closed);
+        controller.start_next(1);
+        assert_eq!(controller.state, NotebookState::Opening);
+        controller.finish();
+        assert_eq!(controller.state, NotebookState::Open);
+    }
+}
@@ -0,0 +1,331 @@
+//! Notebooks on the desk
+//!
+//! The desk has a few notebooks, one of them is selected. The selected one is
+//! the one drawn on, turned, saved and opened into, its pages are in the
+//! [`NotebookPages`] resource. The pages of the others are kept on them in a
+//! [`ShelvedNotebook`] until they're selected, they're saved all at once with
+//! [`SaveShelvedNotebooks`]. Clicking on a notebook selects it.
+
+use std::path::PathBuf;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::drawable::{
+    cursor_ray,
+    document::{
+        new_notebook_path, notebook_contents, NotebookDocument, NotebookPages, UnsavedChanges,
+    },
+    stroke::DrawableContent,
+    Cover, DrawableObject, InSelectedNotebook,
+};
+
+use super::animation::NotebookController;
+
+/// Marks the selected notebook
+#[derive(Component, Debug, Default)]
+pub struct SelectedNotebook;
+
+/// The pages of a notebook that isn't selected
+#[derive(Component, Debug, Default)]
+pub struct ShelvedNotebook {
+    pages: NotebookPages,
+    unsaved: bool,
+}
+
+impl ShelvedNotebook {
+    /// the pages that aren't shown on its drawables
+    pub fn pages(&self) -> &NotebookPages {
+        &self.pages
+    }
+
+    /// whether it had unsaved changes when another notebook was selected
+    pub fn unsaved(&self) -> bool {
+        self.unsaved
+    }
+}
+
+/// Message for selecting a notebook on the desk
+#[derive(Debug, Clone, Copy, Message)]
+pub struct SelectNotebook(pub Entity);
+
+/// Sent when another notebook was selected
+#[derive(Debug, Clone, Copy, Message)]
+pub struct NotebookSelected(pub Entity);
+
+/// Message for saving the notebooks that aren't selected and have unsaved
+/// changes, each to the document it was opened from or a new one
+#[derive(Debug, Clone, Copy, Message)]
+pub struct SaveShelvedNotebooks;
+
+/// Sent when a notebook that isn't selected was saved
+#[derive(Debug, Message)]
+pub struct ShelvedNotebookSaved {
+    pub notebook: Entity,
+    pub path: PathBuf,
+}
+
+/// the notebook `entity` is part of
+fn notebook_of(
+    entity: Entity,
+    parent_query: &Query<&ChildOf>,
+    notebook_query: &Query<(), With<NotebookController>>,
+) -> Option<Entity> {
+    std::iter::once(entity)
+        .chain(parent_query.iter_ancestors(entity))
+        .find(|ancestor| notebook_query.contains(*ancestor))
+}
+
+/// selects the notebook clicked on, if the mouse was pressed and released on
+/// the same one
+#[allow(clippy::too_many_arguments)]
+pub fn click_notebook_system(
+    buttons: Res<ButtonInput<MouseButton>>,
+    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut ray_cast: MeshRayCast,
+    parent_query: Query<&ChildOf>,
+    notebook_query: Query<(), With<NotebookController>>,
+    ui_query: Query<&Interaction>,
+    mut select_writer: MessageWriter<SelectNotebook>,
+    // the notebook the mouse was pressed on
+    mut pressed: Local<Option<Entity>>,
+) {
+    let pressed_now = buttons.just_pressed(MouseButton::Left);
+    let released = buttons.just_released(MouseButton::Left);
+    if !pressed_now && !released {
+        return;
+    }
+    let on_gui = ui_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    let notebook = cursor_ray(&window, *camera)
+        .filter(|_| !on_gui)
+        .and_then(|ray| {
+            let settings = MeshRayCastSettings::default()
+                .always_early_exit()
+                .with_visibility(RayCastVisibility::Visible);
+            let (entity, _) = ray_cast.cast_ray(ray, &settings).first()?;
+            notebook_of(*entity, &parent_query, &notebook_query)
+        });
+    if pressed_now {
+        *pressed = notebook;
+    } else if let Some(notebook) = notebook.filter(|notebook| pressed.take() == Some(*notebook)) {
+        select_writer.write(SelectNotebook(notebook));
+    }
+}
+
+/// swaps the pages of the selected notebook with the ones of the notebook
+/// being selected
+#[allow(clippy::too_many_arguments)]
+pub fn select_notebook_system(
+    mut commands: Commands,
+    mut reader: MessageReader<SelectNotebook>,
+    selected_query: Query<Entity, With<SelectedNotebook>>,
+    mut shelved_query: Query<&mut ShelvedNotebook>,
+    notebook_query: Query<(), With<NotebookController>>,
+    children_query: Query<&Children>,
+    drawable_query: Query<(), With<DrawableObject>>,
+    mut notebook_pages: ResMut<NotebookPages>,
+    mut unsaved: ResMut<UnsavedChanges>,
+    mut selected_writer: MessageWriter<NotebookSelected>,
+) {
+    let Some(SelectNotebook(notebook)) = reader.read().last().copied() else {
+        return;
+    };
+    let previous = selected_query.iter().next();
+    if previous == Some(notebook) || !notebook_query.contains(notebook) {
+        return;
+    }
+    let drawables = |notebook| {
+        children_query
+            .iter_descendants(notebook)
+            .filter(|entity| drawable_query.contains(*entity))
+            .collect::<Vec<_>>()
+    };
+
+    let shelved = ShelvedNotebook {
+        pages: std::mem::take(&mut *notebook_pages),
+        unsaved: unsaved.0,
+    };
+    if let Some(previous) = previous {
+        commands
+            .entity(previous)
+            .remove::<SelectedNotebook>()
+            .insert(shelved);
+        for drawable in drawables(previous) {
+            commands.entity(drawable).remove::<InSelectedNotebook>();
+        }
+    }
+    let selected = shelved_query
+        .get_mut(notebook)
+        .map(|mut shelved| std::mem::take(&mut *shelved))
+        .unwrap_or_default();
+    *notebook_pages = selected.pages;
+    unsaved.0 = selected.unsaved;
+    commands
+        .entity(notebook)
+        .remove::<ShelvedNotebook>()
+        .insert(SelectedNotebook);
+    for drawable in drawables(notebook) {
+        commands.entity(drawable).insert(InSelectedNotebook);
+    }
+    selected_writer.write(NotebookSelected(notebook));
+}
+
+pub fn save_shelved_notebooks(
+    mut reader: MessageReader<SaveShelvedNotebooks>,
+    mut shelved_query: Query<(Entity, &mut ShelvedNotebook)>,
+    children_query: Query<&Children>,
+    drawable_query: Query<(Entity, &DrawableContent, Has<Cover>), With<DrawableObject>>,
+    mut saved_writer: MessageWriter<ShelvedNotebookSaved>,
+) {
+    if reader.read().count() == 0 {
+        return;
+    }
+    for (notebook, mut shelved) in &mut shelved_query {
+        if !shelved.unsaved {
+            continue;
+        }
+        let (contents, cover) =
+            notebook_contents(notebook, &shelved.pages, &children_query, &drawable_query);
+        let path = shelved.pages.path.clone().unwrap_or_else(new_notebook_path);
+        let result = NotebookDocument::from_contents(&contents)
+            .and_then(|document| document.with_cover(cover.as_ref()))
+            .and_then(|document| document.save(&path));
+        match result {
+            Ok(()) => {
+                info!("Saved notebook to {}", path.display());
+                shelved.pages.path = Some(path.clone());
+                shelved.unsaved = false;
+                saved_writer.write(ShelvedNotebookSaved { notebook, path });
+            }
+            Err(error) => error!("Failed to save notebook: {error}"),
+        }
+    }
+}
+
+/// marks the drawables of the selected notebook as they're spawned
+pub fn mark_selected_drawables(
+    mut commands: Commands,
+    drawable_query: Query<Entity, Added<DrawableObject>>,
+    parent_query: Query<&ChildOf>,
+    selected_query: Query<(), With<SelectedNotebook>>,
+) {
+    for drawable in &drawable_query {
+        if parent_query
+            .iter_ancestors(drawable)
+            .any(|ancestor| selected_query.contains(ancestor))
+        {
+            commands.entity(drawable).insert(InSelectedNotebook);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use super::{
+        save_shelved_notebooks, select_notebook_system, NotebookSelected, SaveShelvedNotebooks,
+        SelectNotebook, SelectedNotebook, ShelvedNotebook, ShelvedNotebookSaved,
+    };
+    use crate::{
+        drawable::{
+            document::{NotebookDocument, NotebookPages, UnsavedChanges},
+            stroke::{DrawableContent, Stroke, StrokeId},
+            DrawableObject, InSelectedNotebook, PaintSettings,
+        },
+        notebook::animation::NotebookController,
+    };
+
+    #[test]
+    fn selecting_swaps_the_pages() {
+        let mut app = App::new();
+        app.add_message::<SelectNotebook>()
+            .add_message::<NotebookSelected>()
+            .init_resource::<NotebookPages>()
+            .init_resource::<UnsavedChanges>()
+            .add_systems(Update, select_notebook_system);
+        let mut spawn_notebook = |selected: bool| {
+            let notebook = app
+                .world_mut()
+                .spawn((NotebookController::default(), Transform::default()))
+                .id();
+            let page = app
+                .world_mut()
+                .spawn((
+                    DrawableObject,
+                    DrawableContent::new(Vec2::ONE),
+                    ChildOf(notebook),
+                ))
+                .id();
+            if selected {
+                app.world_mut()
+                    .entity_mut(notebook)
+                    .insert(SelectedNotebook);
+                app.world_mut().entity_mut(page).insert(InSelectedNotebook);
+            }
+            (notebook, page)
+        };
+        let (first, first_page) = spawn_notebook(true);
+        let (second, second_page) = spawn_notebook(false);
+        app.world_mut().resource_mut::<NotebookPages>().first_shown = 3;
+        app.world_mut().resource_mut::<UnsavedChanges>().0 = true;
+
+        app.world_mut().write_message(SelectNotebook(second));
+        app.update();
+        let world = app.world();
+        assert!(world.entity(second).contains::<SelectedNotebook>());
+        assert!(world.entity(second_page).contains::<InSelectedNotebook>());
+        assert!(!world.entity(first_page).contains::<InSelectedNotebook>());
+        assert_eq!(world.resource::<NotebookPages>().first_shown, 0);
+        assert!(!world.resource::<UnsavedChanges>().0);
+
+        // the first notebook gets its pages back
+        app.world_mut().write_message(SelectNotebook(first));
+        app.update();
+        let world = app.world();
+        assert!(world.entity(first).contains::<SelectedNotebook>());
+        assert!(world.entity(second).contains::<ShelvedNotebook>());
+        assert_eq!(world.resource::<NotebookPages>().first_shown, 3);
+        assert!(world.resource::<UnsavedChanges>().0);
+    }
+
+    #[test]
+    fn shelved_notebooks_are_saved_to_their_documents() {
+        let mut app = App::new();
+        app.add_message::<SaveShelvedNotebooks>()
+            .add_message::<ShelvedNotebookSaved>()
+            .add_systems(Update, save_shelved_notebooks);
+        let name = format!("elements_shelved_{}.ron", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let mut pages = NotebookPages::default();
+        pages.path = Some(path.clone());
+        let notebook = app
+            .world_mut()
+            .spawn(ShelvedNotebook {
+                pages,
+                unsaved: true,
+            })
+            .id();
+        let mut page = DrawableContent::new(Vec2::ONE);
+        page.push_painted(Stroke {
+            id: StrokeId(1),
+            points: Vec::new(),
+            paint_settings: PaintSettings::default(),
+            started_at: 0.0,
+        });
+        app.world_mut()
+            .spawn((DrawableObject, page, ChildOf(notebook)));
+
+        app.world_mut().write_message(SaveShelvedNotebooks);
+        app.update();
+        let document = NotebookDocument::load(&path).unwrap();
+        assert_eq!(document.pages[0].strokes.len(), 1);
+        let world = app.world();
+        assert!(!world.get::<ShelvedNotebook>(notebook).unwrap().unsaved());
+        let saved = world.resource::<Messages<ShelvedNotebookSaved>>();
+        assert_eq!(saved.len(), 1);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,96 +1,53 @@
+pub mod animation;
+pub mod desk;
 mod page;
 
-use std::time::Duration;
+use bevy::prelude::*;
 
-use bevy::{gltf::GltfMeshName, prelude::*};
-
-use crate::{
-    drawable::Drawable,
-    scene_hook::{HookedSceneBundle, SceneHook},
-};
+use crate::{config::Settings, scene_hook::SceneMappingHook};
+use animation::{NotebookAnimations, NotebookController, NotebookInput};
+use desk::SelectedNotebook;
 
 const NOTEBOOK_PATH: &str = "models/notebook.glb";
+/// which components go on which parts of the notebook model
+const NOTEBOOK_MAPPING_PATH: &str = "scenes/notebook.mapping.ron";
 
-pub fn add_notebook_load(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut graphs: ResMut<Assets<AnimationGraph>>,
-) {
-    let scene = asset_server.load(GltfAssetLabel::Scene(0).from_asset(NOTEBOOK_PATH));
-    // commands.spawn(SceneRoot(scene));
-    commands.spawn(HookedSceneBundle {
-        scene: SceneRoot(scene),
-        hook: SceneHook::new(|entity, cmds| {
-            match entity.get::<GltfMeshName>().map(|x| x.0.as_str()) {
-                Some("page_mesh") => cmds.insert(Drawable::default()),
-                _ => cmds,
-            };
-        }),
-    });
-
-    // add animations
-    let (graph, node_indices) = AnimationGraph::from_clips([
-        asset_server.load(GltfAssetLabel::Animation(0).from_asset(NOTEBOOK_PATH))
-    ]);
-
-    let graph_handle = graphs.add(graph);
-    commands.insert_resource(NotebookAnimations {
-        animations: node_indices,
-        graph: graph_handle,
-    });
-}
-
-pub fn setup_notebook_animations_once_loaded(
-    mut commands: Commands,
-    animations: Res<NotebookAnimations>,
-    mut anim_players: Query<(Entity, &mut AnimationPlayer), Added<AnimationPlayer>>,
-) {
-    // when animation player is added, play notebook animations on the entity
-    for (entity, mut player) in &mut anim_players {
-        let mut transitions = AnimationTransitions::new();
-
-        transitions
-            .play(&mut player, animations.animations[0], Duration::ZERO)
-            .pause();
+/// Number of notebooks on the desk
+const DESK_NOTEBOOKS: usize = 3;
+/// Distance between the notebooks on the desk, wider than an open notebook
+const NOTEBOOK_SPACING: f32 = 30.0;
 
-        commands
-            .entity(entity)
-            .insert(AnimationGraphHandle(animations.graph.clone()))
-            .insert(transitions);
+/// spawns the notebooks in a row on the desk, the first one is selected
+pub fn add_notebook_load(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let scene = asset_server.load(GltfAssetLabel::Scene(0).from_asset(NOTEBOOK_PATH));
+    let mapping = asset_server.load(NOTEBOOK_MAPPING_PATH);
+    for index in 0..DESK_NOTEBOOKS {
+        let mut notebook = commands.spawn((
+            SceneRoot(scene.clone()),
+            SceneMappingHook(mapping.clone()),
+            Transform::from_xyz(index as f32 * NOTEBOOK_SPACING, 0.0, 0.0),
+            NotebookController::default(),
+            // the animation graph is made once the model is loaded
+            NotebookAnimations::new(asset_server.load(NOTEBOOK_PATH)),
+        ));
+        if index == 0 {
+            notebook.insert(SelectedNotebook);
+        }
     }
 }
 
-#[derive(Resource)]
-pub struct NotebookAnimations {
-    animations: Vec<AnimationNodeIndex>,
-    graph: Handle<AnimationGraph>,
-}
-
+/// The turn page key turns forward, and back with Shift held
 pub fn keyboard_animation_control(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut animation_players: Query<(&mut AnimationPlayer, &mut AnimationTransitions)>,
-    // animations: Res<Animations>,
-    // mut current_animation: Local<usize>,
+    settings: Res<Settings>,
+    mut input_writer: MessageWriter<NotebookInput>,
 ) {
-    for (mut player, mut _transitions) in &mut animation_players {
-        let Some((&playing_animation_index, _)) = player.playing_animations().next() else {
-            continue;
-        };
-
-        if keyboard_input.just_pressed(KeyCode::Space) {
-            let playing_animation_option = player.animation_mut(playing_animation_index);
-
-            if let Some(playing_animation) = playing_animation_option {
-                if playing_animation.is_paused() {
-                    playing_animation.resume();
-                }
-                if playing_animation.is_finished() {
-                    let current_time = playing_animation.seek_time();
-                    playing_animation.replay();
-                    playing_animation.set_seek_time(current_time);
-                }
-                playing_animation.set_speed(-playing_animation.speed());
-            }
-        }
+    if keyboard_input.just_pressed(settings.key_bindings.turn_page) {
+        let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        input_writer.write(if shift {
+            NotebookInput::TurnBack
+        } else {
+            NotebookInput::TurnForward
+        });
     }
 }
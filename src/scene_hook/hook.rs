@@ -7,10 +7,13 @@
 use bevy::ecs::{
     component::Component,
     entity::Entity,
+    error::BevyError,
+    message::Message,
     prelude::{Without, World},
     system::{Commands, EntityCommands, Query, Res},
     world::EntityRef,
 };
+use bevy::log::error;
 use bevy::scene::{SceneInstance, SceneSpawner};
 
 /// Marker Component for scenes that were hooked.
@@ -18,6 +21,38 @@ use bevy::scene::{SceneInstance, SceneSpawner};
 #[non_exhaustive]
 pub struct SceneHooked;
 
+/// Message written once the hook of a scene ran on every entity of the scene.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct SceneHookCompleted {
+    /// The entity holding the [`SceneHook`].
+    pub root: Entity,
+}
+
+/// Message written when a fallible hook returns an error for an entity.
+#[derive(Message, Debug)]
+pub struct SceneHookFailed {
+    /// The entity holding the [`SceneHook`].
+    pub root: Entity,
+    /// The scene entity the hook failed on.
+    pub entity: Entity,
+    pub error: BevyError,
+}
+
+impl SceneHookFailed {
+    /// Logs the error and queues the message.
+    pub(super) fn report(root: Entity, entity: Entity, error: BevyError, cmds: &mut Commands) {
+        error!("Scene hook failed for {entity} in scene {root}: {error}");
+        cmds.write_message(SceneHookFailed {
+            root,
+            entity,
+            error,
+        });
+    }
+}
+
+type HookFn =
+    dyn Fn(&EntityRef, &mut EntityCommands) -> Result<(), BevyError> + Send + Sync + 'static;
+
 /// Add this as a component to any entity to run `hook`
 /// when the scene is loaded.
 ///
@@ -65,7 +100,7 @@ pub struct SceneHooked;
 /// ```
 #[derive(Component)]
 pub struct SceneHook {
-    hook: Box<dyn Fn(&EntityRef, &mut EntityCommands) + Send + Sync + 'static>,
+    hook: Box<HookFn>,
 }
 impl SceneHook {
     /// Add a hook to a scene, to run for each entity when the scene is
@@ -105,6 +140,20 @@ impl SceneHook {
     /// }
     /// ```
     pub fn new<F: Fn(&EntityRef, &mut EntityCommands) + Send + Sync + 'static>(hook: F) -> Self {
+        Self::new_fallible(move |entity, cmds| {
+            hook(entity, cmds);
+            Ok(())
+        })
+    }
+
+    /// Like [`SceneHook::new`], but the hook can fail.
+    ///
+    /// Errors don't stop the hook from running on the other entities of the
+    /// scene, they are logged and written as [`SceneHookFailed`] messages.
+    pub fn new_fallible<F>(hook: F) -> Self
+    where
+        F: Fn(&EntityRef, &mut EntityCommands) -> Result<(), BevyError> + Send + Sync + 'static,
+    {
         Self {
             hook: Box::new(hook),
         }
@@ -113,22 +162,30 @@ impl SceneHook {
 
 /// Run once [`SceneHook`]s added to [`SceneRoot`](crate::SceneRoot) or
 /// [`DynamicSceneRoot`](crate::DynamicSceneRoot) when the scenes are loaded.
+///
+/// Hooks only run once the whole scene instance is ready, so each entity is
+/// hooked exactly once, followed by a [`SceneHookCompleted`] message.
 pub fn run_hooks(
     unloaded_instances: Query<(Entity, &SceneInstance, &SceneHook), Without<SceneHooked>>,
     scene_manager: Res<SceneSpawner>,
     world: &World,
     mut cmds: Commands,
 ) {
-    for (entity, instance, hooked) in unloaded_instances.iter() {
-        if scene_manager.instance_is_ready(**instance) {
-            cmds.entity(entity).insert(SceneHooked);
+    for (root, instance, hooked) in unloaded_instances.iter() {
+        if !scene_manager.instance_is_ready(**instance) {
+            continue;
         }
+        cmds.entity(root).insert(SceneHooked);
+
         let entities = scene_manager
             .iter_instance_entities(**instance)
-            .chain(std::iter::once(entity));
+            .chain(std::iter::once(root));
         for entity_ref in entities.filter_map(|e| world.get_entity(e).ok()) {
             let mut cmd = cmds.entity(entity_ref.id());
-            (hooked.hook)(&entity_ref, &mut cmd);
+            if let Err(error) = (hooked.hook)(&entity_ref, &mut cmd) {
+                SceneHookFailed::report(root, entity_ref.id(), error, &mut cmds);
+            }
         }
+        cmds.write_message(SceneHookCompleted { root });
     }
 }
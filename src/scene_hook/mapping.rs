@@ -0,0 +1,275 @@
+//! Data driven mapping from glTF names to components.
+//!
+//! Instead of writing a [`SceneHook`](super::SceneHook) closure for every
+//! scene, a [`ComponentMapping`] asset (a `*.mapping.ron` file) lists which
+//! components to insert on which mesh or node. Artists can also attach
+//! components straight from Blender by adding an `elements` custom property,
+//! which is exported as glTF extras, containing a RON list of
+//! [`MappedComponent`]s, e.g. `[Drawable(resolution: 2048)]`.
+//!
+//! ```ron
+//! (
+//!     meshes: {
+//!         "page_mesh": [Drawable(resolution: 1024)],
+//!     },
+//!     nodes: {
+//!         "neutral_bone": [Hidden],
+//!     },
+//! )
+//! ```
+
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    ecs::{system::EntityCommands, world::EntityRef},
+    gltf::{GltfExtras, GltfMeshExtras, GltfMeshName},
+    prelude::*,
+    scene::{SceneInstance, SceneSpawner},
+};
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+
+use crate::drawable::{Cover, DefaultResolution, Drawable};
+
+use super::{SceneHookCompleted, SceneHookFailed, SceneHooked};
+
+/// Name of the glTF extras property holding a RON list of [`MappedComponent`]s.
+pub const EXTRAS_KEY: &str = "elements";
+
+/// Components to insert on scene entities, keyed by glTF name.
+#[derive(Asset, TypePath, Debug, Default, Deserialize)]
+pub struct ComponentMapping {
+    /// Keyed by the glTF mesh name ([`GltfMeshName`]).
+    #[serde(default)]
+    pub meshes: HashMap<String, Vec<MappedComponent>>,
+    /// Keyed by the glTF node name ([`Name`]).
+    #[serde(default)]
+    pub nodes: HashMap<String, Vec<MappedComponent>>,
+}
+
+impl ComponentMapping {
+    /// All the components that apply to `entity`, from the mapping file and
+    /// from the entity's own glTF extras.
+    ///
+    /// Invalid extras don't stop the other components from being returned,
+    /// their errors are returned alongside them.
+    pub fn components_for(&self, entity: &EntityRef) -> (Vec<MappedComponent>, Vec<MappingError>) {
+        let mut components = Vec::new();
+        let mut errors = Vec::new();
+
+        if let Some(mesh_name) = entity.get::<GltfMeshName>() {
+            if let Some(mapped) = self.meshes.get(mesh_name.0.as_str()) {
+                components.extend(mapped.iter().cloned());
+            }
+        }
+        if let Some(name) = entity.get::<Name>() {
+            if let Some(mapped) = self.nodes.get(name.as_str()) {
+                components.extend(mapped.iter().cloned());
+            }
+        }
+
+        let extras = [
+            entity.get::<GltfExtras>().map(|x| x.value.as_str()),
+            entity.get::<GltfMeshExtras>().map(|x| x.value.as_str()),
+        ];
+        for extras in extras.into_iter().flatten() {
+            match components_from_extras(extras) {
+                Ok(from_extras) => components.extend(from_extras),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        (components, errors)
+    }
+}
+
+/// A component that can be inserted from a [`ComponentMapping`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum MappedComponent {
+    /// Makes the mesh drawable, see [`Drawable`]. Without a resolution it
+    /// uses the [`DefaultResolution`].
+    Drawable {
+        #[serde(default, deserialize_with = "some_resolution")]
+        resolution: Option<usize>,
+    },
+    /// Makes the mesh the notebook's cover, a drawable that isn't a page.
+    Cover {
+        #[serde(default, deserialize_with = "some_resolution")]
+        resolution: Option<usize>,
+    },
+    /// Hides the entity.
+    Hidden,
+}
+
+/// lets resolutions be written without `Some`
+fn some_resolution<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    usize::deserialize(deserializer).map(Some)
+}
+
+impl MappedComponent {
+    pub fn insert(&self, cmds: &mut EntityCommands, default_resolution: DefaultResolution) {
+        match self {
+            MappedComponent::Drawable { resolution } => {
+                cmds.insert(Drawable::new(resolution.unwrap_or(default_resolution.0)));
+            }
+            MappedComponent::Cover { resolution } => {
+                let drawable = Drawable::new(resolution.unwrap_or(default_resolution.0));
+                cmds.insert((drawable, Cover));
+            }
+            MappedComponent::Hidden => {
+                cmds.insert(Visibility::Hidden);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MappingError {
+    #[error("extras are not valid json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("`{EXTRAS_KEY}` is not a string")]
+    NotAString,
+    #[error("could not parse components: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+/// Reads the components listed under [`EXTRAS_KEY`] in a glTF extras json object.
+fn components_from_extras(extras: &str) -> Result<Vec<MappedComponent>, MappingError> {
+    let value: serde_json::Value = serde_json::from_str(extras)?;
+    match value.get(EXTRAS_KEY) {
+        None => Ok(Vec::new()),
+        Some(serde_json::Value::String(components)) => Ok(ron::from_str(components)?),
+        Some(_) => Err(MappingError::NotAString),
+    }
+}
+
+/// Add this next to a [`SceneRoot`] to apply a [`ComponentMapping`] once both
+/// the scene and the mapping are loaded.
+#[derive(Component, Debug)]
+pub struct SceneMappingHook(pub Handle<ComponentMapping>);
+
+/// Applies [`SceneMappingHook`]s to scenes that have finished loading.
+///
+/// Like [`run_hooks`](super::run_hooks), this writes a [`SceneHookCompleted`]
+/// once done and reports invalid extras as [`SceneHookFailed`].
+pub fn run_mapping_hooks(
+    unloaded_instances: Query<(Entity, &SceneInstance, &SceneMappingHook), Without<SceneHooked>>,
+    scene_manager: Res<SceneSpawner>,
+    mappings: Res<Assets<ComponentMapping>>,
+    default_resolution: Option<Res<DefaultResolution>>,
+    world: &World,
+    mut cmds: Commands,
+) {
+    for (root, instance, hook) in unloaded_instances.iter() {
+        if !scene_manager.instance_is_ready(**instance) {
+            continue;
+        }
+        let Some(mapping) = mappings.get(&hook.0) else {
+            continue;
+        };
+        cmds.entity(root).insert(SceneHooked);
+        let default_resolution = default_resolution.as_deref().copied().unwrap_or_default();
+
+        let entities = scene_manager
+            .iter_instance_entities(**instance)
+            .chain(std::iter::once(root));
+        for entity_ref in entities.filter_map(|e| world.get_entity(e).ok()) {
+            let (components, errors) = mapping.components_for(&entity_ref);
+            let mut cmd = cmds.entity(entity_ref.id());
+            for component in components {
+                component.insert(&mut cmd, default_resolution);
+            }
+            for error in errors {
+                SceneHookFailed::report(root, entity_ref.id(), error.into(), &mut cmds);
+            }
+        }
+        cmds.write_message(SceneHookCompleted { root });
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ComponentMappingLoaderError {
+    #[error("could not read mapping file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse mapping file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+/// Loads `*.mapping.ron` files as [`ComponentMapping`]s.
+#[derive(Default)]
+pub struct ComponentMappingLoader;
+
+impl AssetLoader for ComponentMappingLoader {
+    type Asset = ComponentMapping;
+    type Settings = ();
+    type Error = ComponentMappingLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["mapping.ron"]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{components_from_extras, ComponentMapping, MappedComponent};
+
+    #[test]
+    fn parse_mapping_file() {
+        let mapping: ComponentMapping = ron::from_str(
+            r#"(
+                meshes: { "page_mesh": [Drawable(resolution: 512)] },
+                nodes: { "bone": [Hidden, Drawable()] },
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            mapping.meshes["page_mesh"],
+            vec![MappedComponent::Drawable {
+                resolution: Some(512)
+            }]
+        );
+        assert_eq!(
+            mapping.nodes["bone"],
+            vec![
+                MappedComponent::Hidden,
+                MappedComponent::Drawable { resolution: None }
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_extras() {
+        let components =
+            components_from_extras(r#"{"elements": "[Drawable(resolution: 2048)]"}"#).unwrap();
+        assert_eq!(
+            components,
+            vec![MappedComponent::Drawable {
+                resolution: Some(2048)
+            }]
+        );
+
+        assert_eq!(
+            components_from_extras(r#"{"elements": "[Cover(resolution: 512)]"}"#).unwrap(),
+            vec![MappedComponent::Cover {
+                resolution: Some(512)
+            }]
+        );
+        assert!(components_from_extras(r#"{"other": 1}"#)
+            .unwrap()
+            .is_empty());
+        assert!(components_from_extras(r#"{"elements": 1}"#).is_err());
+    }
+}
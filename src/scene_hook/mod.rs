@@ -14,11 +14,16 @@
 //!
 //! The respective documentation of [`SceneHook`] and [`reload::Hook`] for
 //! usage examples.
+//!
+//! For the common case of inserting components by name, the [`mapping`]
+//! module reads the mapping from a file instead of code.
 mod hook;
+pub mod mapping;
 
 use bevy::{ecs::system::SystemParam, prelude::*, scene::scene_spawner_system};
 
-pub use hook::{run_hooks, SceneHook, SceneHooked};
+pub use hook::{run_hooks, SceneHook, SceneHookCompleted, SceneHookFailed, SceneHooked};
+pub use mapping::{run_mapping_hooks, ComponentMapping, SceneMappingHook};
 
 /// Bundle a [`SceneHook`] with the standard [`SceneRoot`] components.
 ///
@@ -69,11 +74,135 @@ pub enum Systems {
 pub struct HookPlugin;
 impl Plugin for HookPlugin {
     fn build(&self, app: &mut App) {
+        app.init_asset::<ComponentMapping>()
+            .init_asset_loader::<mapping::ComponentMappingLoader>();
+        app.add_message::<SceneHookCompleted>()
+            .add_message::<SceneHookFailed>();
         app.add_systems(
             SpawnScene,
-            run_hooks
+            (run_hooks, run_mapping_hooks)
                 .in_set(Systems::SceneHookRunner)
                 .after(scene_spawner_system),
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::{gltf::GltfExtras, prelude::*, scene::ScenePlugin};
+
+    use super::{
+        mapping::MappedComponent, ComponentMapping, HookPlugin, SceneHook, SceneHookCompleted,
+        SceneHookFailed, SceneMappingHook,
+    };
+
+    #[derive(Component, Default)]
+    struct Hooked(usize);
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            ScenePlugin,
+            HookPlugin,
+        ));
+        app
+    }
+
+    fn scene(app: &mut App, spawn: impl FnOnce(&mut World)) -> Handle<Scene> {
+        let mut world = World::new();
+        spawn(&mut world);
+        app.world_mut()
+            .resource_mut::<Assets<Scene>>()
+            .add(Scene::new(world))
+    }
+
+    fn messages<M: Message>(app: &mut App) -> usize {
+        app.world_mut()
+            .resource_mut::<Messages<M>>()
+            .drain()
+            .count()
+    }
+
+    #[test]
+    fn hooks_run_once() {
+        let mut app = app();
+        let scene = scene(&mut app, |world| {
+            world.spawn(Name::new("page"));
+        });
+        app.world_mut().spawn((
+            SceneRoot(scene),
+            SceneHook::new(|entity, cmds| {
+                if entity.contains::<Name>() {
+                    let count = entity.get::<Hooked>().map_or(0, |hooked| hooked.0);
+                    cmds.insert(Hooked(count + 1));
+                }
+            }),
+        ));
+
+        let mut completed = 0;
+        for _ in 0..5 {
+            app.update();
+            completed += messages::<SceneHookCompleted>(&mut app);
+        }
+        assert_eq!(completed, 1);
+        let mut hooked = app.world_mut().query::<&Hooked>();
+        let counts: Vec<_> = hooked.iter(app.world()).map(|hooked| hooked.0).collect();
+        assert_eq!(counts, [1]);
+    }
+
+    #[test]
+    fn failing_hooks_are_reported() {
+        let mut app = app();
+        let scene = scene(&mut app, |world| {
+            world.spawn(Name::new("page"));
+        });
+        app.world_mut().spawn((
+            SceneRoot(scene),
+            SceneHook::new_fallible(|entity, _| {
+                if entity.contains::<Name>() {
+                    return Err("no pages here".into());
+                }
+                Ok(())
+            }),
+        ));
+
+        app.update();
+        app.update();
+        assert_eq!(messages::<SceneHookFailed>(&mut app), 1);
+        assert_eq!(messages::<SceneHookCompleted>(&mut app), 1);
+    }
+
+    #[test]
+    fn invalid_extras_keep_the_mapped_components() {
+        let mut app = app();
+        let scene = scene(&mut app, |world| {
+            world.spawn((
+                Name::new("page"),
+                GltfExtras {
+                    value: r#"{"elements": "[Drawable("#.to_string(),
+                },
+            ));
+        });
+        let mut mapping = ComponentMapping::default();
+        mapping
+            .nodes
+            .insert("page".to_string(), vec![MappedComponent::Hidden]);
+        let mapping = app
+            .world_mut()
+            .resource_mut::<Assets<ComponentMapping>>()
+            .add(mapping);
+        app.world_mut()
+            .spawn((SceneRoot(scene), SceneMappingHook(mapping)));
+
+        app.update();
+        app.update();
+        assert_eq!(messages::<SceneHookFailed>(&mut app), 1);
+        assert_eq!(messages::<SceneHookCompleted>(&mut app), 1);
+        let mut hidden = app.world_mut().query::<(&Name, &Visibility)>();
+        let (name, visibility) = hidden.single(app.world()).unwrap();
+        assert_eq!(name.as_str(), "page");
+        assert_eq!(*visibility, Visibility::Hidden);
+    }
+}